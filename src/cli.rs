@@ -1,19 +1,45 @@
-use crate::config::{LocationRepo, Name};
-use clap::{Args as ClapArgs, Parser as ClapParser, Subcommand as ClapSubcommand};
+use crate::config::{LocationRepo, Name, Progress};
+use clap::{
+    ArgGroup, Args as ClapArgs, Parser as ClapParser, Subcommand as ClapSubcommand,
+    ValueEnum as ClapValueEnum,
+};
 use std::path::{Path, PathBuf};
 
 #[derive(ClapParser, Debug)]
-#[command(version, about)]
+#[command(version, about, after_help = crate::exit::help())]
 pub struct Args {
-    /// Set configuration file.
-    #[arg(short, long = "config", default_value = "aresticrat.toml")]
-    config_file: PathBuf,
+    /// Set configuration file (repeatable).
+    ///
+    /// Multiple files are merged in the specified order: settings of later
+    /// files override those of earlier ones, e.g. of a base file by host
+    /// specific overrides. The first file determines the default state file
+    /// and the lock of the configuration.
+    #[arg(
+        short,
+        long = "config",
+        value_name = "CONFIG_FILE",
+        default_value = "aresticrat.toml"
+    )]
+    config_files: Vec<PathBuf>,
+    /// Override a setting of the configuration (repeatable).
+    ///
+    /// The key is the dotted path of the setting, e.g. repos.local.path or
+    /// options.backup.exclude-caches. Overrides take precedence over the
+    /// configuration files and environment variables.
+    #[arg(long = "set", value_name = "KEY=VALUE", value_parser = parse_setting)]
+    settings: Vec<(String, String)>,
     /// Set working directory.
     #[arg(long = "wd")]
     working_dir: Option<PathBuf>,
     /// Do not output to stdout/stderr (doesn't affect logging).
     #[arg(short, long)]
     quiet: bool,
+    /// Only output results, not the output of restic and hooks.
+    ///
+    /// Prints the result of every location and repository and the final
+    /// summary, e.g. to keep the mails of cron jobs short.
+    #[arg(short, long, conflicts_with = "quiet")]
+    summary: bool,
     /// Print more information (doesn't affect logging) (repeatable).
     ///
     /// Specify multiple times to increase verbosity step by step:
@@ -21,6 +47,33 @@ pub struct Args {
     /// debug -> trace.
     #[arg(short, long, action = clap::ArgAction::Count)]
     verbose: u8,
+    /// Set output format.
+    ///
+    /// The JSON format replaces the human-readable output with one JSON object
+    /// per line (events, warnings, errors and a final summary). Output of
+    /// restic is suppressed.
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    output: OutputFormat,
+    /// Set when the output is colored.
+    ///
+    /// With auto, the output is colored on terminals, unless the NO_COLOR
+    /// environment variable is set.
+    #[arg(long, value_enum, default_value_t = ColorMode::Auto)]
+    color: ColorMode,
+    /// Set how restic reports the progress of running commands.
+    ///
+    /// Overrides the progress setting of the configuration file: live prints
+    /// an update every second, periodic one every minute and off none.
+    #[arg(long, value_enum)]
+    progress: Option<Progress>,
+    /// Do not write or delete any data and do not run hooks, just show what
+    /// would be done.
+    ///
+    /// Applies to every operation of the command, e.g. to every step of run.
+    /// Hooks are printed instead of run. Commands that would write data, but
+    /// have no dry run (e.g. check or copy), are skipped.
+    #[arg(short = 'n', long)]
+    dry_run: bool,
     /// Additionally read environment variables from the specified file
     /// (repeatable).
     ///
@@ -28,47 +81,262 @@ pub struct Args {
     /// overwrite those in earlier files.
     #[arg(long = "env", value_name = "ENV_FILE")]
     env_files: Vec<PathBuf>,
+    /// Additionally write the output of restic and hooks to the specified
+    /// file.
+    ///
+    /// The placeholders {{command}} and {{timestamp}} are replaced by the name
+    /// of the command and the start time of the run.
+    #[arg(long, value_name = "LOG_FILE")]
+    log_file: Option<String>,
+    /// Write a report of the run to the specified file.
+    ///
+    /// The report lists the result, duration and added data of every location
+    /// and repository. Files ending in .html are written as HTML page, all
+    /// others as JSON. The placeholders {{command}} and {{timestamp}} are
+    /// replaced as for --log-file.
+    #[arg(long, value_name = "REPORT_FILE")]
+    report: Option<String>,
+    /// Additionally report log messages to the Windows Event Log.
+    ///
+    /// Messages are written to the application log with the source name
+    /// "aresticrat". The level can be adjusted with the ARESTICRAT_EVENTLOG
+    /// environment variable (default: info).
+    #[cfg(windows)]
+    #[arg(long)]
+    event_log: bool,
     #[command(subcommand)]
     command: Command,
 }
 
 impl Args {
+    /// The first configuration file.
     pub fn config_file(&self) -> &Path {
-        &self.config_file
+        &self.config_files[0]
+    }
+    pub fn config_files(&self) -> &[PathBuf] {
+        &self.config_files
+    }
+    pub fn settings(&self) -> &[(String, String)] {
+        &self.settings
     }
     pub fn quiet(&self) -> bool {
         self.quiet
     }
+    pub fn summary(&self) -> bool {
+        self.summary
+    }
     pub fn verbose(&self) -> u8 {
         self.verbose
     }
+    pub fn output(&self) -> OutputFormat {
+        self.output
+    }
+    pub fn color(&self) -> ColorMode {
+        self.color
+    }
+    pub fn progress(&self) -> Option<Progress> {
+        self.progress
+    }
+    pub fn dry_run(&self) -> bool {
+        self.dry_run
+    }
     pub fn working_dir(&self) -> Option<&Path> {
         self.working_dir.as_deref()
     }
     pub fn env_files(&self) -> &[PathBuf] {
         &self.env_files
     }
+    pub fn log_file(&self) -> Option<&str> {
+        self.log_file.as_deref()
+    }
+    pub fn report(&self) -> Option<&str> {
+        self.report.as_deref()
+    }
+    #[cfg(windows)]
+    pub fn event_log(&self) -> bool {
+        self.event_log
+    }
     pub fn command(&self) -> &Command {
         &self.command
     }
+    pub fn command_mut(&mut self) -> &mut Command {
+        &mut self.command
+    }
+}
+
+#[derive(ClapValueEnum, Clone, Copy, Debug, Eq, PartialEq)]
+pub enum OutputFormat {
+    Text,
+    Json,
+}
+
+#[derive(ClapValueEnum, Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ColorMode {
+    Auto,
+    Always,
+    Never,
+}
+
+fn parse_setting(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((key, value)) if !key.trim().is_empty() => {
+            Ok((key.trim().to_string(), value.to_string()))
+        }
+        _ => Err("expected KEY=VALUE".to_string()),
+    }
 }
 
 #[derive(ClapSubcommand, Debug)]
 pub enum Command {
     /// Create a new backup of configured locations.
     Backup(BackupArgs),
+    /// Check the integrity of the repositories of configured locations.
+    Check(CheckArgs),
+    /// Copy the snapshots of configured locations to another repository.
+    Copy(CopyArgs),
+    /// Show the configuration.
+    #[command(subcommand)]
+    Config(ConfigCommand),
+    /// Control a running daemon: trigger operations, show its status and
+    /// pause or resume its schedules.
+    ///
+    /// Requires the control address of the daemon settings.
+    #[command(subcommand)]
+    Ctl(CtlCommand),
+    /// Show which scheduled operations of configured locations are pending
+    /// and optionally run them.
+    ///
+    /// An operation is pending if its schedule or interval has elapsed since
+    /// its last run, as recorded in the state file: the backup of a location
+    /// and the check and prune of its repositories. With --run, the pending
+    /// operations run, e.g. to catch up after a laptop has been asleep.
+    Due(DueArgs),
+    /// Stay resident and run operations according to the schedules of
+    /// configured locations.
+    Daemon,
+    /// Restore the latest snapshot of a configured location into a temporary
+    /// directory and compare the restored files with the live ones.
+    ///
+    /// Files that have not been modified since the snapshot must be identical.
+    /// Files modified or deleted since are only counted. The restored files
+    /// are removed afterwards, unless a target directory is given.
+    Drill(DrillArgs),
+    /// Estimate what a backup of configured locations would add to their
+    /// repositories, without writing any data.
+    Estimate(EstimateArgs),
     /// Run a native restic command for a configured repository.
     Exec(ExecArgs),
+    /// Show whether a backup of a configured location would include paths and
+    /// which exclude rule of its backup settings excludes them.
+    ExplainExcludes(ExplainExcludesArgs),
     /// Remove snapshots of configured locations from their repositories.
     Forget(ForgetArgs),
-    /// Validate the configuration file and test access to configured
-    /// repositories.
+    /// Maintain configured repositories: remove stale locks, check the
+    /// integrity, prune if due and clean up the cache.
+    ///
+    /// The steps of a repository stop at the first failure, so that a damaged
+    /// repository is not pruned.
+    Maintenance(MaintenanceArgs),
+    /// Pin a snapshot of a configured location, so that forget keeps it.
+    ///
+    /// The snapshot is tagged with a pin tag. Every forget keeps the snapshots
+    /// with one of the tags of the pin-tags setting, as long as its location
+    /// has a keep policy.
+    Pin(PinArgs),
+    /// Run the steps of a configured pipeline (e.g. backup, forget and check)
+    /// for configured locations.
+    ///
+    /// Without a pipeline, the default maintenance cycle runs: a backup of
+    /// every location, followed by a forget of the locations with a keep
+    /// policy and a check of the repositories whose check interval has
+    /// elapsed. Later steps run even if earlier ones failed. The exit status
+    /// reflects all failures.
+    ///
+    /// Every backup step is followed by the copy of the new snapshots to the
+    /// repositories of the replicate-to settings.
+    Run(RunArgs),
+    /// Manage repository keys in the keyring of the operating system.
+    #[command(subcommand)]
+    Key(KeyCommand),
+    /// Register or show the schedules of configured locations for the
+    /// scheduler of the operating system.
+    #[command(subcommand)]
+    Schedule(ScheduleCommand),
+    /// Show the age of the latest snapshot of configured locations.
+    ///
+    /// Exits with a non-zero status if a snapshot is older than the configured
+    /// maximum age.
+    Status(StatusArgs),
+    /// Monitor the paths of configured locations and create a backup when
+    /// files change.
+    ///
+    /// A backup starts after no further changes have been detected for the
+    /// quiet period, but not earlier than the minimum interval after the
+    /// previous backup of the location.
+    Watch(WatchArgs),
+    /// Validate the configuration file, test access to configured
+    /// repositories and check configured locations.
+    ///
+    /// The checks of a location cover its paths, exclude files, repositories
+    /// and the programs of its commands and hooks.
     Verify(VerifyArgs),
     /// Show copyright and license information.
     #[command(alias = "licenses")]
     License,
 }
 
+impl Command {
+    /// Turns the command into a dry run. Returns false if the command would
+    /// write data, but has no dry run; the caller reports it as skipped. Steps
+    /// without dry run within a command (e.g. checks and copies of pipelines)
+    /// report themselves as skipped.
+    pub fn make_dry_run(&mut self) -> bool {
+        match self {
+            Command::Backup(args) => args.dry_run = true,
+            Command::Forget(args) => args.dry_run = true,
+            Command::Run(args) => args.dry_run = true,
+            Command::Due(args) => args.dry_run = true,
+            Command::Drill(args) => args.dry_run = true,
+            Command::Estimate(_)
+            | Command::ExplainExcludes(_)
+            | Command::Schedule(ScheduleCommand::Show(_))
+            | Command::Status(_)
+            | Command::Config(_)
+            | Command::Ctl(CtlCommand::Status)
+            | Command::Verify(_)
+            | Command::License => {}
+            _ => return false,
+        }
+        true
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Command::Backup(_) => "backup",
+            Command::Check(_) => "check",
+            Command::Copy(_) => "copy",
+            Command::Config(_) => "config",
+            Command::Ctl(_) => "ctl",
+            Command::Daemon => "daemon",
+            Command::Drill(_) => "drill",
+            Command::Due(_) => "due",
+            Command::Estimate(_) => "estimate",
+            Command::Exec(_) => "exec",
+            Command::ExplainExcludes(_) => "explain-excludes",
+            Command::Forget(_) => "forget",
+            Command::Maintenance(_) => "maintenance",
+            Command::Pin(_) => "pin",
+            Command::Run(_) => "run",
+            Command::Key(_) => "key",
+            Command::Schedule(_) => "schedule",
+            Command::Status(_) => "status",
+            Command::Verify(_) => "verify",
+            Command::Watch(_) => "watch",
+            Command::License => "license",
+        }
+    }
+}
+
 #[derive(ClapArgs, Debug)]
 pub struct BackupArgs {
     /// Only backup data of this location (repeatable).
@@ -77,15 +345,184 @@ pub struct BackupArgs {
     /// Do not upload or write any data, just show what would be done.
     #[arg(short = 'n', long)]
     dry_run: bool,
+    /// Additional arguments passed to restic backup.
+    #[arg(raw = true, value_name = "RESTIC_ARG")]
+    restic_args: Vec<String>,
 }
 
 impl BackupArgs {
+    pub fn new(selected_locations: Vec<LocationRepo>, dry_run: bool) -> Self {
+        Self {
+            selected_locations,
+            dry_run,
+            restic_args: Vec::new(),
+        }
+    }
+    pub fn with_restic_args(mut self, restic_args: Vec<String>) -> Self {
+        self.restic_args = restic_args;
+        self
+    }
     pub fn selected_locations(&self) -> &Vec<LocationRepo> {
         &self.selected_locations
     }
     pub fn dry_run(&self) -> bool {
         self.dry_run
     }
+    pub fn restic_args(&self) -> &[String] {
+        &self.restic_args
+    }
+}
+
+#[derive(ClapArgs, Debug)]
+pub struct DrillArgs {
+    /// The location whose latest snapshot is restored.
+    #[arg(value_name = "LOCATION")]
+    location: Name,
+    /// Only restore from this repository (repeatable).
+    #[arg(short, long = "repo", value_name = "REPO")]
+    repos: Vec<Name>,
+    /// Restore into this directory and keep the restored files.
+    #[arg(long, value_name = "DIR")]
+    target: Option<PathBuf>,
+    /// Let restic verify the restored files against the snapshot.
+    #[arg(long)]
+    verify: bool,
+    /// Do not restore any files, just show what would be restored.
+    #[arg(short = 'n', long)]
+    dry_run: bool,
+    /// Additional arguments passed to restic restore.
+    #[arg(raw = true, value_name = "RESTIC_ARG")]
+    restic_args: Vec<String>,
+}
+
+impl DrillArgs {
+    pub fn location(&self) -> &Name {
+        &self.location
+    }
+    pub fn repos(&self) -> &[Name] {
+        &self.repos
+    }
+    pub fn target(&self) -> Option<&Path> {
+        self.target.as_deref()
+    }
+    pub fn verify(&self) -> bool {
+        self.verify
+    }
+    pub fn dry_run(&self) -> bool {
+        self.dry_run
+    }
+    pub fn restic_args(&self) -> &[String] {
+        &self.restic_args
+    }
+}
+
+#[derive(ClapArgs, Debug)]
+pub struct DueArgs {
+    /// Only show the operations of this location (repeatable).
+    #[arg(short = 'l', long = "location", value_name = "LOCATION[@REPO]")]
+    selected_locations: Vec<LocationRepo>,
+    /// Run the pending operations.
+    #[arg(long)]
+    run: bool,
+    /// Do not write or delete any data, just show what would be done.
+    #[arg(short = 'n', long, requires = "run")]
+    dry_run: bool,
+}
+
+impl DueArgs {
+    pub fn selected_locations(&self) -> &Vec<LocationRepo> {
+        &self.selected_locations
+    }
+    pub fn run(&self) -> bool {
+        self.run
+    }
+    pub fn dry_run(&self) -> bool {
+        self.dry_run
+    }
+}
+
+#[derive(ClapArgs, Debug)]
+pub struct EstimateArgs {
+    /// Only estimate the backup of this location (repeatable).
+    #[arg(short = 'l', long = "location", value_name = "LOCATION[@REPO]")]
+    selected_locations: Vec<LocationRepo>,
+    /// Additional arguments passed to restic backup.
+    #[arg(raw = true, value_name = "RESTIC_ARG")]
+    restic_args: Vec<String>,
+}
+
+impl EstimateArgs {
+    pub fn selected_locations(&self) -> &Vec<LocationRepo> {
+        &self.selected_locations
+    }
+    pub fn restic_args(&self) -> &[String] {
+        &self.restic_args
+    }
+}
+
+#[derive(ClapArgs, Debug)]
+pub struct CheckArgs {
+    /// Only check the repositories of this location (repeatable).
+    #[arg(short = 'l', long = "location", value_name = "LOCATION[@REPO]")]
+    selected_locations: Vec<LocationRepo>,
+    /// Additional arguments passed to restic check.
+    #[arg(raw = true, value_name = "RESTIC_ARG")]
+    restic_args: Vec<String>,
+}
+
+impl CheckArgs {
+    pub fn new(selected_locations: Vec<LocationRepo>) -> Self {
+        Self {
+            selected_locations,
+            restic_args: Vec::new(),
+        }
+    }
+    pub fn with_restic_args(mut self, restic_args: Vec<String>) -> Self {
+        self.restic_args = restic_args;
+        self
+    }
+    pub fn selected_locations(&self) -> &Vec<LocationRepo> {
+        &self.selected_locations
+    }
+    pub fn restic_args(&self) -> &[String] {
+        &self.restic_args
+    }
+}
+
+#[derive(ClapArgs, Debug)]
+pub struct CopyArgs {
+    /// Only copy the snapshots of this location (repeatable).
+    #[arg(short = 'l', long = "location", value_name = "LOCATION[@REPO]")]
+    selected_locations: Vec<LocationRepo>,
+    /// The repository to which the snapshots are copied.
+    #[arg(long = "to", value_name = "REPO")]
+    target: Name,
+    /// Additional arguments passed to restic copy.
+    #[arg(raw = true, value_name = "RESTIC_ARG")]
+    restic_args: Vec<String>,
+}
+
+impl CopyArgs {
+    pub fn new(selected_locations: Vec<LocationRepo>, target: Name) -> Self {
+        Self {
+            selected_locations,
+            target,
+            restic_args: Vec::new(),
+        }
+    }
+    pub fn with_restic_args(mut self, restic_args: Vec<String>) -> Self {
+        self.restic_args = restic_args;
+        self
+    }
+    pub fn selected_locations(&self) -> &Vec<LocationRepo> {
+        &self.selected_locations
+    }
+    pub fn target(&self) -> &Name {
+        &self.target
+    }
+    pub fn restic_args(&self) -> &[String] {
+        &self.restic_args
+    }
 }
 
 #[derive(ClapArgs, Debug)]
@@ -93,6 +530,26 @@ pub struct ExecArgs {
     /// Only run the command for this repository (repeatable).
     #[arg(short, long = "repo", value_name = "REPO")]
     repos: Vec<Name>,
+    /// Only run the command for the snapshots of this location (repeatable).
+    ///
+    /// The command runs for the repositories of the location (unless
+    /// repositories are selected) and commands that filter snapshots by tag,
+    /// e.g. snapshots, forget or stats, only see those of the location.
+    #[arg(short = 'l', long = "location", value_name = "LOCATION")]
+    locations: Vec<Name>,
+    /// Run the command for all repositories at once.
+    ///
+    /// Every line of the output is prefixed with the repository name. If the
+    /// arguments include --json, the output is printed as one JSON document
+    /// with the output of every repository under its name instead.
+    #[arg(short, long)]
+    parallel: bool,
+    /// Print the JSON output of all repositories as one pretty-printed
+    /// document, with the output of every repository under its name.
+    ///
+    /// restic's --json option is added if the arguments do not include it.
+    #[arg(long)]
+    merge_json: bool,
     /// One or more arguments passed to the restic executable.
     #[arg(required = true, raw = true, value_name = "ARG")]
     args: Vec<String>,
@@ -102,11 +559,45 @@ impl ExecArgs {
     pub fn repos(&self) -> &[Name] {
         &self.repos
     }
+    pub fn locations(&self) -> &[Name] {
+        &self.locations
+    }
+    pub fn parallel(&self) -> bool {
+        self.parallel
+    }
+    pub fn merge_json(&self) -> bool {
+        self.merge_json
+    }
     pub fn args(&self) -> &[String] {
         &self.args
     }
 }
 
+#[derive(ClapArgs, Debug)]
+pub struct ExplainExcludesArgs {
+    /// The location whose backup settings are used.
+    #[arg(value_name = "LOCATION")]
+    location: Name,
+    /// The paths to explain. Defaults to the paths of the location.
+    #[arg(value_name = "PATH")]
+    paths: Vec<PathBuf>,
+    /// Explain the contents of directories, too.
+    #[arg(short, long)]
+    recursive: bool,
+}
+
+impl ExplainExcludesArgs {
+    pub fn location(&self) -> &Name {
+        &self.location
+    }
+    pub fn paths(&self) -> &Vec<PathBuf> {
+        &self.paths
+    }
+    pub fn recursive(&self) -> bool {
+        self.recursive
+    }
+}
+
 #[derive(ClapArgs, Debug)]
 pub struct ForgetArgs {
     /// Only remove snapshots of this location (repeatable).
@@ -115,15 +606,285 @@ pub struct ForgetArgs {
     /// Do not delete any data, just show what would be done.
     #[arg(short = 'n', long)]
     dry_run: bool,
+    /// Additional arguments passed to restic forget.
+    #[arg(raw = true, value_name = "RESTIC_ARG")]
+    restic_args: Vec<String>,
 }
 
 impl ForgetArgs {
+    pub fn new(selected_locations: Vec<LocationRepo>, dry_run: bool) -> Self {
+        Self {
+            selected_locations,
+            dry_run,
+            restic_args: Vec::new(),
+        }
+    }
+    pub fn with_restic_args(mut self, restic_args: Vec<String>) -> Self {
+        self.restic_args = restic_args;
+        self
+    }
     pub fn selected_locations(&self) -> &Vec<LocationRepo> {
         &self.selected_locations
     }
     pub fn dry_run(&self) -> bool {
         self.dry_run
     }
+    pub fn restic_args(&self) -> &[String] {
+        &self.restic_args
+    }
+}
+
+#[derive(ClapArgs, Debug)]
+pub struct MaintenanceArgs {
+    /// Only maintain this repository (repeatable).
+    #[arg(short, long = "repo", value_name = "REPO")]
+    repos: Vec<Name>,
+    /// Read this part of the data during the check (e.g. 5% or 1/10). Takes
+    /// precedence over the read-data-subset setting.
+    #[arg(long, value_name = "SUBSET")]
+    read_data_subset: Option<String>,
+}
+
+impl MaintenanceArgs {
+    pub fn new(repos: Vec<Name>) -> Self {
+        Self {
+            repos,
+            read_data_subset: None,
+        }
+    }
+    pub fn repos(&self) -> &[Name] {
+        &self.repos
+    }
+    pub fn read_data_subset(&self) -> Option<&str> {
+        self.read_data_subset.as_deref()
+    }
+}
+
+#[derive(ClapArgs, Debug)]
+pub struct RunArgs {
+    /// The name of the pipeline (default: the maintenance cycle).
+    #[arg(value_name = "PIPELINE")]
+    pipeline: Option<Name>,
+    /// Only run the pipeline for this location (repeatable).
+    #[arg(short = 'l', long = "location", value_name = "LOCATION[@REPO]")]
+    selected_locations: Vec<LocationRepo>,
+    /// Do not write or delete any data, just show what would be done.
+    #[arg(short = 'n', long)]
+    dry_run: bool,
+}
+
+impl RunArgs {
+    pub fn pipeline(&self) -> Option<&Name> {
+        self.pipeline.as_ref()
+    }
+    pub fn selected_locations(&self) -> &Vec<LocationRepo> {
+        &self.selected_locations
+    }
+    pub fn dry_run(&self) -> bool {
+        self.dry_run
+    }
+}
+
+#[derive(ClapArgs, Debug)]
+pub struct PinArgs {
+    /// The location whose snapshot is pinned.
+    #[arg(value_name = "LOCATION")]
+    location: Name,
+    /// Only pin the snapshot in this repository (repeatable).
+    #[arg(short, long = "repo", value_name = "REPO")]
+    repos: Vec<Name>,
+    /// The ID of the snapshot to pin. Defaults to the latest snapshot of the
+    /// location in each repository.
+    #[arg(short, long, value_name = "ID")]
+    snapshot: Option<String>,
+    /// The tag that pins the snapshot. Defaults to the first tag of the
+    /// pin-tags setting.
+    #[arg(short, long, value_name = "TAG")]
+    tag: Option<String>,
+    /// Remove the pin tag from the snapshot instead.
+    #[arg(long)]
+    unpin: bool,
+}
+
+impl PinArgs {
+    pub fn location(&self) -> &Name {
+        &self.location
+    }
+    pub fn repos(&self) -> &[Name] {
+        &self.repos
+    }
+    pub fn snapshot(&self) -> Option<&str> {
+        self.snapshot.as_deref()
+    }
+    pub fn tag(&self) -> Option<&str> {
+        self.tag.as_deref()
+    }
+    pub fn unpin(&self) -> bool {
+        self.unpin
+    }
+}
+
+#[derive(ClapSubcommand, Debug)]
+pub enum KeyCommand {
+    /// Save the key of a repository in the keyring entry configured by
+    /// key-keyring.
+    ///
+    /// The key is read from the terminal or, if the standard input is not a
+    /// terminal, from the first line of the standard input.
+    Store(KeyStoreArgs),
+}
+
+#[derive(ClapArgs, Debug)]
+pub struct KeyStoreArgs {
+    /// The repository whose key is saved.
+    #[arg(value_name = "REPO")]
+    repo: Name,
+}
+
+impl KeyStoreArgs {
+    pub fn repo(&self) -> &Name {
+        &self.repo
+    }
+}
+
+#[derive(ClapSubcommand, Debug)]
+pub enum ConfigCommand {
+    /// Show the files of the configuration in the order in which they are
+    /// merged.
+    Show(ConfigShowArgs),
+}
+
+#[derive(ClapArgs, Debug)]
+pub struct ConfigShowArgs {
+    /// Print the merged settings of all files and environment variables as
+    /// JSON instead. Secrets are masked.
+    #[arg(long)]
+    resolved: bool,
+}
+
+impl ConfigShowArgs {
+    pub fn resolved(&self) -> bool {
+        self.resolved
+    }
+}
+
+#[derive(ClapSubcommand, Debug)]
+pub enum CtlCommand {
+    /// Show the scheduled, running and recently finished operations.
+    Status,
+    /// Start a backup of a location now.
+    Backup(CtlRunArgs),
+    /// Start a forget of a location now.
+    Forget(CtlRunArgs),
+    /// Start a check of a location now.
+    Check(CtlRunArgs),
+    /// Stop starting scheduled operations. Triggered operations still run.
+    Pause,
+    /// Start scheduled operations again.
+    Resume,
+}
+
+#[derive(ClapArgs, Debug)]
+pub struct CtlRunArgs {
+    /// The location whose operation is started.
+    #[arg(value_name = "LOCATION")]
+    location: Name,
+}
+
+impl CtlRunArgs {
+    pub fn location(&self) -> &Name {
+        &self.location
+    }
+}
+
+#[derive(ClapSubcommand, Debug)]
+pub enum ScheduleCommand {
+    /// Create a scheduled task for every scheduled operation of configured
+    /// locations.
+    ///
+    /// The tasks run aresticrat with the current configuration files, working
+    /// directory and environment files. Existing tasks are replaced.
+    Install(ScheduleInstallArgs),
+    /// Print the schedules of configured locations in the format of a
+    /// scheduler.
+    ///
+    /// The printed entries run aresticrat with the current configuration
+    /// files, working directory and environment files.
+    Show(ScheduleShowArgs),
+}
+
+#[derive(ClapArgs, Debug)]
+#[command(group(ArgGroup::new("scheduler").required(true)))]
+pub struct ScheduleInstallArgs {
+    /// Register the tasks with the Windows Task Scheduler.
+    ///
+    /// Tasks are created in the folder "aresticrat" by schtasks. Only cron
+    /// expressions that correspond to a minute, hourly, daily, weekly or
+    /// monthly trigger are supported.
+    #[arg(long, group = "scheduler")]
+    windows: bool,
+}
+
+impl ScheduleInstallArgs {
+    pub fn windows(&self) -> bool {
+        self.windows
+    }
+}
+
+#[derive(ClapArgs, Debug)]
+#[command(group(ArgGroup::new("format").required(true)))]
+pub struct ScheduleShowArgs {
+    /// Print lines that can be added to a crontab.
+    ///
+    /// Cron has no seconds field, so expressions with seconds other than 0
+    /// are not supported.
+    #[arg(long, group = "format")]
+    cron: bool,
+}
+
+impl ScheduleShowArgs {
+    pub fn cron(&self) -> bool {
+        self.cron
+    }
+}
+
+#[derive(ClapArgs, Debug)]
+pub struct StatusArgs {
+    /// Only show the status of this location (repeatable).
+    #[arg(short = 'l', long = "location", value_name = "LOCATION[@REPO]")]
+    selected_locations: Vec<LocationRepo>,
+    /// Remove the locks of locked repositories if all of them are older than
+    /// the stale-lock-age setting.
+    #[arg(long)]
+    unlock_stale: bool,
+}
+
+impl StatusArgs {
+    pub fn new(selected_locations: Vec<LocationRepo>) -> Self {
+        Self {
+            selected_locations,
+            unlock_stale: false,
+        }
+    }
+    pub fn selected_locations(&self) -> &Vec<LocationRepo> {
+        &self.selected_locations
+    }
+    pub fn unlock_stale(&self) -> bool {
+        self.unlock_stale
+    }
+}
+
+#[derive(ClapArgs, Debug)]
+pub struct WatchArgs {
+    /// Only watch this location (repeatable).
+    #[arg(short = 'l', long = "location", value_name = "LOCATION[@REPO]")]
+    selected_locations: Vec<LocationRepo>,
+}
+
+impl WatchArgs {
+    pub fn selected_locations(&self) -> &Vec<LocationRepo> {
+        &self.selected_locations
+    }
 }
 
 #[derive(ClapArgs, Debug)]
@@ -131,10 +892,51 @@ pub struct VerifyArgs {
     /// Create missing repositories.
     #[arg(long)]
     init: bool,
+    /// Print the results as JSON document instead of text.
+    ///
+    /// The document lists the status of every repository and location (e.g.
+    /// OK, NOT FOUND, LOCKED or FAILED) and their problems.
+    #[arg(long)]
+    json: bool,
+    /// Report problems as warnings and exit with status 0 anyway.
+    ///
+    /// By default, verify fails if a repository or location has a problem.
+    #[arg(long)]
+    warn_only: bool,
+    /// Also check the integrity of every repository and read all its data.
+    #[arg(long, conflicts_with = "read_data_subset")]
+    read_data: bool,
+    /// Also check the integrity of every repository and read this part of its
+    /// data (e.g. 5% or 1/10).
+    ///
+    /// Without value, the check.read-data-subset setting of the repository
+    /// (or the read-data-subset setting of the maintenance) applies. Without
+    /// either, the check reads no data.
+    #[arg(long, value_name = "SUBSET", num_args = 0..=1)]
+    read_data_subset: Option<Option<String>>,
+    /// Remove the locks of locked repositories if all of them are older than
+    /// the stale-lock-age setting.
+    #[arg(long)]
+    unlock_stale: bool,
 }
 
 impl VerifyArgs {
     pub fn init(&self) -> bool {
         self.init
     }
+    pub fn json(&self) -> bool {
+        self.json
+    }
+    pub fn warn_only(&self) -> bool {
+        self.warn_only
+    }
+    pub fn read_data(&self) -> bool {
+        self.read_data
+    }
+    pub fn read_data_subset(&self) -> Option<Option<&str>> {
+        self.read_data_subset.as_ref().map(Option::as_deref)
+    }
+    pub fn unlock_stale(&self) -> bool {
+        self.unlock_stale
+    }
 }
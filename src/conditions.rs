@@ -0,0 +1,125 @@
+use crate::config::Location;
+use crate::print_log;
+use tracing::Level;
+
+/// The reason why the backup of a location is skipped, if any. Conditions that
+/// cannot be determined do not skip the backup.
+pub fn skip_reason(location: &Location) -> Option<&'static str> {
+    if location.skip_on_battery() && check("battery power", on_battery()) {
+        return Some("the system runs on battery power");
+    }
+    if location.skip_on_metered() && check("metered network", metered()) {
+        return Some("the network connection is metered");
+    }
+    None
+}
+
+fn check(condition: &str, state: Option<bool>) -> bool {
+    if state.is_none() {
+        print_log!(
+            Level::DEBUG,
+            "Failed to determine the {condition} state. Assume it does not apply."
+        );
+    }
+    state.unwrap_or(false)
+}
+
+/// Whether the system runs on battery power. Systems without battery never do.
+#[cfg(target_os = "linux")]
+fn on_battery() -> Option<bool> {
+    let mut has_battery = false;
+    for entry in std::fs::read_dir("/sys/class/power_supply").ok()?.flatten() {
+        let path = entry.path();
+        let read = |name: &str| std::fs::read_to_string(path.join(name)).unwrap_or_default();
+        match read("type").trim() {
+            "Mains" | "USB" if read("online").trim() == "1" => return Some(false),
+            // Batteries of peripherals, e.g. of a mouse, have the device scope.
+            "Battery" if read("scope").trim() != "Device" => has_battery = true,
+            _ => {}
+        }
+    }
+    Some(has_battery)
+}
+
+#[cfg(target_os = "macos")]
+fn on_battery() -> Option<bool> {
+    let output = command_output("pmset", &["-g", "batt"])?;
+    Some(output.contains("'Battery Power'"))
+}
+
+#[cfg(windows)]
+fn on_battery() -> Option<bool> {
+    use windows_sys::Win32::System::Power::{GetSystemPowerStatus, SYSTEM_POWER_STATUS};
+
+    let mut status: SYSTEM_POWER_STATUS = unsafe { std::mem::zeroed() };
+    if unsafe { GetSystemPowerStatus(&mut status) } == 0 {
+        return None;
+    }
+    match status.ACLineStatus {
+        0 => Some(true),
+        1 => Some(false),
+        _ => None,
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+fn on_battery() -> Option<bool> {
+    None
+}
+
+/// Whether the network connection is metered, as reported by NetworkManager
+/// (including its guesses, e.g. for mobile broadband).
+#[cfg(target_os = "linux")]
+fn metered() -> Option<bool> {
+    let output = command_output(
+        "busctl",
+        &[
+            "--system",
+            "get-property",
+            "org.freedesktop.NetworkManager",
+            "/org/freedesktop/NetworkManager",
+            "org.freedesktop.NetworkManager",
+            "Metered",
+        ],
+    )?;
+    // NMMetered: 0 unknown, 1 yes, 2 no, 3 guess yes, 4 guess no.
+    match output.trim().strip_prefix("u ")? {
+        "1" | "3" => Some(true),
+        "2" | "4" => Some(false),
+        _ => None,
+    }
+}
+
+/// Whether the cost of the internet connection depends on the transferred
+/// data, as reported by the Windows connection profile.
+#[cfg(windows)]
+fn metered() -> Option<bool> {
+    let script = "[Windows.Networking.Connectivity.NetworkInformation, \
+        Windows.Networking.Connectivity, ContentType = WindowsRuntime]::\
+        GetInternetConnectionProfile().GetConnectionCost().NetworkCostType";
+    let output = command_output("powershell", &["-NoProfile", "-Command", script])?;
+    match output.trim() {
+        "Fixed" | "Variable" => Some(true),
+        "Unrestricted" => Some(false),
+        _ => None,
+    }
+}
+
+#[cfg(not(any(target_os = "linux", windows)))]
+fn metered() -> Option<bool> {
+    None
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos", windows))]
+fn command_output(program: &str, args: &[&str]) -> Option<String> {
+    let output = std::process::Command::new(program)
+        .args(args)
+        .stdin(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).into_owned())
+}
@@ -1,5 +1,6 @@
 use serde::Deserialize;
 use std::collections::HashMap;
+use std::collections::HashSet;
 use std::fmt::Display;
 use std::ops::Deref;
 use std::path::Path;
@@ -8,55 +9,614 @@ use std::str::FromStr;
 use thiserror::Error;
 
 use crate::ENV_PREFIX;
+use crate::container;
+use crate::database;
+use crate::event::Operation;
+use crate::secrets;
 
 #[derive(Debug, Deserialize)]
 pub struct Config {
     #[serde(default = "default_executable")]
     executable: String,
+    #[serde(default = "default_shell")]
+    shell: CommandSeq,
+    #[serde(rename = "log-file", default)]
+    log_file: Option<String>,
+    #[serde(default)]
+    report: Option<String>,
+    #[serde(rename = "state-file", default)]
+    state_file: Option<PathBuf>,
+    #[serde(rename = "cache-dir", default)]
+    cache_dir: Option<PathBuf>,
+    #[serde(rename = "no-cache", default)]
+    no_cache: bool,
+    #[serde(rename = "retry-lock", default)]
+    retry_lock: String,
+    #[serde(rename = "no-lock", default)]
+    no_lock: bool,
+    #[serde(rename = "extra-args", default)]
+    extra_args: ExtraArgs,
+    #[serde(default)]
+    progress: Progress,
+    #[serde(rename = "progress-fps", default)]
+    progress_fps: Option<f64>,
+    #[serde(rename = "prevent-sleep", default)]
+    prevent_sleep: bool,
+    #[serde(rename = "min-free-space", default)]
+    min_free_space: Option<Size>,
+    #[serde(rename = "stale-lock-age", default = "default_stale_lock_age")]
+    stale_lock_age: Duration,
+    #[serde(rename = "pin-tags", default = "default_pin_tags")]
+    pin_tags: Vec<String>,
     #[serde(default)]
     options: Options,
     repos: HashMap<Name, Repo>,
     locations: HashMap<Name, Location>,
     #[serde(default)]
     environment: Environment,
+    #[serde(default)]
+    metrics: Metrics,
+    #[serde(default)]
+    notify: Notify,
+    #[serde(default)]
+    redact: Redact,
+    #[serde(default)]
+    daemon: Daemon,
+    #[serde(default)]
+    watch: Watch,
+    #[serde(default)]
+    lock: Lock,
+    #[serde(default)]
+    retry: Retry,
+    #[serde(default)]
+    priority: Priority,
+    #[serde(default)]
+    pipelines: HashMap<Name, Vec<Step>>,
+    #[serde(default)]
+    maintenance: Maintenance,
+    /// The configuration files the configuration has been read from.
+    #[serde(skip)]
+    sources: Vec<PathBuf>,
+    /// The settings that override those of the configuration files.
+    #[serde(skip)]
+    settings: Vec<(String, String)>,
+    /// The configuration files and the files encrypted with SOPS.
+    #[serde(skip)]
+    files: Vec<PathBuf>,
+    /// The merged settings of all sources, used to compare configurations.
+    #[serde(skip)]
+    raw: serde_json::Value,
 }
 
 fn default_executable() -> String {
     "restic".to_string()
 }
 
+fn default_shell() -> CommandSeq {
+    #[cfg(windows)]
+    let shell = ["powershell", "-NoProfile", "-Command"];
+    #[cfg(not(windows))]
+    let shell = ["/bin/sh", "-c"];
+    CommandSeq(shell.map(String::from).to_vec())
+}
+
+fn default_stale_lock_age() -> Duration {
+    Duration(std::time::Duration::from_secs(60 * 60))
+}
+
+fn default_pin_tags() -> Vec<String> {
+    vec!["pinned".to_string()]
+}
+
+type Builder = config::ConfigBuilder<config::builder::DefaultState>;
+
+/// The builder with the configuration files and the settings of nested
+/// environment variables and the command line, and the source of the other
+/// environment variables. The latter is added separately, because it must
+/// also be used to read the decryption settings.
+fn builder(
+    config_paths: &[PathBuf],
+    settings: &[(String, String)],
+    vars: Vec<(String, String)>,
+) -> Result<(Builder, config::Environment), config::ConfigError> {
+    // Variables with nested keys are excluded, because their double
+    // underscores would be taken as empty keys.
+    let nested_prefix = format!("{ENV_PREFIX}__");
+    let (nested, flat): (Vec<_>, Vec<_>) = vars
+        .into_iter()
+        .partition(|(name, _)| name.starts_with(&nested_prefix));
+    let env = config::Environment::with_prefix(ENV_PREFIX)
+        .separator("_")
+        .source(Some(flat.into_iter().collect()));
+    let mut builder = config::Config::builder();
+    for config_path in config_paths {
+        builder = builder.add_source(config::File::with_name(
+            config_path.to_string_lossy().deref(),
+        ));
+    }
+    for (key, value) in env_overrides(nested, &nested_prefix) {
+        builder = builder.set_override(key, value)?;
+    }
+    for (key, value) in settings {
+        builder = builder.set_override(key.as_str(), value.as_str())?;
+    }
+    Ok((builder, env))
+}
+
+/// The settings of environment variables with nested keys, e.g.
+/// `ARESTICRAT__REPOS__NAS__RETRY_LOCK` for `repos.nas.retry-lock`. Double
+/// underscores separate keys and single underscores stand for hyphens. Keys
+/// are lowercase.
+fn env_overrides(vars: Vec<(String, String)>, prefix: &str) -> Vec<(String, String)> {
+    vars.into_iter()
+        .filter_map(|(name, value)| {
+            let key = name
+                .strip_prefix(prefix)?
+                .split("__")
+                .map(|k| k.to_ascii_lowercase().replace('_', "-"))
+                .collect::<Vec<_>>()
+                .join(".");
+            Some((key, value))
+        })
+        .collect()
+}
+
 impl Config {
-    pub fn new(config_path: &Path) -> Result<Self, config::ConfigError> {
-        let s = config::Config::builder()
-            .add_source(config::File::with_name(
-                config_path.to_string_lossy().deref(),
-            ))
-            .add_source(config::Environment::with_prefix(ENV_PREFIX).separator("_"))
-            .build()?;
-        s.try_deserialize()
+    /// Reads the configuration from the given files. Settings of later files
+    /// override those of earlier ones; tables are merged. The given settings
+    /// override all others.
+    pub fn new(
+        config_paths: &[PathBuf],
+        settings: &[(String, String)],
+    ) -> Result<Self, config::ConfigError> {
+        let (mut builder, env) = builder(config_paths, settings, std::env::vars().collect())?;
+
+        // The decryption settings must be known before the encrypted sources
+        // and values can be processed.
+        let secrets: SecretsConfig = builder
+            .clone()
+            .add_source(env.clone())
+            .build()?
+            .try_deserialize()?;
+
+        for file in secrets.sops.files() {
+            let content = secrets::sops_decrypt(file)
+                .map_err(|e| config::ConfigError::Message(e.to_string()))?;
+            builder =
+                builder.add_source(config::File::from_str(&content, config::FileFormat::Json));
+        }
+
+        let mut s = builder.add_source(env).build()?;
+        secrets::resolve_values(&mut s.cache, secrets.age.identity())
+            .map_err(|e| config::ConfigError::Message(e.to_string()))?;
+        let raw: serde_json::Value = s.clone().try_deserialize()?;
+        let mut config: Self = s.try_deserialize()?;
+        config.validate()?;
+        config.sources = config_paths.to_vec();
+        config.settings = settings.to_vec();
+        config.files = config_paths
+            .iter()
+            .chain(secrets.sops.files())
+            .cloned()
+            .collect();
+        config.raw = raw;
+        Ok(config)
+    }
+
+    fn validate(&self) -> Result<(), config::ConfigError> {
+        let mut repo_names: Vec<_> = self.repos.keys().collect();
+        repo_names.sort();
+        for repo_name in repo_names {
+            let sources = self.repos[repo_name].password_sources();
+            if sources.len() > 1 {
+                return Err(config::ConfigError::Message(format!(
+                    "Repository {repo_name} defines more than one password source ({}; each listed with its alias).",
+                    sources.join(", ")
+                )));
+            }
+            let repo = &self.repos[repo_name];
+            if let Some(variable) = repo.unknown_path_variables().first() {
+                return Err(config::ConfigError::Message(format!(
+                    "Repository {repo_name} uses an unknown variable {{{{{variable}}}}} in its path."
+                )));
+            }
+            if let Some(probe) = &repo.probe {
+                let problem = match (&probe.address, &probe.url) {
+                    (Some(_), Some(_)) => Some("both an address and a URL to probe"),
+                    (None, None) if crate::probe::address(&repo.path()).is_none() => {
+                        Some("neither an address nor a URL to probe and its path has no server")
+                    }
+                    _ => None,
+                };
+                if let Some(problem) = problem {
+                    return Err(config::ConfigError::Message(format!(
+                        "Repository {repo_name} defines {problem}."
+                    )));
+                }
+            }
+            for target in &repo.replicate_to {
+                if target == repo_name || !self.repos.contains_key(target) {
+                    return Err(config::ConfigError::Message(format!(
+                        "Repository {repo_name} replicates to an invalid repository {target}."
+                    )));
+                }
+            }
+            if let Some(backend) = repo.backend() {
+                for (name, _) in repo.backends.options() {
+                    if name != backend {
+                        return Err(config::ConfigError::Message(format!(
+                            "Repository {repo_name} defines {name} settings, but uses the {backend} backend."
+                        )));
+                    }
+                }
+            }
+        }
+        let mut location_names: Vec<_> = self.locations.keys().collect();
+        location_names.sort();
+        for location_name in location_names {
+            let location = &self.locations[location_name];
+            let files_from = location
+                .options
+                .backup()
+                .or(self.options.backup())
+                .is_some_and(BackupOptions::has_files_from);
+            if let Some(message) = location.problem(files_from) {
+                return Err(config::ConfigError::Message(format!(
+                    "Location {location_name} defines {message}."
+                )));
+            }
+            let mut repo_names: Vec<_> = location.repo_forget.keys().collect();
+            repo_names.sort();
+            if let Some(repo_name) = repo_names
+                .into_iter()
+                .find(|repo_name| !location.repos.contains(repo_name))
+            {
+                return Err(config::ConfigError::Message(format!(
+                    "Location {location_name} defines forget options for repository {repo_name}, which it does not use."
+                )));
+            }
+            if let Some(sink) = self.unknown_route(&location.notify) {
+                return Err(config::ConfigError::Message(format!(
+                    "Location {location_name} defines routes for an unknown notification service {sink}."
+                )));
+            }
+        }
+        if let Some(sink) = self.unknown_route(&self.notify) {
+            return Err(config::ConfigError::Message(format!(
+                "Notification routes refer to an unknown notification service {sink}."
+            )));
+        }
+        if let Some(problem) = self.dependency_problem() {
+            return Err(config::ConfigError::Message(problem));
+        }
+        if self
+            .progress_fps
+            .is_some_and(|fps| fps.is_nan() || fps <= 0.0)
+        {
+            return Err(config::ConfigError::Message(
+                "progress-fps must be greater than 0.".to_string(),
+            ));
+        }
+        for (pipeline_name, steps) in &self.pipelines {
+            for target in steps.iter().filter_map(Step::target) {
+                if !self.repos.contains_key(target) {
+                    return Err(config::ConfigError::Message(format!(
+                        "Pipeline {pipeline_name} copies to an undefined repository {target}."
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The first service of the routes that is neither built in nor a
+    /// configured plugin. Plugins of the plugins directory are only discovered
+    /// when events are sent, so all names are accepted if it is set.
+    fn unknown_route<'a>(&self, notify: &'a Notify) -> Option<&'a str> {
+        if self.notify.plugins_dir.is_some() {
+            return None;
+        }
+        let mut sinks: Vec<_> = notify.routes.keys().collect();
+        sinks.sort();
+        sinks
+            .into_iter()
+            .find(|sink| {
+                !NOTIFY_SERVICES.contains(&sink.as_str())
+                    && !self.notify.plugins.contains_key(*sink)
+            })
+            .map(String::as_str)
+    }
+
+    /// The first problem of the dependencies of the locations: an undefined
+    /// location or a cycle.
+    fn dependency_problem(&self) -> Option<String> {
+        let mut location_names: Vec<_> = self.locations.keys().collect();
+        location_names.sort();
+        for location_name in &location_names {
+            for dependency in &self.locations[*location_name].depends_on {
+                if !self.locations.contains_key(dependency) {
+                    return Some(format!(
+                        "Location {location_name} depends on an undefined location {dependency}."
+                    ));
+                }
+            }
+        }
+        for location_name in location_names {
+            let mut pending: Vec<_> = self.locations[location_name].depends_on.iter().collect();
+            let mut seen = HashSet::new();
+            while let Some(dependency) = pending.pop() {
+                if dependency == location_name {
+                    return Some(format!("Location {location_name} depends on itself."));
+                }
+                if seen.insert(dependency) {
+                    pending.extend(&self.locations[dependency].depends_on);
+                }
+            }
+        }
+        None
     }
 
     pub fn executable(&self) -> &str {
         &self.executable
     }
+    /// The shell that runs shell hooks. The script is passed as its last
+    /// argument.
+    pub fn shell(&self) -> &CommandSeq {
+        &self.shell
+    }
+    pub fn log_file(&self) -> Option<&str> {
+        self.log_file.as_deref()
+    }
+    pub fn report(&self) -> Option<&str> {
+        self.report.as_deref()
+    }
+    pub fn state_file(&self) -> Option<&Path> {
+        self.state_file.as_deref()
+    }
+    /// The default cache directory of restic. Repositories may override it.
+    pub fn cache_dir(&self) -> Option<&Path> {
+        self.cache_dir.as_deref()
+    }
+    /// Whether restic runs without a cache by default.
+    pub fn no_cache(&self) -> bool {
+        self.no_cache
+    }
+    /// How long restic retries to lock repositories by default, e.g. "5m".
+    pub fn retry_lock(&self) -> &str {
+        &self.retry_lock
+    }
+    /// Whether read-only commands run without locking repositories by default.
+    pub fn no_lock(&self) -> bool {
+        self.no_lock
+    }
+    /// Additional arguments of restic subcommands for all repositories.
+    pub fn extra_args(&self) -> &ExtraArgs {
+        &self.extra_args
+    }
+    /// How restic reports the progress of running commands.
+    pub fn progress(&self) -> Progress {
+        self.progress
+    }
+    /// The number of progress updates per second, if it deviates from the
+    /// default of the progress mode.
+    pub fn progress_fps(&self) -> Option<f64> {
+        self.progress_fps
+    }
+    /// Whether the system is kept from going to sleep during runs.
+    pub fn prevent_sleep(&self) -> bool {
+        self.prevent_sleep
+    }
+    /// The free space the volumes of the restic cache and of local
+    /// repositories need before backups. Repositories may override it.
+    pub fn min_free_space(&self) -> Option<Size> {
+        self.min_free_space
+    }
+    /// The age from which repository locks count as stale, i.e. as left
+    /// behind by crashed runs.
+    pub fn stale_lock_age(&self) -> Duration {
+        self.stale_lock_age
+    }
+    /// The tags of pinned snapshots, which every forget keeps.
+    pub fn pin_tags(&self) -> &[String] {
+        &self.pin_tags
+    }
     pub fn options(&self) -> &Options {
         &self.options
     }
     pub fn repos(&self) -> &HashMap<Name, Repo> {
         &self.repos
     }
+    /// The configuration files in the order in which they are merged.
+    pub fn sources(&self) -> &[PathBuf] {
+        &self.sources
+    }
+
+    /// The settings that override those of the configuration files.
+    pub fn settings(&self) -> &[(String, String)] {
+        &self.settings
+    }
+
+    /// The configuration files and the files encrypted with SOPS.
+    pub fn files(&self) -> &[PathBuf] {
+        &self.files
+    }
+
+    /// The merged settings of all sources.
+    pub fn resolved(&self) -> &serde_json::Value {
+        &self.raw
+    }
+
+    /// The names of the locations and repositories that differ from the
+    /// other configuration and whether any other setting differs.
+    pub fn changes(&self, other: &Config) -> Changes {
+        let section = |key: &str| {
+            let (old, new) = (&other.raw[key], &self.raw[key]);
+            let names = |v: &serde_json::Value| {
+                v.as_object()
+                    .map(|o| o.keys().cloned().collect::<Vec<_>>())
+                    .unwrap_or_default()
+            };
+            let (old_names, new_names) = (names(old), names(new));
+            SectionChanges {
+                added: new_names
+                    .iter()
+                    .filter(|n| !old_names.contains(n))
+                    .cloned()
+                    .collect(),
+                removed: old_names
+                    .iter()
+                    .filter(|n| !new_names.contains(n))
+                    .cloned()
+                    .collect(),
+                changed: new_names
+                    .iter()
+                    .filter(|n| old_names.contains(n) && old[n.as_str()] != new[n.as_str()])
+                    .cloned()
+                    .collect(),
+            }
+        };
+        let others = |raw: &serde_json::Value| {
+            let mut raw = raw.clone();
+            if let Some(object) = raw.as_object_mut() {
+                object.remove("locations");
+                object.remove("repos");
+            }
+            raw
+        };
+        Changes {
+            locations: section("locations"),
+            repos: section("repos"),
+            settings: others(&self.raw) != others(&other.raw),
+        }
+    }
+
     pub fn locations(&self) -> &HashMap<Name, Location> {
         &self.locations
     }
     pub fn environment(&self) -> &Environment {
         &self.environment
     }
+    pub fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+    pub fn notify(&self) -> &Notify {
+        &self.notify
+    }
+    pub fn redact(&self) -> &Redact {
+        &self.redact
+    }
+    pub fn daemon(&self) -> &Daemon {
+        &self.daemon
+    }
+    pub fn watch(&self) -> &Watch {
+        &self.watch
+    }
+    pub fn lock(&self) -> &Lock {
+        &self.lock
+    }
+    pub fn retry(&self) -> &Retry {
+        &self.retry
+    }
+    pub fn priority(&self) -> &Priority {
+        &self.priority
+    }
+    pub fn pipelines(&self) -> &HashMap<Name, Vec<Step>> {
+        &self.pipelines
+    }
+    pub fn maintenance(&self) -> &Maintenance {
+        &self.maintenance
+    }
+
+    /// Returns all configured values that must not appear in any output, e.g.
+    /// passwords and access tokens.
+    pub fn secrets(&self) -> Vec<&str> {
+        let mut secrets: Vec<&str> = self.repos.values().map(|r| r.password()).collect();
+        if let Some(gateway) = self.metrics.pushgateway() {
+            secrets.extend(gateway.password());
+        }
+        secrets.extend(self.daemon.control_token());
+        let notify = &self.notify;
+        if let Some(webhook) = notify.webhook() {
+            secrets.extend(webhook.headers().values().map(String::as_str));
+        }
+        if let Some(email) = notify.email() {
+            secrets.extend(email.password());
+        }
+        if let Some(ntfy) = notify.ntfy() {
+            secrets.extend(ntfy.token());
+        }
+        for chat in [notify.slack(), notify.discord()].into_iter().flatten() {
+            secrets.push(chat.url());
+        }
+        for push in [notify.gotify(), notify.pushover()].into_iter().flatten() {
+            secrets.push(push.token());
+        }
+        for location in self.locations.values() {
+            secrets.extend(location.database.password());
+        }
+        secrets
+    }
+}
+
+/// The differences between two configurations, see [`Config::changes`].
+pub struct Changes {
+    pub locations: SectionChanges,
+    pub repos: SectionChanges,
+    /// Whether any setting outside of locations and repositories differs.
+    pub settings: bool,
+}
+
+/// The names of the added, removed and changed entries of a section.
+pub struct SectionChanges {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub changed: Vec<String>,
+}
+
+/// The settings required to decrypt the configuration.
+#[derive(Debug, Default, Deserialize)]
+struct SecretsConfig {
+    #[serde(default)]
+    age: Age,
+    #[serde(default)]
+    sops: Sops,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Age {
+    #[serde(default)]
+    identity: Option<PathBuf>,
+}
+
+impl Age {
+    pub fn identity(&self) -> Option<&Path> {
+        self.identity.as_deref()
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Sops {
+    #[serde(default)]
+    files: Vec<PathBuf>,
+}
+
+impl Sops {
+    pub fn files(&self) -> &[PathBuf] {
+        &self.files
+    }
 }
 
 #[derive(Debug, Default, Deserialize)]
 pub struct Options {
     backup: Option<BackupOptions>,
     forget: Option<ForgetOptions>,
+    check: Option<CheckOptions>,
+    copy: Option<CopyOptions>,
+    status: Option<StatusOptions>,
+    #[serde(default)]
+    hooks: HookOptions,
 }
 
 impl Options {
@@ -66,6 +626,19 @@ impl Options {
     pub fn forget(&self) -> Option<&ForgetOptions> {
         self.forget.as_ref()
     }
+    pub fn check(&self) -> Option<&CheckOptions> {
+        self.check.as_ref()
+    }
+    pub fn copy(&self) -> Option<&CopyOptions> {
+        self.copy.as_ref()
+    }
+    pub fn status(&self) -> Option<&StatusOptions> {
+        self.status.as_ref()
+    }
+    /// The hooks of whole runs. Only the global ones are used.
+    pub fn hooks(&self) -> &HookOptions {
+        &self.hooks
+    }
 }
 
 #[derive(Clone, Debug, Default, Deserialize)]
@@ -82,8 +655,16 @@ pub struct BackupOptions {
     #[serde(default)]
     iexclude_file: Vec<PathBuf>,
     #[serde(default)]
+    files_from: Vec<PathBuf>,
+    #[serde(default)]
+    files_from_verbatim: Vec<PathBuf>,
+    #[serde(default)]
+    files_from_raw: Vec<PathBuf>,
+    #[serde(default)]
     exclude_caches: bool,
     #[serde(default)]
+    exclude_gitignored: bool,
+    #[serde(default)]
     exclude_if_present: Vec<String>,
     #[serde(default)]
     exclude_larger_than: Option<String>,
@@ -92,6 +673,9 @@ pub struct BackupOptions {
     #[serde(default)]
     ignore_inode: bool,
     #[serde(default)]
+    force_rescan: bool,
+    parent: Option<String>,
+    #[serde(default)]
     no_scan: bool,
     #[serde(default)]
     one_file_system: bool,
@@ -101,6 +685,8 @@ pub struct BackupOptions {
     use_fs_snapshot: bool,
     #[serde(default)]
     with_atime: bool,
+    read_concurrency: Option<u32>,
+    timeout: Option<Duration>,
     #[serde(default)]
     hooks: HookOptions,
 }
@@ -121,9 +707,34 @@ impl BackupOptions {
     pub fn iexclude_file(&self) -> &Vec<PathBuf> {
         &self.iexclude_file
     }
+    pub fn files_from(&self) -> &Vec<PathBuf> {
+        &self.files_from
+    }
+    pub fn files_from_verbatim(&self) -> &Vec<PathBuf> {
+        &self.files_from_verbatim
+    }
+    pub fn files_from_raw(&self) -> &Vec<PathBuf> {
+        &self.files_from_raw
+    }
+    /// Whether the paths to back up are read from files, so that a location
+    /// does not need paths of its own.
+    pub fn has_files_from(&self) -> bool {
+        !self.files_from.is_empty()
+            || !self.files_from_verbatim.is_empty()
+            || !self.files_from_raw.is_empty()
+    }
     pub fn exclude_caches(&self) -> bool {
         self.exclude_caches
     }
+    /// Whether paths that are ignored by .gitignore files are excluded.
+    pub fn exclude_gitignored(&self) -> bool {
+        self.exclude_gitignored
+    }
+    /// Adds excludes that are generated for a backup, e.g. of ignored paths.
+    pub fn add_excludes(&mut self, patterns: &[String], file: &Path) {
+        self.exclude.extend_from_slice(patterns);
+        self.exclude_file.push(file.to_path_buf());
+    }
     pub fn exclude_if_present(&self) -> &Vec<String> {
         &self.exclude_if_present
     }
@@ -136,6 +747,15 @@ impl BackupOptions {
     pub fn ignore_inode(&self) -> bool {
         self.ignore_inode
     }
+    /// Whether all files are read again instead of only the modified ones.
+    pub fn force_rescan(&self) -> bool {
+        self.force_rescan
+    }
+    /// The snapshot whose files are compared with the current ones to detect
+    /// modifications, instead of the latest snapshot of the location.
+    pub fn parent(&self) -> Option<&str> {
+        self.parent.as_deref()
+    }
     pub fn no_scan(&self) -> bool {
         self.no_scan
     }
@@ -151,6 +771,12 @@ impl BackupOptions {
     pub fn with_atime(&self) -> bool {
         self.with_atime
     }
+    pub fn read_concurrency(&self) -> Option<u32> {
+        self.read_concurrency
+    }
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
     pub fn hooks(&self) -> &HookOptions {
         &self.hooks
     }
@@ -159,13 +785,54 @@ impl BackupOptions {
 #[derive(Clone, Debug, Default, Deserialize)]
 #[serde(rename_all = "kebab-case")]
 pub struct HookOptions {
-    r#if: Vec<CommandSeq>,
+    #[serde(default)]
+    r#if: Vec<Hook>,
+    #[serde(default)]
+    before: Vec<Hook>,
+    #[serde(default)]
+    after: Vec<Hook>,
+    #[serde(default)]
+    on_failure: Vec<Hook>,
+    #[serde(default)]
+    finally: Vec<Hook>,
 }
 
 impl HookOptions {
-    pub fn r#if(&self) -> &[CommandSeq] {
+    /// The hooks with additional commands that run after the BEFORE hooks and
+    /// before the FINALLY hooks.
+    pub fn surround(&self, before: Hook, finally: Hook) -> Self {
+        let mut hooks = self.clone();
+        hooks.before.push(before);
+        hooks.finally.insert(0, finally);
+        hooks
+    }
+    pub fn r#if(&self) -> &[Hook] {
         &self.r#if
     }
+    pub fn before(&self) -> &[Hook] {
+        &self.before
+    }
+    pub fn after(&self) -> &[Hook] {
+        &self.after
+    }
+    pub fn on_failure(&self) -> &[Hook] {
+        &self.on_failure
+    }
+    pub fn finally(&self) -> &[Hook] {
+        &self.finally
+    }
+    /// All hooks with the names of their kinds, e.g. "BEFORE".
+    pub fn all(&self) -> impl Iterator<Item = (&'static str, &Hook)> {
+        [
+            ("IF", &self.r#if),
+            ("BEFORE", &self.before),
+            ("AFTER", &self.after),
+            ("ON-FAILURE", &self.on_failure),
+            ("FINALLY", &self.finally),
+        ]
+        .into_iter()
+        .flat_map(|(name, hooks)| hooks.iter().map(move |hook| (name, hook)))
+    }
 }
 
 #[derive(Clone, Debug, Default, Deserialize)]
@@ -173,6 +840,8 @@ impl HookOptions {
 pub struct ForgetOptions {
     #[serde(default)]
     prune: bool,
+    prune_interval: Option<Duration>,
+    prune_every: Option<u32>,
     keep_last: Option<u32>,
     keep_hourly: Option<u32>,
     keep_daily: Option<u32>,
@@ -187,6 +856,7 @@ pub struct ForgetOptions {
     keep_within_yearly: Option<String>,
     #[serde(default)]
     keep_tag: Vec<String>,
+    timeout: Option<Duration>,
     #[serde(default)]
     hooks: HookOptions,
 }
@@ -195,6 +865,14 @@ impl ForgetOptions {
     pub fn prune(&self) -> bool {
         self.prune
     }
+    /// The minimum time between two prunes of a repository.
+    pub fn prune_interval(&self) -> Option<Duration> {
+        self.prune_interval
+    }
+    /// Prune a repository only with every nth forget.
+    pub fn prune_every(&self) -> Option<u32> {
+        self.prune_every
+    }
     pub fn keep_last(&self) -> Option<u32> {
         self.keep_last
     }
@@ -234,9 +912,98 @@ impl ForgetOptions {
     pub fn keep_tag(&self) -> &Vec<String> {
         &self.keep_tag
     }
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
     pub fn hooks(&self) -> &HookOptions {
         &self.hooks
     }
+    /// The options with the tags added to the kept tags if they have a keep
+    /// policy. Kept tags without further policy would forget all other
+    /// snapshots.
+    pub fn with_keep_tags(mut self, tags: &[String]) -> Self {
+        if self.has_policy() {
+            for tag in tags {
+                if !self.keep_tag.contains(tag) {
+                    self.keep_tag.push(tag.clone());
+                }
+            }
+        }
+        self
+    }
+    /// Whether any keep policy is configured. Without a policy, restic refuses
+    /// to forget snapshots.
+    pub fn has_policy(&self) -> bool {
+        [
+            self.keep_last,
+            self.keep_hourly,
+            self.keep_daily,
+            self.keep_weekly,
+            self.keep_monthly,
+            self.keep_yearly,
+        ]
+        .iter()
+        .any(Option::is_some)
+            || [
+                &self.keep_within,
+                &self.keep_within_hourly,
+                &self.keep_within_daily,
+                &self.keep_within_weekly,
+                &self.keep_within_monthly,
+                &self.keep_within_yearly,
+            ]
+            .iter()
+            .any(|k| k.is_some())
+            || !self.keep_tag.is_empty()
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct CheckOptions {
+    timeout: Option<Duration>,
+    interval: Option<Duration>,
+    #[serde(default)]
+    hooks: HookOptions,
+}
+
+impl CheckOptions {
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+    /// The minimum time between two checks of a repository in the maintenance
+    /// cycle.
+    pub fn interval(&self) -> Option<Duration> {
+        self.interval
+    }
+    pub fn hooks(&self) -> &HookOptions {
+        &self.hooks
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct CopyOptions {
+    #[serde(default)]
+    hooks: HookOptions,
+}
+
+impl CopyOptions {
+    pub fn hooks(&self) -> &HookOptions {
+        &self.hooks
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct StatusOptions {
+    max_age: Option<Duration>,
+}
+
+impl StatusOptions {
+    pub fn max_age(&self) -> Option<Duration> {
+        self.max_age
+    }
 }
 
 #[derive(Debug, Default, Deserialize)]
@@ -259,87 +1026,2054 @@ impl Environment {
 
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "kebab-case")]
-pub struct Repo {
-    #[serde(default)]
-    path: String,
-    #[serde(default, alias = "key")]
-    password: String,
+pub struct Daemon {
     #[serde(default)]
-    password_file: Option<PathBuf>,
-    #[serde(default)]
-    password_command: String,
+    jitter: Option<Duration>,
+    #[serde(default = "default_daemon_max_concurrent")]
+    max_concurrent: usize,
+    #[serde(default = "default_daemon_reload")]
+    reload: bool,
     #[serde(default)]
-    retry_lock: String,
+    control: Option<String>,
     #[serde(default)]
-    options: Vec<String>,
+    control_token: Option<String>,
     #[serde(default)]
-    environment: Environment,
+    dashboard: Option<String>,
 }
 
-impl Repo {
-    pub fn path(&self) -> &str {
-        &self.path
+fn default_daemon_reload() -> bool {
+    true
+}
+
+fn default_daemon_max_concurrent() -> usize {
+    1
+}
+
+impl Default for Daemon {
+    fn default() -> Self {
+        Self {
+            jitter: None,
+            max_concurrent: default_daemon_max_concurrent(),
+            reload: default_daemon_reload(),
+            control: None,
+            control_token: None,
+            dashboard: None,
+        }
     }
-    pub fn password(&self) -> &str {
-        &self.password
+}
+
+impl Daemon {
+    pub fn jitter(&self) -> Option<Duration> {
+        self.jitter
     }
-    pub fn password_file(&self) -> Option<&Path> {
-        self.password_file.as_deref()
+    pub fn max_concurrent(&self) -> usize {
+        self.max_concurrent.max(1)
     }
-    pub fn password_command(&self) -> &str {
-        &self.password_command
+    pub fn reload(&self) -> bool {
+        self.reload
     }
-    pub fn retry_lock(&self) -> &str {
-        &self.retry_lock
+    pub fn control(&self) -> Option<&str> {
+        self.control.as_deref()
     }
-    pub fn options(&self) -> &Vec<String> {
-        &self.options
+    pub fn control_token(&self) -> Option<&str> {
+        self.control_token.as_deref()
     }
-    pub fn environment(&self) -> &Environment {
-        &self.environment
+    pub fn dashboard(&self) -> Option<&str> {
+        self.dashboard.as_deref()
     }
 }
 
 #[derive(Debug, Deserialize)]
-pub struct Location {
-    #[serde(alias = "from")]
-    paths: Vec<PathBuf>,
-    #[serde(alias = "to")]
-    repos: Vec<Name>,
-    #[serde(default)]
-    options: Options,
+#[serde(rename_all = "kebab-case")]
+pub struct Watch {
+    #[serde(default = "default_watch_quiet_period")]
+    quiet_period: Duration,
+    #[serde(default = "default_watch_min_interval")]
+    min_interval: Duration,
 }
 
-impl Location {
-    pub fn paths(&self) -> &Vec<PathBuf> {
-        &self.paths
-    }
-    pub fn repos(&self) -> &Vec<Name> {
-        &self.repos
-    }
-    pub fn options(&self) -> &Options {
-        &self.options
-    }
+fn default_watch_quiet_period() -> Duration {
+    Duration(std::time::Duration::from_secs(60))
 }
 
-#[derive(Clone, Debug, Eq, Hash, PartialEq)]
-pub struct Name(String);
+fn default_watch_min_interval() -> Duration {
+    Duration(std::time::Duration::from_secs(15 * 60))
+}
 
-impl Name {
-    pub fn parse(s: &str) -> Result<Self, NameParseError> {
-        if s.chars().all(Self::is_valid_char) {
-            Ok(Self(s.to_string()))
-        } else {
-            Err(NameParseError(
-                "Invalid name (only [A-Za-z0-9_-] are allowed).".to_string(),
-            ))
+impl Default for Watch {
+    fn default() -> Self {
+        Self {
+            quiet_period: default_watch_quiet_period(),
+            min_interval: default_watch_min_interval(),
         }
     }
+}
 
-    fn is_valid_char(c: char) -> bool {
-        c.is_ascii_alphanumeric() || c == '_' || c == '-'
+impl Watch {
+    pub fn quiet_period(&self) -> Duration {
+        self.quiet_period
     }
-
+    pub fn min_interval(&self) -> Duration {
+        self.min_interval
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Lock {
+    #[serde(default)]
+    on_overlap: OnOverlap,
+    #[serde(default = "default_lock_timeout")]
+    timeout: Duration,
+    #[serde(default)]
+    dir: Option<PathBuf>,
+}
+
+fn default_lock_timeout() -> Duration {
+    Duration(std::time::Duration::from_secs(60 * 60))
+}
+
+impl Default for Lock {
+    fn default() -> Self {
+        Self {
+            on_overlap: Default::default(),
+            timeout: default_lock_timeout(),
+            dir: None,
+        }
+    }
+}
+
+impl Lock {
+    pub fn on_overlap(&self) -> OnOverlap {
+        self.on_overlap
+    }
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+    pub fn dir(&self) -> Option<&Path> {
+        self.dir.as_deref()
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OnOverlap {
+    #[default]
+    Wait,
+    Skip,
+    Fail,
+}
+
+/// The CPU and I/O priority of restic, hooks and paths commands.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Priority {
+    #[serde(default)]
+    cpu: PriorityLevel,
+    #[serde(default)]
+    io: PriorityLevel,
+}
+
+impl Priority {
+    pub fn cpu(&self) -> PriorityLevel {
+        self.cpu
+    }
+    /// The I/O priority. It is only supported on Linux.
+    pub fn io(&self) -> PriorityLevel {
+        self.io
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum PriorityLevel {
+    #[default]
+    Normal,
+    /// Below other processes, but still ahead of idle ones.
+    Low,
+    /// Only when the system is otherwise idle.
+    Idle,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Retry {
+    #[serde(default)]
+    retries: u32,
+    #[serde(default = "default_retry_backoff")]
+    backoff: Duration,
+    #[serde(default)]
+    exit_codes: Vec<i32>,
+    #[serde(default = "default_retry_patterns")]
+    patterns: Vec<String>,
+}
+
+fn default_retry_backoff() -> Duration {
+    Duration(std::time::Duration::from_secs(30))
+}
+
+fn default_retry_patterns() -> Vec<String> {
+    [
+        "connection refused",
+        "connection reset",
+        "connection timed out",
+        "i/o timeout",
+        "network is unreachable",
+        "no route to host",
+        "temporary failure in name resolution",
+        "tls handshake timeout",
+        "unexpected eof",
+    ]
+    .map(String::from)
+    .to_vec()
+}
+
+impl Default for Retry {
+    fn default() -> Self {
+        Self {
+            retries: 0,
+            backoff: default_retry_backoff(),
+            exit_codes: Vec::new(),
+            patterns: default_retry_patterns(),
+        }
+    }
+}
+
+impl Retry {
+    pub fn retries(&self) -> u32 {
+        self.retries
+    }
+    pub fn backoff(&self) -> Duration {
+        self.backoff
+    }
+    pub fn exit_codes(&self) -> &[i32] {
+        &self.exit_codes
+    }
+    pub fn patterns(&self) -> &[String] {
+        &self.patterns
+    }
+}
+
+/// Settings of the maintenance command.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Maintenance {
+    read_data_subset: Option<String>,
+    prune_interval: Option<Duration>,
+    prune_timeout: Option<Duration>,
+    #[serde(default)]
+    hooks: HookOptions,
+}
+
+impl Maintenance {
+    /// The part of the data that is read by the check, e.g. "5%".
+    pub fn read_data_subset(&self) -> Option<&str> {
+        self.read_data_subset.as_deref()
+    }
+    /// The minimum time between two prunes of a repository.
+    pub fn prune_interval(&self) -> Option<Duration> {
+        self.prune_interval
+    }
+    pub fn prune_timeout(&self) -> Option<Duration> {
+        self.prune_timeout
+    }
+    pub fn hooks(&self) -> &HookOptions {
+        &self.hooks
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Redact {
+    #[serde(default)]
+    env: Vec<String>,
+}
+
+impl Redact {
+    pub fn env(&self) -> &[String] {
+        &self.env
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Metrics {
+    #[serde(default)]
+    textfile_dir: Option<PathBuf>,
+    #[serde(default)]
+    pushgateway: Option<Pushgateway>,
+}
+
+impl Metrics {
+    pub fn textfile_dir(&self) -> Option<&Path> {
+        self.textfile_dir.as_deref()
+    }
+    pub fn pushgateway(&self) -> Option<&Pushgateway> {
+        self.pushgateway.as_ref()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Pushgateway {
+    url: String,
+    #[serde(default = "default_pushgateway_job")]
+    job: String,
+    #[serde(default)]
+    username: Option<String>,
+    #[serde(default)]
+    password: Option<String>,
+}
+
+fn default_pushgateway_job() -> String {
+    "aresticrat".to_string()
+}
+
+impl Pushgateway {
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+    pub fn job(&self) -> &str {
+        &self.job
+    }
+    pub fn username(&self) -> Option<&str> {
+        self.username.as_deref()
+    }
+    pub fn password(&self) -> Option<&str> {
+        self.password.as_deref()
+    }
+}
+
+/// The names of the built-in notification services, which routes may refer
+/// to besides plugins.
+const NOTIFY_SERVICES: [&str; 8] = [
+    "healthchecks",
+    "webhook",
+    "email",
+    "ntfy",
+    "slack",
+    "discord",
+    "gotify",
+    "pushover",
+];
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Notify {
+    #[serde(default)]
+    healthchecks: Option<Healthchecks>,
+    #[serde(default)]
+    webhook: Option<Webhook>,
+    #[serde(default)]
+    email: Option<Email>,
+    #[serde(default)]
+    ntfy: Option<Ntfy>,
+    #[serde(default)]
+    slack: Option<ChatWebhook>,
+    #[serde(default)]
+    discord: Option<ChatWebhook>,
+    #[serde(default)]
+    gotify: Option<PushService>,
+    #[serde(default)]
+    pushover: Option<PushService>,
+    #[serde(default)]
+    retries: u32,
+    #[serde(default = "default_notify_retry_delay")]
+    retry_delay: Duration,
+    #[serde(default)]
+    routes: HashMap<String, Vec<String>>,
+    #[serde(default)]
+    templates: NotifyTemplates,
+    #[serde(default)]
+    plugins: HashMap<String, CommandSeq>,
+    #[serde(default)]
+    plugins_dir: Option<PathBuf>,
+}
+
+fn default_notify_retry_delay() -> Duration {
+    Duration(std::time::Duration::from_secs(10))
+}
+
+impl Default for Notify {
+    fn default() -> Self {
+        Self {
+            healthchecks: None,
+            webhook: None,
+            email: None,
+            ntfy: None,
+            slack: None,
+            discord: None,
+            gotify: None,
+            pushover: None,
+            retries: 0,
+            retry_delay: default_notify_retry_delay(),
+            routes: HashMap::new(),
+            templates: NotifyTemplates::default(),
+            plugins: HashMap::new(),
+            plugins_dir: None,
+        }
+    }
+}
+
+impl Notify {
+    pub fn healthchecks(&self) -> Option<&Healthchecks> {
+        self.healthchecks.as_ref()
+    }
+    pub fn webhook(&self) -> Option<&Webhook> {
+        self.webhook.as_ref()
+    }
+    pub fn email(&self) -> Option<&Email> {
+        self.email.as_ref()
+    }
+    pub fn ntfy(&self) -> Option<&Ntfy> {
+        self.ntfy.as_ref()
+    }
+    pub fn slack(&self) -> Option<&ChatWebhook> {
+        self.slack.as_ref()
+    }
+    pub fn discord(&self) -> Option<&ChatWebhook> {
+        self.discord.as_ref()
+    }
+    pub fn gotify(&self) -> Option<&PushService> {
+        self.gotify.as_ref()
+    }
+    pub fn pushover(&self) -> Option<&PushService> {
+        self.pushover.as_ref()
+    }
+    pub fn retries(&self) -> u32 {
+        self.retries
+    }
+    pub fn retry_delay(&self) -> Duration {
+        self.retry_delay
+    }
+    pub fn routes(&self) -> &HashMap<String, Vec<String>> {
+        &self.routes
+    }
+    pub fn templates(&self) -> &NotifyTemplates {
+        &self.templates
+    }
+    pub fn plugins(&self) -> &HashMap<String, CommandSeq> {
+        &self.plugins
+    }
+    pub fn plugins_dir(&self) -> Option<&Path> {
+        self.plugins_dir.as_deref()
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct NotifyTemplates {
+    #[serde(default)]
+    title: Option<String>,
+    #[serde(default)]
+    message: Option<String>,
+}
+
+impl NotifyTemplates {
+    pub fn title(&self) -> Option<&str> {
+        self.title.as_deref()
+    }
+    pub fn message(&self) -> Option<&str> {
+        self.message.as_deref()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Healthchecks {
+    ping_url: String,
+}
+
+impl Healthchecks {
+    pub fn ping_url(&self) -> &str {
+        &self.ping_url
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Webhook {
+    url: String,
+    #[serde(default = "default_webhook_method")]
+    method: String,
+    #[serde(default)]
+    headers: HashMap<String, String>,
+    #[serde(default)]
+    body: Option<String>,
+}
+
+fn default_webhook_method() -> String {
+    "POST".to_string()
+}
+
+impl Webhook {
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+    pub fn method(&self) -> &str {
+        &self.method
+    }
+    pub fn headers(&self) -> &HashMap<String, String> {
+        &self.headers
+    }
+    pub fn body(&self) -> Option<&str> {
+        self.body.as_deref()
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Email {
+    server: String,
+    #[serde(default)]
+    port: Option<u16>,
+    #[serde(default)]
+    tls: EmailTls,
+    #[serde(default)]
+    username: Option<String>,
+    #[serde(default)]
+    password: Option<String>,
+    from: String,
+    to: Vec<String>,
+    #[serde(default)]
+    always: bool,
+}
+
+impl Email {
+    pub fn server(&self) -> &str {
+        &self.server
+    }
+    pub fn port(&self) -> Option<u16> {
+        self.port
+    }
+    pub fn tls(&self) -> EmailTls {
+        self.tls
+    }
+    pub fn username(&self) -> Option<&str> {
+        self.username.as_deref()
+    }
+    pub fn password(&self) -> Option<&str> {
+        self.password.as_deref()
+    }
+    pub fn from(&self) -> &str {
+        &self.from
+    }
+    pub fn to(&self) -> &Vec<String> {
+        &self.to
+    }
+    pub fn always(&self) -> bool {
+        self.always
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum EmailTls {
+    #[default]
+    Starttls,
+    Tls,
+    None,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Ntfy {
+    url: String,
+    #[serde(default)]
+    token: Option<String>,
+    #[serde(default)]
+    priority: NtfyPriority,
+}
+
+impl Ntfy {
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+    pub fn token(&self) -> Option<&str> {
+        self.token.as_deref()
+    }
+    pub fn priority(&self) -> &NtfyPriority {
+        &self.priority
+    }
+}
+
+/// Message priorities by event status (1-5 or min, low, default, high, max).
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct NtfyPriority {
+    #[serde(default = "default_ntfy_priority_succeeded")]
+    succeeded: String,
+    #[serde(default = "default_ntfy_priority_failed")]
+    failed: String,
+}
+
+fn default_ntfy_priority_succeeded() -> String {
+    "low".to_string()
+}
+
+fn default_ntfy_priority_failed() -> String {
+    "high".to_string()
+}
+
+impl Default for NtfyPriority {
+    fn default() -> Self {
+        Self {
+            succeeded: default_ntfy_priority_succeeded(),
+            failed: default_ntfy_priority_failed(),
+        }
+    }
+}
+
+impl NtfyPriority {
+    pub fn succeeded(&self) -> &str {
+        &self.succeeded
+    }
+    pub fn failed(&self) -> &str {
+        &self.failed
+    }
+}
+
+/// An incoming webhook of a chat service like Slack or Discord.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ChatWebhook {
+    url: String,
+    #[serde(default)]
+    channel: Option<String>,
+    #[serde(default)]
+    template: Option<String>,
+}
+
+impl ChatWebhook {
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+    pub fn channel(&self) -> Option<&str> {
+        self.channel.as_deref()
+    }
+    pub fn template(&self) -> Option<&str> {
+        self.template.as_deref()
+    }
+}
+
+/// A push notification service that is addressed by URL and token, like
+/// Gotify or Pushover.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct PushService {
+    #[serde(default)]
+    url: Option<String>,
+    token: String,
+    #[serde(default)]
+    user: Option<String>,
+    #[serde(default)]
+    priority: PushPriority,
+}
+
+impl PushService {
+    pub fn url(&self) -> Option<&str> {
+        self.url.as_deref()
+    }
+    pub fn token(&self) -> &str {
+        &self.token
+    }
+    pub fn user(&self) -> Option<&str> {
+        self.user.as_deref()
+    }
+    pub fn priority_succeeded(&self, default: i32) -> i32 {
+        self.priority.succeeded.unwrap_or(default)
+    }
+    pub fn priority_failed(&self, default: i32) -> i32 {
+        self.priority.failed.unwrap_or(default)
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct PushPriority {
+    #[serde(default)]
+    succeeded: Option<i32>,
+    #[serde(default)]
+    failed: Option<i32>,
+}
+
+fn default_cache_password_command() -> bool {
+    true
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Repo {
+    #[serde(default)]
+    path: String,
+    #[serde(default, alias = "key")]
+    password: String,
+    #[serde(default, alias = "key-file")]
+    password_file: Option<PathBuf>,
+    #[serde(default, alias = "key-command")]
+    password_command: String,
+    #[serde(default = "default_cache_password_command")]
+    cache_password_command: bool,
+    #[serde(default, alias = "key-keyring")]
+    password_keyring: Option<KeyringEntry>,
+    #[serde(default, alias = "key-credential")]
+    password_credential: Option<String>,
+    #[serde(default)]
+    retry_lock: String,
+    no_lock: Option<bool>,
+    #[serde(default)]
+    extra_args: ExtraArgs,
+    #[serde(default)]
+    timeout: Timeouts,
+    #[serde(default)]
+    check: RepoCheck,
+    #[serde(default)]
+    hooks: RepoHooks,
+    probe: Option<Probe>,
+    #[serde(default)]
+    options: Vec<String>,
+    #[serde(flatten)]
+    backends: Backends,
+    pack_size: Option<u32>,
+    limit_upload: Option<u32>,
+    limit_download: Option<u32>,
+    compression: Option<Compression>,
+    cache_dir: Option<PathBuf>,
+    no_cache: Option<bool>,
+    #[serde(default)]
+    cacert: Vec<PathBuf>,
+    tls_client_cert: Option<PathBuf>,
+    #[serde(default)]
+    insecure_tls: bool,
+    max_cores: Option<u32>,
+    min_free_space: Option<Size>,
+    #[serde(default)]
+    replicate_to: Vec<Name>,
+    #[serde(default)]
+    environment: Environment,
+}
+
+/// The variables of repository paths, e.g. `{{hostname}}`.
+const REPO_PATH_VARIABLES: [&str; 4] = ["hostname", "date", "year", "month"];
+
+fn repo_path_variable(name: &str) -> Option<String> {
+    let now = chrono::Local::now();
+    match name {
+        "hostname" => hostname::get()
+            .ok()
+            .map(|name| name.to_string_lossy().into_owned()),
+        "date" => Some(now.format("%Y-%m-%d").to_string()),
+        "year" => Some(now.format("%Y").to_string()),
+        "month" => Some(now.format("%m").to_string()),
+        _ => None,
+    }
+}
+
+impl Repo {
+    /// The path with its variables replaced by their current values, e.g.
+    /// `{{hostname}}` by the name of this host.
+    pub fn path(&self) -> String {
+        crate::template::render(&self.path, repo_path_variable, str::to_string)
+    }
+    /// The variables of the path that are not defined.
+    fn unknown_path_variables(&self) -> Vec<String> {
+        let unknown = std::cell::RefCell::new(Vec::new());
+        crate::template::render(
+            &self.path,
+            |name| {
+                if !REPO_PATH_VARIABLES.contains(&name) {
+                    unknown.borrow_mut().push(name.to_string());
+                }
+                None
+            },
+            str::to_string,
+        );
+        unknown.into_inner()
+    }
+    pub fn password(&self) -> &str {
+        &self.password
+    }
+    pub fn password_file(&self) -> Option<&Path> {
+        self.password_file.as_deref()
+    }
+    pub fn password_command(&self) -> &str {
+        &self.password_command
+    }
+    pub fn cache_password_command(&self) -> bool {
+        self.cache_password_command
+    }
+    pub fn password_keyring(&self) -> Option<&KeyringEntry> {
+        self.password_keyring.as_ref()
+    }
+    pub fn password_credential(&self) -> Option<&str> {
+        self.password_credential.as_deref()
+    }
+
+    /// Names of the options that define the password of this repository.
+    fn password_sources(&self) -> Vec<&'static str> {
+        [
+            ("password/key", !self.password.is_empty()),
+            ("password-file/key-file", self.password_file.is_some()),
+            (
+                "password-command/key-command",
+                !self.password_command.is_empty(),
+            ),
+            (
+                "password-keyring/key-keyring",
+                self.password_keyring.is_some(),
+            ),
+            (
+                "password-credential/key-credential",
+                self.password_credential.is_some(),
+            ),
+        ]
+        .into_iter()
+        .filter_map(|(name, set)| set.then_some(name))
+        .collect()
+    }
+
+    /// How long restic retries to lock the repository. It takes precedence
+    /// over the global setting, unless it is empty.
+    pub fn retry_lock(&self) -> &str {
+        &self.retry_lock
+    }
+    /// Whether read-only commands run without locking the repository. It
+    /// takes precedence over the global setting.
+    pub fn no_lock(&self) -> Option<bool> {
+        self.no_lock
+    }
+    /// Additional arguments of restic subcommands for this repository. They
+    /// follow the global ones.
+    pub fn extra_args(&self) -> &ExtraArgs {
+        &self.extra_args
+    }
+    pub fn timeout(&self) -> &Timeouts {
+        &self.timeout
+    }
+    pub fn check(&self) -> &RepoCheck {
+        &self.check
+    }
+    pub fn hooks(&self) -> &RepoHooks {
+        &self.hooks
+    }
+    /// How the reachability of the repository is probed before operations, if
+    /// at all.
+    pub fn probe(&self) -> Option<&Probe> {
+        self.probe.as_ref()
+    }
+    pub fn rclone(&self) -> &Rclone {
+        &self.backends.rclone
+    }
+    /// The extended options of the backend (`--option`): the raw ones
+    /// followed by those of the backend settings, e.g. `rclone.connections=8`.
+    pub fn backend_options(&self) -> Vec<String> {
+        let mut options = self.options.clone();
+        for (_, backend_options) in self.backends.options() {
+            options.extend(backend_options);
+        }
+        options
+    }
+    /// The directory of the repository, if it uses the local backend.
+    pub fn local_path(&self) -> Option<PathBuf> {
+        if self.backend()? != "local" {
+            return None;
+        }
+        let path = self.path();
+        Some(PathBuf::from(path.strip_prefix("local:").unwrap_or(&path)))
+    }
+    /// The backend of the repository, e.g. "s3", taken from its path.
+    fn backend(&self) -> Option<&str> {
+        if self.path.is_empty() {
+            return None;
+        }
+        Some(match self.path.split_once(':') {
+            Some((scheme, _)) if Backends::NAMES.contains(&scheme) => scheme,
+            _ => "local",
+        })
+    }
+    /// The target size of pack files in MiB.
+    pub fn pack_size(&self) -> Option<u32> {
+        self.pack_size
+    }
+    /// The upload bandwidth limit in KiB/s.
+    pub fn limit_upload(&self) -> Option<u32> {
+        self.limit_upload
+    }
+    /// The download bandwidth limit in KiB/s.
+    pub fn limit_download(&self) -> Option<u32> {
+        self.limit_download
+    }
+    pub fn compression(&self) -> Option<Compression> {
+        self.compression
+    }
+    /// The cache directory of restic. It takes precedence over the global
+    /// one.
+    pub fn cache_dir(&self) -> Option<&Path> {
+        self.cache_dir.as_deref()
+    }
+    /// Whether restic runs without a cache. It takes precedence over the
+    /// global setting.
+    pub fn no_cache(&self) -> Option<bool> {
+        self.no_cache
+    }
+    /// Certificates of authorities that sign the TLS certificate of the
+    /// backend, in addition to the system ones.
+    pub fn cacert(&self) -> &[PathBuf] {
+        &self.cacert
+    }
+    /// A file with the certificate and key for TLS client authentication.
+    pub fn tls_client_cert(&self) -> Option<&Path> {
+        self.tls_client_cert.as_deref()
+    }
+    pub fn insecure_tls(&self) -> bool {
+        self.insecure_tls
+    }
+    /// The number of CPU cores restic may use at once (GOMAXPROCS).
+    pub fn max_cores(&self) -> Option<u32> {
+        self.max_cores
+    }
+    /// The free space the volumes of the restic cache and of the repository,
+    /// if it is local, need before backups. It takes precedence over the
+    /// global setting.
+    pub fn min_free_space(&self) -> Option<Size> {
+        self.min_free_space
+    }
+    /// The repositories to which new snapshots of this repository are copied
+    /// after backups of the run command.
+    pub fn replicate_to(&self) -> &[Name] {
+        &self.replicate_to
+    }
+    pub fn environment(&self) -> &Environment {
+        &self.environment
+    }
+}
+
+/// The settings of the backends of a repository, which are passed to restic
+/// as extended options. Unknown keys are rejected, so that typos are reported
+/// when the configuration is loaded.
+#[derive(Clone, Debug, Default, Deserialize)]
+struct Backends {
+    #[serde(default)]
+    azure: Azure,
+    #[serde(default)]
+    b2: B2,
+    #[serde(default)]
+    gs: Gs,
+    #[serde(default)]
+    local: Local,
+    #[serde(default)]
+    rclone: Rclone,
+    #[serde(default)]
+    rest: Rest,
+    #[serde(default)]
+    s3: S3,
+    #[serde(default)]
+    sftp: Sftp,
+    #[serde(default)]
+    swift: Swift,
+}
+
+impl Backends {
+    const NAMES: [&str; 9] = [
+        "azure", "b2", "gs", "local", "rclone", "rest", "s3", "sftp", "swift",
+    ];
+
+    /// The options of every backend with settings, e.g.
+    /// `("s3", ["s3.region=eu-west-1"])`.
+    fn options(&self) -> Vec<(&'static str, Vec<String>)> {
+        [
+            ("azure", self.azure.options()),
+            ("b2", self.b2.options()),
+            ("gs", self.gs.options()),
+            ("local", self.local.options()),
+            ("rclone", self.rclone.options()),
+            ("rest", self.rest.options()),
+            ("s3", self.s3.options()),
+            ("sftp", self.sftp.options()),
+            ("swift", self.swift.options()),
+        ]
+        .into_iter()
+        .filter(|(_, options)| !options.is_empty())
+        .collect()
+    }
+}
+
+/// Formats the settings of a backend that are set as extended options, e.g.
+/// `s3.region=eu-west-1`.
+fn backend_options<const N: usize>(
+    backend: &str,
+    settings: [(&str, Option<String>); N],
+) -> Vec<String> {
+    settings
+        .into_iter()
+        .filter_map(|(key, value)| value.map(|v| format!("{backend}.{key}={v}")))
+        .collect()
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct Azure {
+    connections: Option<u32>,
+    endpoint_suffix: Option<String>,
+    access_tier: Option<String>,
+}
+
+impl Azure {
+    fn options(&self) -> Vec<String> {
+        backend_options(
+            "azure",
+            [
+                ("connections", self.connections.map(|n| n.to_string())),
+                ("endpoint-suffix", self.endpoint_suffix.clone()),
+                ("access-tier", self.access_tier.clone()),
+            ],
+        )
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct B2 {
+    connections: Option<u32>,
+}
+
+impl B2 {
+    fn options(&self) -> Vec<String> {
+        backend_options(
+            "b2",
+            [("connections", self.connections.map(|n| n.to_string()))],
+        )
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct Gs {
+    connections: Option<u32>,
+    region: Option<String>,
+}
+
+impl Gs {
+    fn options(&self) -> Vec<String> {
+        backend_options(
+            "gs",
+            [
+                ("connections", self.connections.map(|n| n.to_string())),
+                ("region", self.region.clone()),
+            ],
+        )
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct Local {
+    connections: Option<u32>,
+}
+
+impl Local {
+    fn options(&self) -> Vec<String> {
+        backend_options(
+            "local",
+            [("connections", self.connections.map(|n| n.to_string()))],
+        )
+    }
+}
+
+/// The settings of the rclone backend of a repository.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Rclone {
+    program: Option<String>,
+    args: Option<String>,
+    connections: Option<u32>,
+    timeout: Option<Duration>,
+}
+
+impl Rclone {
+    /// The rclone executable. Defaults to `rclone`.
+    pub fn program(&self) -> &str {
+        self.program.as_deref().unwrap_or("rclone")
+    }
+
+    fn options(&self) -> Vec<String> {
+        backend_options(
+            "rclone",
+            [
+                ("program", self.program.clone()),
+                ("args", self.args.clone()),
+                ("connections", self.connections.map(|n| n.to_string())),
+                (
+                    "timeout",
+                    self.timeout
+                        .map(|d| format!("{}s", std::time::Duration::from(d).as_secs())),
+                ),
+            ],
+        )
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct Rest {
+    connections: Option<u32>,
+}
+
+impl Rest {
+    fn options(&self) -> Vec<String> {
+        backend_options(
+            "rest",
+            [("connections", self.connections.map(|n| n.to_string()))],
+        )
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct S3 {
+    connections: Option<u32>,
+    region: Option<String>,
+    storage_class: Option<String>,
+    bucket_lookup: Option<S3BucketLookup>,
+    list_objects_v1: Option<bool>,
+    unsafe_anonymous_auth: Option<bool>,
+}
+
+impl S3 {
+    fn options(&self) -> Vec<String> {
+        backend_options(
+            "s3",
+            [
+                ("connections", self.connections.map(|n| n.to_string())),
+                ("region", self.region.clone()),
+                ("storage-class", self.storage_class.clone()),
+                (
+                    "bucket-lookup",
+                    self.bucket_lookup.map(|l| l.as_str().to_string()),
+                ),
+                (
+                    "list-objects-v1",
+                    self.list_objects_v1.map(|b| b.to_string()),
+                ),
+                (
+                    "unsafe-anonymous-auth",
+                    self.unsafe_anonymous_auth.map(|b| b.to_string()),
+                ),
+            ],
+        )
+    }
+}
+
+/// How the S3 bucket is addressed.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+enum S3BucketLookup {
+    Auto,
+    Dns,
+    Path,
+}
+
+impl S3BucketLookup {
+    fn as_str(self) -> &'static str {
+        match self {
+            S3BucketLookup::Auto => "auto",
+            S3BucketLookup::Dns => "dns",
+            S3BucketLookup::Path => "path",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct Sftp {
+    command: Option<String>,
+    args: Option<String>,
+    connections: Option<u32>,
+}
+
+impl Sftp {
+    fn options(&self) -> Vec<String> {
+        backend_options(
+            "sftp",
+            [
+                ("command", self.command.clone()),
+                ("args", self.args.clone()),
+                ("connections", self.connections.map(|n| n.to_string())),
+            ],
+        )
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct Swift {
+    connections: Option<u32>,
+}
+
+impl Swift {
+    fn options(&self) -> Vec<String> {
+        backend_options(
+            "swift",
+            [("connections", self.connections.map(|n| n.to_string()))],
+        )
+    }
+}
+
+/// Additional arguments of restic subcommands, which are passed to restic as
+/// is, e.g. flags that aresticrat does not support (yet).
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExtraArgs {
+    #[serde(default)]
+    backup: Vec<String>,
+    #[serde(default)]
+    forget: Vec<String>,
+    #[serde(default)]
+    check: Vec<String>,
+    #[serde(default)]
+    copy: Vec<String>,
+    #[serde(default)]
+    prune: Vec<String>,
+    #[serde(default)]
+    unlock: Vec<String>,
+    #[serde(default)]
+    init: Vec<String>,
+    #[serde(default)]
+    snapshots: Vec<String>,
+}
+
+impl ExtraArgs {
+    pub fn backup(&self) -> &[String] {
+        &self.backup
+    }
+    pub fn forget(&self) -> &[String] {
+        &self.forget
+    }
+    pub fn check(&self) -> &[String] {
+        &self.check
+    }
+    pub fn copy(&self) -> &[String] {
+        &self.copy
+    }
+    pub fn prune(&self) -> &[String] {
+        &self.prune
+    }
+    pub fn unlock(&self) -> &[String] {
+        &self.unlock
+    }
+    pub fn init(&self) -> &[String] {
+        &self.init
+    }
+    pub fn snapshots(&self) -> &[String] {
+        &self.snapshots
+    }
+
+    /// These arguments followed by the other ones.
+    pub fn chain(&self, other: &ExtraArgs) -> ExtraArgs {
+        let chain = |a: &[String], b: &[String]| [a, b].concat();
+        ExtraArgs {
+            backup: chain(&self.backup, &other.backup),
+            forget: chain(&self.forget, &other.forget),
+            check: chain(&self.check, &other.check),
+            copy: chain(&self.copy, &other.copy),
+            prune: chain(&self.prune, &other.prune),
+            unlock: chain(&self.unlock, &other.unlock),
+            init: chain(&self.init, &other.init),
+            snapshots: chain(&self.snapshots, &other.snapshots),
+        }
+    }
+}
+
+/// The compression mode of a repository.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Compression {
+    Auto,
+    Max,
+    Off,
+}
+
+impl Compression {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Compression::Auto => "auto",
+            Compression::Max => "max",
+            Compression::Off => "off",
+        }
+    }
+}
+
+/// How restic reports the progress of running commands. Its output is not a
+/// terminal, so every update is a line of its own.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum Progress {
+    /// An update every second.
+    Live,
+    /// An update every minute.
+    #[default]
+    Periodic,
+    /// No updates, only the final summary.
+    Off,
+}
+
+impl Progress {
+    /// The default number of updates per second, if any.
+    pub fn fps(self) -> Option<f64> {
+        match self {
+            Progress::Live => Some(1.0),
+            Progress::Periodic => Some(1.0 / 60.0),
+            Progress::Off => None,
+        }
+    }
+}
+
+/// How the reachability of a repository is probed before operations: by a TCP
+/// connection to the address (by default the server of the repository path)
+/// or by an HTTP HEAD request to the URL.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Probe {
+    address: Option<String>,
+    url: Option<String>,
+    #[serde(default = "default_probe_timeout")]
+    timeout: Duration,
+    #[serde(default)]
+    on_unreachable: OnUnreachable,
+    #[serde(default = "default_probe_wait")]
+    wait: Duration,
+}
+
+fn default_probe_timeout() -> Duration {
+    Duration(std::time::Duration::from_secs(5))
+}
+
+fn default_probe_wait() -> Duration {
+    Duration(std::time::Duration::from_secs(5 * 60))
+}
+
+impl Probe {
+    /// The address of a TCP connection, e.g. "nas:8000".
+    pub fn address(&self) -> Option<&str> {
+        self.address.as_deref()
+    }
+    /// The URL of an HTTP HEAD request.
+    pub fn url(&self) -> Option<&str> {
+        self.url.as_deref()
+    }
+    /// The time a single probe may take.
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+    pub fn on_unreachable(&self) -> OnUnreachable {
+        self.on_unreachable
+    }
+    /// How long an unreachable repository is waited for.
+    pub fn wait(&self) -> Duration {
+        self.wait
+    }
+}
+
+/// What an operation does if the repository is unreachable.
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OnUnreachable {
+    /// Wait until the repository can be reached, but at most for a while.
+    Wait,
+    /// Skip the repository with a warning.
+    Skip,
+    #[default]
+    Fail,
+}
+
+/// Hooks of a repository. They run once per run, no matter how many locations
+/// use the repository.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct RepoHooks {
+    #[serde(default)]
+    before: Vec<Hook>,
+    #[serde(default)]
+    after: Vec<Hook>,
+}
+
+impl RepoHooks {
+    /// Commands that run before the first operation on the repository.
+    pub fn before(&self) -> &[Hook] {
+        &self.before
+    }
+    /// Commands that run at the end of a run that used the repository.
+    pub fn after(&self) -> &[Hook] {
+        &self.after
+    }
+}
+
+/// Check settings of a repository. They take precedence over the check
+/// options of the locations.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct RepoCheck {
+    interval: Option<Duration>,
+    read_data_subset: Option<String>,
+}
+
+impl RepoCheck {
+    pub fn interval(&self) -> Option<Duration> {
+        self.interval
+    }
+    pub fn read_data_subset(&self) -> Option<&str> {
+        self.read_data_subset.as_deref()
+    }
+}
+
+/// Timeouts of operations on a repository. They take precedence over the
+/// timeouts of the operation options.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Timeouts {
+    backup: Option<Duration>,
+    forget: Option<Duration>,
+    check: Option<Duration>,
+}
+
+impl Timeouts {
+    pub fn backup(&self) -> Option<Duration> {
+        self.backup
+    }
+    pub fn forget(&self) -> Option<Duration> {
+        self.forget
+    }
+    pub fn check(&self) -> Option<Duration> {
+        self.check
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Location {
+    #[serde(alias = "from", default)]
+    paths: Vec<PathBuf>,
+    #[serde(rename = "paths-command", default)]
+    paths_command: Option<CommandSeq>,
+    #[serde(rename = "missing-paths", default)]
+    missing_paths: Option<MissingPaths>,
+    #[serde(default)]
+    command: Option<CommandSeq>,
+    #[serde(rename = "stdin-filename", default)]
+    stdin_filename: Option<String>,
+    #[serde(rename = "type", default)]
+    kind: Option<LocationKind>,
+    #[serde(flatten)]
+    database: Database,
+    #[serde(flatten)]
+    volume: Volume,
+    #[serde(alias = "to")]
+    repos: Vec<Name>,
+    #[serde(default)]
+    options: Options,
+    #[serde(default)]
+    notify: Notify,
+    #[serde(default)]
+    schedule: Schedule,
+    #[serde(rename = "extra-args", default)]
+    extra_args: ExtraArgs,
+    #[serde(rename = "max-cores", default)]
+    max_cores: Option<u32>,
+    #[serde(rename = "skip-on-battery", default)]
+    skip_on_battery: bool,
+    #[serde(rename = "skip-on-metered", default)]
+    skip_on_metered: bool,
+    #[serde(rename = "repo-forget", default)]
+    repo_forget: HashMap<Name, ForgetOptions>,
+    #[serde(rename = "min-interval", default)]
+    min_interval: Option<Duration>,
+    #[serde(rename = "depends-on", default)]
+    depends_on: Vec<Name>,
+    #[serde(default)]
+    priority: i32,
+}
+
+impl Location {
+    pub fn paths(&self) -> &Vec<PathBuf> {
+        &self.paths
+    }
+    /// The command whose standard output lists additional paths, one per
+    /// line. The backup runs it and adds the paths to the static ones.
+    pub fn paths_command(&self) -> Option<&CommandSeq> {
+        self.paths_command.as_ref()
+    }
+    /// What happens to paths that do not exist. Without a policy, they are
+    /// passed to restic.
+    pub fn missing_paths(&self) -> Option<MissingPaths> {
+        self.missing_paths
+    }
+    /// What a backup of the location saves: its paths, the output of its
+    /// command or a dump of each of its databases or volumes. Every source is
+    /// stored in a snapshot of its own.
+    pub fn sources(&self) -> Vec<Source> {
+        match (&self.command, self.kind) {
+            (Some(command), _) => vec![Source::Command {
+                command: command.clone(),
+                filename: self.stdin_filename.clone(),
+                env: Vec::new(),
+            }],
+            (None, Some(LocationKind::Volume)) => container::sources(&self.volume),
+            (None, Some(kind)) => match kind.database() {
+                Some(database_kind) => database::sources(database_kind, &self.database),
+                None => Vec::new(),
+            },
+            (None, None) => vec![Source::Paths(self.paths.clone())],
+        }
+    }
+    /// The first inconsistency of the settings, e.g. "no databases", if any.
+    /// Locations whose backup reads the paths from files need no paths.
+    fn problem(&self, files_from: bool) -> Option<&'static str> {
+        let sources = [
+            !self.paths.is_empty() || self.paths_command.is_some(),
+            self.command.is_some(),
+            self.kind.is_some(),
+        ];
+        match sources.iter().filter(|s| **s).count() {
+            0 if files_from => {}
+            0 => return Some("neither paths nor a command nor a type"),
+            1 => {}
+            _ => return Some("more than one of paths, command and type"),
+        }
+        if self.command.is_none() && self.stdin_filename.is_some() {
+            return Some("a stdin-filename without a command");
+        }
+        if self.paths.is_empty() && self.paths_command.is_none() && self.missing_paths.is_some() {
+            return Some("a missing-paths policy without paths");
+        }
+
+        let database = &self.database;
+        match self.kind.and_then(LocationKind::database) {
+            Some(_) if database.databases.is_empty() => return Some("no databases"),
+            Some(DatabaseKind::Sqlite) if database.has_connection() => {
+                return Some("connection settings for SQLite");
+            }
+            None if !database.databases.is_empty() || database.has_connection() => {
+                return Some("database settings without a database type");
+            }
+            _ => {}
+        }
+
+        let volume = &self.volume;
+        match self.kind {
+            Some(LocationKind::Volume) if volume.volumes.is_empty() => Some("no volumes"),
+            Some(LocationKind::Volume) => None,
+            _ if !volume.volumes.is_empty() || !volume.stop_containers.is_empty() => {
+                Some("volume settings without the volume type")
+            }
+            _ => None,
+        }
+    }
+    /// The hooks of a backup of the location, including the built-in ones of
+    /// its type, e.g. to stop the containers that use a volume.
+    pub fn backup_hooks(&self, hooks: &HookOptions) -> HookOptions {
+        match self.kind {
+            Some(LocationKind::Volume) => container::hooks(&self.volume, hooks),
+            _ => hooks.clone(),
+        }
+    }
+    pub fn repos(&self) -> &Vec<Name> {
+        &self.repos
+    }
+    pub fn options(&self) -> &Options {
+        &self.options
+    }
+    pub fn notify(&self) -> &Notify {
+        &self.notify
+    }
+    pub fn schedule(&self) -> &Schedule {
+        &self.schedule
+    }
+    /// Additional arguments of the restic subcommands of this location's
+    /// operations (backup, forget, check and copy). They follow those of the
+    /// repository.
+    pub fn extra_args(&self) -> &ExtraArgs {
+        &self.extra_args
+    }
+    /// The number of CPU cores restic may use at once during this location's
+    /// operations. It takes precedence over the one of the repository.
+    pub fn max_cores(&self) -> Option<u32> {
+        self.max_cores
+    }
+    /// Whether backups are skipped while the system runs on battery power.
+    pub fn skip_on_battery(&self) -> bool {
+        self.skip_on_battery
+    }
+    /// Whether backups are skipped while the network connection is metered.
+    pub fn skip_on_metered(&self) -> bool {
+        self.skip_on_metered
+    }
+    /// The forget options of the location's snapshots in the repository, if
+    /// they differ from the forget options of the location.
+    pub fn repo_forget(&self, repo_name: &Name) -> Option<&ForgetOptions> {
+        self.repo_forget.get(repo_name)
+    }
+    /// The minimum age of the latest snapshot in a repository before the
+    /// location is backed up to it again.
+    pub fn min_interval(&self) -> Option<Duration> {
+        self.min_interval
+    }
+    /// The locations whose operations run before those of this location.
+    pub fn depends_on(&self) -> &[Name] {
+        &self.depends_on
+    }
+    /// Locations with a higher priority run first.
+    pub fn priority(&self) -> i32 {
+        self.priority
+    }
+}
+
+/// What a backup of a location saves.
+#[derive(Clone, Debug)]
+pub enum Source {
+    Paths(Vec<PathBuf>),
+    /// The standard output of a command, e.g. a database dump, stored as a
+    /// single file with the given name.
+    Command {
+        command: CommandSeq,
+        filename: Option<String>,
+        /// Additional environment variables of the command, e.g. a password.
+        env: Vec<(String, String)>,
+    },
+}
+
+/// What happens to the paths of a location that do not exist, e.g. on a
+/// removable drive that is not mounted.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum MissingPaths {
+    /// The backup of the location fails.
+    Fail,
+    /// The path is omitted with a warning. If no path remains, the location
+    /// is skipped.
+    SkipPath,
+    /// The location is skipped without failure.
+    SkipLocation,
+}
+
+/// The type of a location whose data is produced by aresticrat instead of
+/// being read from paths.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum LocationKind {
+    Postgres,
+    Mysql,
+    Sqlite,
+    Volume,
+}
+
+impl LocationKind {
+    /// The kind of database of database locations.
+    pub fn database(self) -> Option<DatabaseKind> {
+        match self {
+            LocationKind::Postgres => Some(DatabaseKind::Postgres),
+            LocationKind::Mysql => Some(DatabaseKind::Mysql),
+            LocationKind::Sqlite => Some(DatabaseKind::Sqlite),
+            LocationKind::Volume => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum DatabaseKind {
+    Postgres,
+    Mysql,
+    Sqlite,
+}
+
+/// The settings of a container volume location.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Volume {
+    #[serde(default)]
+    volumes: Vec<String>,
+    #[serde(default)]
+    engine: ContainerEngine,
+    #[serde(default = "default_volume_image")]
+    image: String,
+    #[serde(default)]
+    stop_containers: Vec<String>,
+}
+
+impl Default for Volume {
+    fn default() -> Self {
+        Self {
+            volumes: Vec::new(),
+            engine: ContainerEngine::default(),
+            image: default_volume_image(),
+            stop_containers: Vec::new(),
+        }
+    }
+}
+
+fn default_volume_image() -> String {
+    "alpine".to_string()
+}
+
+impl Volume {
+    /// The names of the volumes.
+    pub fn volumes(&self) -> &[String] {
+        &self.volumes
+    }
+    pub fn engine(&self) -> ContainerEngine {
+        self.engine
+    }
+    /// The image of the container that reads the volumes. It must provide tar.
+    pub fn image(&self) -> &str {
+        &self.image
+    }
+    /// Containers that are stopped during the backup of the volumes.
+    pub fn stop_containers(&self) -> &[String] {
+        &self.stop_containers
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ContainerEngine {
+    #[default]
+    Docker,
+    Podman,
+}
+
+impl ContainerEngine {
+    pub fn program(self) -> &'static str {
+        match self {
+            ContainerEngine::Docker => "docker",
+            ContainerEngine::Podman => "podman",
+        }
+    }
+}
+
+/// The connection settings of a database location.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Database {
+    #[serde(default)]
+    databases: Vec<String>,
+    #[serde(default)]
+    host: Option<String>,
+    #[serde(default)]
+    port: Option<u16>,
+    #[serde(default)]
+    user: Option<String>,
+    #[serde(default)]
+    password: Option<String>,
+    #[serde(default)]
+    dump_args: Vec<String>,
+    #[serde(default)]
+    compress: bool,
+}
+
+impl Database {
+    /// The names of the databases or, for SQLite, their files.
+    pub fn databases(&self) -> &[String] {
+        &self.databases
+    }
+    pub fn host(&self) -> Option<&str> {
+        self.host.as_deref()
+    }
+    pub fn port(&self) -> Option<u16> {
+        self.port
+    }
+    pub fn user(&self) -> Option<&str> {
+        self.user.as_deref()
+    }
+    pub fn password(&self) -> Option<&str> {
+        self.password.as_deref()
+    }
+    /// Additional arguments of the dump program.
+    pub fn dump_args(&self) -> &[String] {
+        &self.dump_args
+    }
+    pub fn compress(&self) -> bool {
+        self.compress
+    }
+    fn has_connection(&self) -> bool {
+        self.host.is_some() || self.port.is_some() || self.user.is_some() || self.password.is_some()
+    }
+}
+
+/// The times at which the daemon runs the operations of a location.
+///
+/// A single cron expression (e.g. `schedule = "0 2 * * *"`) schedules the
+/// backup only. A table schedules each operation separately.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct ScheduleTable {
+    #[serde(default)]
+    backup: Option<CronExpr>,
+    #[serde(default)]
+    forget: Option<CronExpr>,
+    #[serde(default)]
+    check: Option<CronExpr>,
+    #[serde(default)]
+    status: Option<CronExpr>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Schedule(ScheduleTable);
+
+impl Schedule {
+    pub fn backup(&self) -> Option<&CronExpr> {
+        self.0.backup.as_ref()
+    }
+    pub fn forget(&self) -> Option<&CronExpr> {
+        self.0.forget.as_ref()
+    }
+    pub fn check(&self) -> Option<&CronExpr> {
+        self.0.check.as_ref()
+    }
+    pub fn status(&self) -> Option<&CronExpr> {
+        self.0.status.as_ref()
+    }
+    /// All scheduled operations.
+    pub fn entries(&self) -> impl Iterator<Item = (Operation, &CronExpr)> {
+        [
+            (Operation::Backup, self.backup()),
+            (Operation::Forget, self.forget()),
+            (Operation::Check, self.check()),
+            (Operation::Status, self.status()),
+        ]
+        .into_iter()
+        .filter_map(|(operation, cron)| Some((operation, cron?)))
+    }
+}
+
+impl<'de> Deserialize<'de> for Schedule {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        use serde::*;
+
+        struct ScheduleVisitor;
+
+        impl<'de> de::Visitor<'de> for ScheduleVisitor {
+            type Value = Schedule;
+
+            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                formatter.write_str("a cron expression or a table of cron expressions by operation")
+            }
+
+            fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                let backup = CronExpr::parse(v).map_err(de::Error::custom)?;
+                Ok(Schedule(ScheduleTable {
+                    backup: Some(backup),
+                    ..Default::default()
+                }))
+            }
+
+            fn visit_map<A>(self, map: A) -> Result<Self::Value, A::Error>
+            where
+                A: de::MapAccess<'de>,
+            {
+                ScheduleTable::deserialize(de::value::MapAccessDeserializer::new(map)).map(Schedule)
+            }
+        }
+
+        deserializer.deserialize_any(ScheduleVisitor)
+    }
+}
+
+/// A cron expression with five (minute to weekday) or six (including seconds)
+/// fields, e.g. "0 2 * * *".
+#[derive(Clone, Debug)]
+pub struct CronExpr {
+    cron: croner::Cron,
+    expr: String,
+}
+
+impl CronExpr {
+    pub fn parse(s: &str) -> Result<Self, CronExprParseError> {
+        let cron = s
+            .parse::<croner::Cron>()
+            .map_err(|e| CronExprParseError(format!("Invalid cron expression \"{s}\": {e}")))?;
+        Ok(Self {
+            cron,
+            expr: s.to_string(),
+        })
+    }
+
+    /// The first matching time after the given time.
+    pub fn next_after<Tz: chrono::TimeZone>(
+        &self,
+        time: &chrono::DateTime<Tz>,
+    ) -> Option<chrono::DateTime<Tz>> {
+        self.cron.find_next_occurrence(time, false).ok()
+    }
+}
+
+impl Display for CronExpr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.expr)
+    }
+}
+
+impl FromStr for CronExpr {
+    type Err = CronExprParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        CronExpr::parse(s)
+    }
+}
+
+impl<'de> Deserialize<'de> for CronExpr {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        use serde::*;
+
+        struct CronExprVisitor;
+
+        impl de::Visitor<'_> for CronExprVisitor {
+            type Value = CronExpr;
+
+            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                formatter.write_str("a cron expression like \"0 2 * * *\"")
+            }
+
+            fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                CronExpr::parse(v).map_err(de::Error::custom)
+            }
+        }
+
+        deserializer.deserialize_str(CronExprVisitor)
+    }
+}
+
+#[derive(Debug, Error)]
+#[error("{0}")]
+pub struct CronExprParseError(String);
+
+/// A step of a pipeline: an operation followed by additional restic arguments,
+/// e.g. "check --read-data-subset 2%". A copy names its target repository
+/// first, e.g. "copy offsite".
+#[derive(Clone, Debug)]
+pub struct Step {
+    operation: Operation,
+    target: Option<Name>,
+    args: Vec<String>,
+    expr: String,
+}
+
+impl Step {
+    pub fn parse(s: &str) -> Result<Self, StepParseError> {
+        let error = |msg: &str| StepParseError(format!("Invalid pipeline step \"{s}\": {msg}"));
+        let words = shell_words::split(s).map_err(|e| error(&e.to_string()))?;
+        let mut words = words.into_iter();
+        let operation = match words.next().as_deref() {
+            Some("backup") => Operation::Backup,
+            Some("forget") => Operation::Forget,
+            Some("check") => Operation::Check,
+            Some("copy") => Operation::Copy,
+            Some(_) => return Err(error("unknown operation")),
+            None => return Err(error("missing operation")),
+        };
+        let target = match operation {
+            Operation::Copy => {
+                let target = words
+                    .next()
+                    .ok_or_else(|| error("missing target repository"))?;
+                Some(Name::parse(&target).map_err(|e| error(&e.to_string()))?)
+            }
+            _ => None,
+        };
+        Ok(Self {
+            operation,
+            target,
+            args: words.collect(),
+            expr: s.to_string(),
+        })
+    }
+
+    pub fn operation(&self) -> Operation {
+        self.operation
+    }
+    /// The target repository of a copy.
+    pub fn target(&self) -> Option<&Name> {
+        self.target.as_ref()
+    }
+    /// Additional arguments passed to restic.
+    pub fn args(&self) -> &[String] {
+        &self.args
+    }
+}
+
+impl Display for Step {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.expr)
+    }
+}
+
+impl FromStr for Step {
+    type Err = StepParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Step::parse(s)
+    }
+}
+
+impl<'de> Deserialize<'de> for Step {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        use serde::*;
+
+        struct StepVisitor;
+
+        impl de::Visitor<'_> for StepVisitor {
+            type Value = Step;
+
+            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                formatter.write_str("a pipeline step like \"check --read-data-subset 2%\"")
+            }
+
+            fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                Step::parse(v).map_err(de::Error::custom)
+            }
+        }
+
+        deserializer.deserialize_str(StepVisitor)
+    }
+}
+
+#[derive(Debug, Error)]
+#[error("{0}")]
+pub struct StepParseError(String);
+
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Name(String);
+
+impl Name {
+    pub fn parse(s: &str) -> Result<Self, NameParseError> {
+        if s.chars().all(Self::is_valid_char) {
+            Ok(Self(s.to_string()))
+        } else {
+            Err(NameParseError(
+                "Invalid name (only [A-Za-z0-9_-] are allowed).".to_string(),
+            ))
+        }
+    }
+
+    fn is_valid_char(c: char) -> bool {
+        c.is_ascii_alphanumeric() || c == '_' || c == '-'
+    }
+
     pub fn as_str(&self) -> &str {
         &self.0
     }
@@ -394,6 +3128,15 @@ impl<'de> Deserialize<'de> for Name {
     }
 }
 
+impl serde::Serialize for Name {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(&self.0)
+    }
+}
+
 #[derive(Debug, Error)]
 #[error("{0}")]
 pub struct NameParseError(String);
@@ -418,6 +3161,10 @@ impl LocationRepo {
         })
     }
 
+    pub fn new(location: Name, repo: Option<Name>) -> Self {
+        LocationRepo(location, repo)
+    }
+
     pub fn location(&self) -> &Name {
         &self.0
     }
@@ -427,6 +3174,12 @@ impl LocationRepo {
     }
 }
 
+impl From<Name> for LocationRepo {
+    fn from(value: Name) -> Self {
+        LocationRepo(value, None)
+    }
+}
+
 impl FromStr for LocationRepo {
     type Err = LocationRepoParseError;
 
@@ -474,6 +3227,80 @@ impl<'de> Deserialize<'de> for LocationRepo {
 #[error("{0}")]
 pub struct LocationRepoParseError(String);
 
+/// An entry of the keyring of the operating system, e.g. "aresticrat/remote".
+#[derive(Clone, Debug)]
+pub struct KeyringEntry {
+    service: String,
+    account: String,
+}
+
+impl KeyringEntry {
+    pub fn parse(s: &str) -> Result<Self, KeyringEntryParseError> {
+        match s.rsplit_once('/') {
+            Some((service, account)) if !service.is_empty() && !account.is_empty() => Ok(Self {
+                service: service.to_string(),
+                account: account.to_string(),
+            }),
+            _ => Err(KeyringEntryParseError(format!(
+                "Invalid keyring entry {s:?}. Expected \"<service>/<account>\"."
+            ))),
+        }
+    }
+
+    pub fn service(&self) -> &str {
+        &self.service
+    }
+    pub fn account(&self) -> &str {
+        &self.account
+    }
+}
+
+impl Display for KeyringEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.service, self.account)
+    }
+}
+
+impl FromStr for KeyringEntry {
+    type Err = KeyringEntryParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        KeyringEntry::parse(s)
+    }
+}
+
+impl<'de> Deserialize<'de> for KeyringEntry {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        use serde::*;
+
+        struct KeyringEntryVisitor;
+
+        impl de::Visitor<'_> for KeyringEntryVisitor {
+            type Value = KeyringEntry;
+
+            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                formatter.write_str("a keyring entry in the form <service>/<account>")
+            }
+
+            fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                KeyringEntry::parse(v).map_err(de::Error::custom)
+            }
+        }
+
+        deserializer.deserialize_str(KeyringEntryVisitor)
+    }
+}
+
+#[derive(Debug, Error)]
+#[error("{0}")]
+pub struct KeyringEntryParseError(String);
+
 #[derive(Clone, Debug)]
 pub struct CommandSeq(Vec<String>);
 
@@ -500,8 +3327,9 @@ impl CommandSeq {
         &self.0[1..]
     }
 
+    /// The command that runs the program at the configured priority.
     pub fn to_command(&self) -> std::process::Command {
-        let mut cmd = std::process::Command::new(self.program());
+        let mut cmd = crate::priority::command(self.program());
         cmd.args(self.args());
         cmd
     }
@@ -563,3 +3391,565 @@ impl<'de> Deserialize<'de> for CommandSeq {
 #[derive(Debug, Error)]
 #[error("{0}")]
 pub struct CommandSeqParseError(String);
+
+/// A hook command. It is written either as COMMAND value, as shell script
+/// with the prefix `sh:` or as table with the command and its options, e.g.
+/// `{ command = "warm-cache", timeout = "5m", on-failure = "warn" }`.
+#[derive(Clone, Debug)]
+pub struct Hook {
+    command: HookCommand,
+    name: Option<String>,
+    cwd: Option<PathBuf>,
+    env: HashMap<String, String>,
+    timeout: Option<Duration>,
+    on_failure: OnHookFailure,
+    output: HookOutput,
+}
+
+impl Hook {
+    pub fn command(&self) -> &HookCommand {
+        &self.command
+    }
+    /// The name of the hook, which labels its output.
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+    /// The working directory of the command. Defaults to the one of
+    /// aresticrat.
+    pub fn cwd(&self) -> Option<&Path> {
+        self.cwd.as_deref()
+    }
+    /// Additional environment variables of the command.
+    pub fn env(&self) -> &HashMap<String, String> {
+        &self.env
+    }
+    /// The time after which the command is terminated and considered failed.
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+    pub fn on_failure(&self) -> OnHookFailure {
+        self.on_failure
+    }
+    pub fn output(&self) -> HookOutput {
+        self.output
+    }
+}
+
+impl From<HookCommand> for Hook {
+    fn from(command: HookCommand) -> Self {
+        Self {
+            command,
+            name: None,
+            cwd: None,
+            env: HashMap::new(),
+            timeout: None,
+            on_failure: OnHookFailure::default(),
+            output: HookOutput::default(),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Hook {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        use serde::*;
+
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum RawCommand {
+            String(String),
+            Seq(Vec<String>),
+        }
+
+        #[derive(Deserialize)]
+        #[serde(rename_all = "kebab-case")]
+        struct HookTable {
+            command: RawCommand,
+            #[serde(default)]
+            name: Option<String>,
+            #[serde(default)]
+            shell: bool,
+            #[serde(default)]
+            cwd: Option<PathBuf>,
+            #[serde(default)]
+            env: HashMap<String, String>,
+            #[serde(default)]
+            timeout: Option<Duration>,
+            #[serde(default)]
+            on_failure: OnHookFailure,
+            #[serde(default)]
+            output: HookOutput,
+        }
+
+        struct HookVisitor;
+
+        impl<'de> de::Visitor<'de> for HookVisitor {
+            type Value = Hook;
+
+            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                formatter.write_str("a COMMAND value or a table with a command and its options")
+            }
+
+            fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                HookCommand::parse(v)
+                    .map(Hook::from)
+                    .map_err(de::Error::custom)
+            }
+
+            fn visit_seq<A>(self, seq: A) -> Result<Self::Value, A::Error>
+            where
+                A: de::SeqAccess<'de>,
+            {
+                CommandSeq::deserialize(de::value::SeqAccessDeserializer::new(seq))
+                    .map(|c| Hook::from(HookCommand::Exec(c)))
+            }
+
+            fn visit_map<A>(self, map: A) -> Result<Self::Value, A::Error>
+            where
+                A: de::MapAccess<'de>,
+            {
+                let table = HookTable::deserialize(de::value::MapAccessDeserializer::new(map))?;
+                let command = match (table.command, table.shell) {
+                    (RawCommand::String(script), true) => HookCommand::Shell(script),
+                    (RawCommand::String(command), false) => {
+                        HookCommand::parse(&command).map_err(de::Error::custom)?
+                    }
+                    (RawCommand::Seq(_), true) => {
+                        return Err(de::Error::custom("A shell command must be a string."));
+                    }
+                    (RawCommand::Seq(command), false) => {
+                        HookCommand::Exec(CommandSeq::from_vec(command).map_err(de::Error::custom)?)
+                    }
+                };
+                Ok(Hook {
+                    command,
+                    name: table.name,
+                    cwd: table.cwd,
+                    env: table.env,
+                    timeout: table.timeout,
+                    on_failure: table.on_failure,
+                    output: table.output,
+                })
+            }
+        }
+
+        deserializer.deserialize_any(HookVisitor)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum HookCommand {
+    /// A program with its arguments, which is run directly.
+    Exec(CommandSeq),
+    /// A script, which is run by the configured shell.
+    Shell(String),
+}
+
+impl HookCommand {
+    /// Parses the string form of a hook command. Strings with the prefix `sh:`
+    /// are shell scripts, all others are COMMAND values in shell form.
+    pub fn parse(str: &str) -> Result<Self, CommandSeqParseError> {
+        match str.strip_prefix("sh:") {
+            Some(script) => Ok(Self::Shell(script.trim_start().to_string())),
+            None => CommandSeq::parse_shell_words(str).map(Self::Exec),
+        }
+    }
+
+    /// The program that runs the hook with the given shell.
+    pub fn program<'a>(&'a self, shell: &'a CommandSeq) -> &'a str {
+        match self {
+            Self::Exec(command) => command.program(),
+            Self::Shell(_) => shell.program(),
+        }
+    }
+
+    /// The command that runs the hook with the given shell.
+    pub fn to_command(&self, shell: &CommandSeq) -> std::process::Command {
+        match self {
+            Self::Exec(command) => command.to_command(),
+            Self::Shell(script) => {
+                let mut cmd = shell.to_command();
+                cmd.arg(script);
+                cmd
+            }
+        }
+    }
+}
+
+/// Where the output of a hook command goes.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum HookOutput {
+    /// The output is printed like the one of restic, depending on the
+    /// verbosity.
+    #[default]
+    Auto,
+    /// The output is always printed while the command runs.
+    Stream,
+    /// The output is only printed if the command fails.
+    Capture,
+    /// The output is neither printed nor written to the log file.
+    Discard,
+}
+
+/// What a failed hook command means for its hooks.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum OnHookFailure {
+    /// The hooks fail and their remaining commands do not run.
+    #[default]
+    Abort,
+    /// The failure is logged as warning and the hooks continue.
+    Warn,
+    /// The failure is only logged in verbose mode and the hooks continue.
+    Ignore,
+}
+
+/// A span of time written as a sequence of numbers with units, e.g. `1d12h`.
+///
+/// Supported units are `w` (weeks), `d` (days), `h` (hours), `m` (minutes) and
+/// `s` (seconds).
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub struct Duration(std::time::Duration);
+
+impl Duration {
+    pub fn parse(s: &str) -> Result<Self, DurationParseError> {
+        let err = || {
+            DurationParseError(format!(
+                "Invalid duration \"{s}\" (expected e.g. 30m, 26h or 1d12h)."
+            ))
+        };
+
+        let mut secs: u64 = 0;
+        let mut num = String::new();
+        for c in s.trim().chars() {
+            if c.is_ascii_digit() {
+                num.push(c);
+                continue;
+            }
+            let unit = match c {
+                'w' => 7 * 24 * 60 * 60,
+                'd' => 24 * 60 * 60,
+                'h' => 60 * 60,
+                'm' => 60,
+                's' => 1,
+                _ => return Err(err()),
+            };
+            let n: u64 = num.parse().map_err(|_| err())?;
+            secs = n
+                .checked_mul(unit)
+                .and_then(|n| secs.checked_add(n))
+                .ok_or_else(err)?;
+            num.clear();
+        }
+        if !num.is_empty() || s.trim().is_empty() {
+            return Err(err());
+        }
+        Ok(Self(std::time::Duration::from_secs(secs)))
+    }
+}
+
+impl From<Duration> for std::time::Duration {
+    fn from(value: Duration) -> Self {
+        value.0
+    }
+}
+
+impl From<std::time::Duration> for Duration {
+    fn from(value: std::time::Duration) -> Self {
+        Self(value)
+    }
+}
+
+impl Display for Duration {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let secs = self.0.as_secs();
+        let (d, h, m, s) = (
+            secs / 86400,
+            secs % 86400 / 3600,
+            secs % 3600 / 60,
+            secs % 60,
+        );
+        if d > 0 {
+            write!(f, "{d}d{h}h")
+        } else if h > 0 {
+            write!(f, "{h}h{m}m")
+        } else if m > 0 {
+            write!(f, "{m}m")
+        } else {
+            write!(f, "{s}s")
+        }
+    }
+}
+
+impl FromStr for Duration {
+    type Err = DurationParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Duration::parse(s)
+    }
+}
+
+impl<'de> Deserialize<'de> for Duration {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        use serde::*;
+
+        struct DurationVisitor;
+
+        impl de::Visitor<'_> for DurationVisitor {
+            type Value = Duration;
+
+            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                formatter.write_str("a duration such as 30m, 26h or 1d12h")
+            }
+
+            fn visit_string<E>(self, v: String) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                self.visit_str(&v)
+            }
+
+            fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                Duration::parse(v).map_err(de::Error::custom)
+            }
+        }
+
+        deserializer.deserialize_str(DurationVisitor)
+    }
+}
+
+#[derive(Debug, Error)]
+#[error("{0}")]
+pub struct DurationParseError(String);
+
+/// A size in bytes, parsed from a string with an optional unit like restic
+/// does, e.g. `500k` or `2G`. Units are powers of 1024.
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub struct Size(u64);
+
+impl Size {
+    pub fn parse(s: &str) -> Result<Self, SizeParseError> {
+        let err = || SizeParseError(format!("Invalid size \"{s}\" (expected e.g. 500M or 2G)."));
+        let size = s.trim();
+        let (value, factor) = match size.chars().last().ok_or_else(err)?.to_ascii_lowercase() {
+            'k' => (&size[..size.len() - 1], 1 << 10),
+            'm' => (&size[..size.len() - 1], 1 << 20),
+            'g' => (&size[..size.len() - 1], 1 << 30),
+            't' => (&size[..size.len() - 1], 1 << 40),
+            'b' => (&size[..size.len() - 1], 1),
+            _ => (size, 1),
+        };
+        value
+            .trim()
+            .parse::<u64>()
+            .ok()
+            .and_then(|v| v.checked_mul(factor))
+            .map(Self)
+            .ok_or_else(err)
+    }
+}
+
+impl From<Size> for u64 {
+    fn from(value: Size) -> Self {
+        value.0
+    }
+}
+
+impl<'de> Deserialize<'de> for Size {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        use serde::*;
+
+        struct SizeVisitor;
+
+        impl de::Visitor<'_> for SizeVisitor {
+            type Value = Size;
+
+            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                formatter.write_str("a size such as 500M or 2G")
+            }
+
+            fn visit_string<E>(self, v: String) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                self.visit_str(&v)
+            }
+
+            fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                Size::parse(v).map_err(de::Error::custom)
+            }
+        }
+
+        deserializer.deserialize_str(SizeVisitor)
+    }
+}
+
+#[derive(Debug, Error)]
+#[error("{0}")]
+pub struct SizeParseError(String);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+        vars.iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    /// Builds the settings of the sources without deserializing them.
+    fn build(
+        config_paths: &[PathBuf],
+        settings: &[(&str, &str)],
+        env: &[(&str, &str)],
+    ) -> serde_json::Value {
+        let (builder, env) = builder(config_paths, &vars(settings), vars(env)).unwrap();
+        builder
+            .add_source(env)
+            .build()
+            .unwrap()
+            .try_deserialize()
+            .unwrap()
+    }
+
+    /// Deserializes and validates a configuration file.
+    fn parse(toml: &str) -> Result<Config, config::ConfigError> {
+        let config: Config = config::Config::builder()
+            .add_source(config::File::from_str(toml, config::FileFormat::Toml))
+            .build()?
+            .try_deserialize()?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    const MINIMAL: &str = r#"
+        [repos.nas]
+        path = "/srv/restic"
+        password = "secret"
+
+        [locations.home]
+        paths = ["/home"]
+        repos = ["nas"]
+    "#;
+
+    #[test]
+    fn log_file_is_read_from_documented_key() {
+        let config = parse(&format!(
+            "log-file = \"logs/{{{{command}}}}.log\"\n{MINIMAL}"
+        ))
+        .unwrap();
+        assert_eq!(config.log_file(), Some("logs/{{command}}.log"));
+    }
+
+    #[test]
+    fn state_file_is_read_from_documented_key() {
+        let config = parse(&format!(
+            "state-file = \"/var/lib/aresticrat/state.json\"\n{MINIMAL}"
+        ))
+        .unwrap();
+        assert_eq!(
+            config.state_file(),
+            Some(Path::new("/var/lib/aresticrat/state.json"))
+        );
+    }
+
+    #[test]
+    fn password_source_aliases_conflict_with_password() {
+        let err = parse(
+            r#"
+            [repos.nas]
+            path = "/srv/restic"
+            password = "secret"
+            key-file = "/etc/restic/key"
+
+            [locations.home]
+            paths = ["/home"]
+            repos = ["nas"]
+            "#,
+        )
+        .err()
+        .unwrap();
+        assert_eq!(
+            err.to_string(),
+            "Repository nas defines more than one password source (password/key, password-file/key-file; each listed with its alias)."
+        );
+    }
+
+    #[test]
+    fn env_overrides_map_nested_keys() {
+        let overrides = env_overrides(
+            vars(&[
+                ("ARESTICRAT__REPOS__NAS__RETRY_LOCK", "5m"),
+                ("ARESTICRAT_EXECUTABLE", "restic"),
+                ("PATH", "/bin"),
+            ]),
+            "ARESTICRAT__",
+        );
+        assert_eq!(overrides, vars(&[("repos.nas.retry-lock", "5m")]));
+    }
+
+    #[test]
+    fn nested_env_vars_are_excluded_from_flat_source() {
+        let settings = build(
+            &[],
+            &[],
+            &[
+                ("ARESTICRAT__REPOS__NAS__RETRY_LOCK", "5m"),
+                ("ARESTICRAT_EXECUTABLE", "restic"),
+            ],
+        );
+        assert_eq!(
+            settings,
+            serde_json::json!({
+                "executable": "restic",
+                "repos": { "nas": { "retry-lock": "5m" } },
+            })
+        );
+    }
+
+    #[test]
+    fn env_overrides_take_precedence_over_files_but_not_settings() {
+        let file = std::env::temp_dir().join(format!(
+            "aresticrat-config-test-{}.toml",
+            std::process::id()
+        ));
+        std::fs::write(
+            &file,
+            "[repos.nas]\nretry-lock = \"1m\"\nstale-lock-age = \"1h\"\n",
+        )
+        .unwrap();
+        let env = [("ARESTICRAT__REPOS__NAS__RETRY_LOCK", "5m")];
+
+        let settings = build(std::slice::from_ref(&file), &[], &env);
+        assert_eq!(settings["repos"]["nas"]["retry-lock"], "5m");
+        assert_eq!(settings["repos"]["nas"]["stale-lock-age"], "1h");
+
+        let settings = build(
+            std::slice::from_ref(&file),
+            &[("repos.nas.retry-lock", "10m")],
+            &env,
+        );
+        assert_eq!(settings["repos"]["nas"]["retry-lock"], "10m");
+
+        std::fs::remove_file(&file).unwrap();
+    }
+}
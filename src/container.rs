@@ -0,0 +1,57 @@
+use crate::config::{CommandSeq, Hook, HookCommand, HookOptions, Source, Volume};
+
+/// The contents of all volumes of a location as tar archives. Every volume is
+/// stored in a snapshot of its own, so that it can be restored independently.
+pub fn sources(volume: &Volume) -> Vec<Source> {
+    volume
+        .volumes()
+        .iter()
+        .map(|name| Source::Command {
+            command: command(
+                volume,
+                &[
+                    "run",
+                    "--rm",
+                    "--network",
+                    "none",
+                    "--volume",
+                    &format!("{name}:/volume:ro"),
+                    volume.image(),
+                    "tar",
+                    "-C",
+                    "/volume",
+                    "-cf",
+                    "-",
+                    ".",
+                ],
+            ),
+            filename: Some(format!("{name}.tar")),
+            env: Vec::new(),
+        })
+        .collect()
+}
+
+/// The backup hooks of a volume location. The containers that use the
+/// volumes are stopped after the BEFORE hooks and started again before the
+/// FINALLY hooks, even if the backup failed.
+pub fn hooks(volume: &Volume, hooks: &HookOptions) -> HookOptions {
+    let containers = volume.stop_containers();
+    if containers.is_empty() {
+        return hooks.clone();
+    }
+    let hook = |action: &str| {
+        let args: Vec<_> = std::iter::once(action)
+            .chain(containers.iter().map(String::as_str))
+            .collect();
+        Hook::from(HookCommand::Exec(command(volume, &args)))
+    };
+    hooks.surround(hook("stop"), hook("start"))
+}
+
+fn command(volume: &Volume, args: &[&str]) -> CommandSeq {
+    let args = std::iter::once(volume.engine().program())
+        .chain(args.iter().copied())
+        .map(String::from)
+        .collect();
+    CommandSeq::from_vec(args).expect("Container commands have a program.")
+}
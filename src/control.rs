@@ -0,0 +1,492 @@
+use crate::cli::CtlCommand;
+use crate::config::{self, Config, Name};
+use crate::event::Operation;
+use crate::{output, print_log, signal};
+use anyhow::{Result, anyhow, bail};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::Level;
+
+/// The number of finished runs that are kept for the status.
+const HISTORY_SIZE: usize = 50;
+/// How often the server checks for interruptions while no request arrives.
+const ACCEPT_INTERVAL: Duration = Duration::from_millis(200);
+/// The longest time the server waits for a request of a connection.
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The state of the daemon that is shared with the control API.
+#[derive(Default)]
+pub struct State {
+    inner: Mutex<Status>,
+}
+
+/// The status of the daemon as returned by the control API.
+#[derive(Default, Deserialize, Serialize)]
+pub struct Status {
+    paused: bool,
+    scheduled: Vec<Scheduled>,
+    running: Vec<Run>,
+    history: VecDeque<Run>,
+    /// The current configuration of the daemon.
+    #[serde(skip)]
+    config: Option<Arc<Config>>,
+    /// Operations that have been triggered, but not started yet.
+    #[serde(skip)]
+    triggered: Vec<(Name, Operation)>,
+}
+
+/// The next run of a scheduled operation.
+#[derive(Deserialize, Serialize)]
+pub struct Scheduled {
+    location: String,
+    operation: String,
+    schedule: String,
+    next: Option<DateTime<Local>>,
+}
+
+/// A running or finished operation.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Run {
+    location: String,
+    operation: String,
+    /// Whether the run has been triggered via the control API instead of its
+    /// schedule.
+    triggered: bool,
+    started: DateTime<Local>,
+    #[serde(default)]
+    finished: Option<DateTime<Local>>,
+    #[serde(default)]
+    error: Option<String>,
+}
+
+impl Status {
+    pub fn paused(&self) -> bool {
+        self.paused
+    }
+    pub fn scheduled(&self) -> &[Scheduled] {
+        &self.scheduled
+    }
+    pub fn running(&self) -> &[Run] {
+        &self.running
+    }
+    pub fn history(&self) -> &VecDeque<Run> {
+        &self.history
+    }
+    pub fn config(&self) -> Option<&Config> {
+        self.config.as_deref()
+    }
+}
+
+impl Scheduled {
+    pub fn location(&self) -> &str {
+        &self.location
+    }
+    pub fn operation(&self) -> &str {
+        &self.operation
+    }
+    pub fn schedule(&self) -> &str {
+        &self.schedule
+    }
+    pub fn next(&self) -> Option<DateTime<Local>> {
+        self.next
+    }
+}
+
+impl Run {
+    pub fn location(&self) -> &str {
+        &self.location
+    }
+    pub fn operation(&self) -> &str {
+        &self.operation
+    }
+    pub fn started(&self) -> DateTime<Local> {
+        self.started
+    }
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+    /// The duration of a finished run.
+    pub fn duration(&self) -> Option<Duration> {
+        self.finished.and_then(|f| (f - self.started).to_std().ok())
+    }
+}
+
+impl State {
+    fn lock(&self) -> std::sync::MutexGuard<'_, Status> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Whether the schedules are paused. Triggered operations still run.
+    pub fn paused(&self) -> bool {
+        self.lock().paused
+    }
+
+    /// Calls the function with the current status.
+    pub fn with_status<T>(&self, f: impl FnOnce(&Status) -> T) -> T {
+        f(&self.lock())
+    }
+
+    /// Updates the configuration and the scheduled operations, e.g. after the
+    /// configuration has been reloaded.
+    pub fn set_scheduled<'a>(
+        &self,
+        config: &Arc<Config>,
+        scheduled: impl Iterator<Item = (&'a Name, Operation, String, Option<DateTime<Local>>)>,
+    ) {
+        let mut status = self.lock();
+        status.config = Some(Arc::clone(config));
+        status.scheduled = scheduled
+            .map(|(location, operation, schedule, next)| Scheduled {
+                location: location.to_string(),
+                operation: operation.to_string(),
+                schedule,
+                next,
+            })
+            .collect();
+        status.scheduled.sort_by_key(|s| s.next);
+    }
+
+    /// The operations that have been triggered since the last call.
+    pub fn take_triggered(&self) -> Vec<(Name, Operation)> {
+        std::mem::take(&mut self.lock().triggered)
+    }
+
+    /// Marks the operation as running. Returns false if it is running
+    /// already.
+    pub fn start(&self, location: &Name, operation: Operation, triggered: bool) -> bool {
+        let mut status = self.lock();
+        if status
+            .running
+            .iter()
+            .any(|r| r.location == location.as_str() && r.operation == operation.as_str())
+        {
+            return false;
+        }
+        status.running.push(Run {
+            location: location.to_string(),
+            operation: operation.to_string(),
+            triggered,
+            started: Local::now(),
+            finished: None,
+            error: None,
+        });
+        true
+    }
+
+    /// Moves the running operation to the history.
+    pub fn finish(&self, location: &Name, operation: Operation, error: Option<String>) {
+        let mut status = self.lock();
+        let Some(index) = status
+            .running
+            .iter()
+            .position(|r| r.location == location.as_str() && r.operation == operation.as_str())
+        else {
+            return;
+        };
+        let mut run = status.running.remove(index);
+        run.finished = Some(Local::now());
+        run.error = error;
+        status.history.push_front(run);
+        status.history.truncate(HISTORY_SIZE);
+    }
+}
+
+/// A request to a server of the daemon.
+pub struct Request {
+    method: String,
+    path: String,
+    authorization: Option<String>,
+}
+
+impl Request {
+    pub fn method(&self) -> &str {
+        &self.method
+    }
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+}
+
+/// The response of a server of the daemon.
+pub struct Response {
+    code: u16,
+    content_type: &'static str,
+    body: String,
+}
+
+impl Response {
+    pub fn json(code: u16, body: &serde_json::Value) -> Self {
+        Self {
+            code,
+            content_type: "application/json",
+            body: body.to_string(),
+        }
+    }
+
+    pub fn html(code: u16, body: String) -> Self {
+        Self {
+            code,
+            content_type: "text/html; charset=utf-8",
+            body,
+        }
+    }
+}
+
+/// Listens for requests on the address, e.g. of the control API.
+pub fn bind(address: &str, purpose: &str) -> Result<TcpListener> {
+    let listener = TcpListener::bind(address)
+        .map_err(|e| anyhow!("Failed to listen for {purpose} on {address}: {e}"))?;
+    print_log!(Level::INFO, "Listening for {purpose} on {address}.");
+    Ok(listener)
+}
+
+/// Answers the HTTP requests of the listener until the process is
+/// interrupted. Requests are answered one after another.
+pub fn serve(listener: TcpListener, handle: impl Fn(&Request) -> Response) {
+    if let Err(err) = listener.set_nonblocking(true) {
+        print_log!(Level::WARN, "Failed to serve requests: {err}");
+        return;
+    }
+    while !signal::interrupted() {
+        match listener.accept() {
+            Ok((stream, _)) => {
+                let result = read_request(&stream)
+                    .and_then(|request| write_response(&stream, &handle(&request)));
+                if let Err(err) = result {
+                    print_log!(Level::DEBUG, "Failed to answer request: {err}");
+                }
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::WouldBlock => {
+                signal::sleep(ACCEPT_INTERVAL);
+            }
+            Err(err) => print_log!(Level::WARN, "Failed to accept request: {err}"),
+        }
+    }
+}
+
+fn read_request(stream: &TcpStream) -> std::io::Result<Request> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    let mut reader = BufReader::new(stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut authorization = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("authorization") {
+                authorization = Some(value.trim().to_string());
+            }
+        }
+    }
+    let mut parts = request_line.split_whitespace();
+    Ok(Request {
+        method: parts.next().unwrap_or_default().to_string(),
+        path: parts.next().unwrap_or_default().to_string(),
+        authorization,
+    })
+}
+
+fn write_response(stream: &TcpStream, response: &Response) -> std::io::Result<()> {
+    let reason = match response.code {
+        200 => "OK",
+        202 => "Accepted",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        _ => "Method Not Allowed",
+    };
+    let mut stream = stream;
+    write!(
+        stream,
+        "HTTP/1.1 {} {reason}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        response.code,
+        response.content_type,
+        response.body.len(),
+        response.body
+    )
+}
+
+/// Answers a request to the control API. If a token is configured, only
+/// requests with the token are accepted.
+pub fn handle(request: &Request, token: Option<&str>, state: &State) -> Response {
+    if let Some(token) = token {
+        if request.authorization.as_deref() != Some(&format!("Bearer {token}")) {
+            return Response::json(401, &error("Missing or wrong token."));
+        }
+    }
+    let (code, body) = respond(request.method(), request.path(), state);
+    Response::json(code, &body)
+}
+
+/// The status code and body of the response to a request.
+fn respond(method: &str, path: &str, state: &State) -> (u16, serde_json::Value) {
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+    match (method, segments.as_slice()) {
+        ("GET", ["status"]) => {
+            let status = state.lock();
+            (200, serde_json::to_value(&*status).unwrap_or_default())
+        }
+        ("POST", ["pause"]) => {
+            state.lock().paused = true;
+            print_log!(Level::INFO, "Schedules paused.");
+            (200, serde_json::json!({ "paused": true }))
+        }
+        ("POST", ["resume"]) => {
+            state.lock().paused = false;
+            print_log!(Level::INFO, "Schedules resumed.");
+            (200, serde_json::json!({ "paused": false }))
+        }
+        ("POST", [operation, location]) => {
+            let operation = match *operation {
+                "backup" => Operation::Backup,
+                "forget" => Operation::Forget,
+                "check" => Operation::Check,
+                _ => return (404, error(&format!("Unknown operation {operation}."))),
+            };
+            let mut status = state.lock();
+            let Some(location) = status
+                .config
+                .as_ref()
+                .and_then(|c| c.locations().keys().find(|l| l.as_str() == *location))
+                .cloned()
+            else {
+                return (400, error(&format!("Location {location} is not defined.")));
+            };
+            print_log!(
+                Level::INFO,
+                "{} of location {location} triggered.",
+                operation.label()
+            );
+            status.triggered.push((location, operation));
+            (202, serde_json::json!({ "triggered": true }))
+        }
+        (_, ["status" | "pause" | "resume"] | [_, _]) => (405, error("Method not allowed.")),
+        _ => (404, error(&format!("Unknown path {path}."))),
+    }
+}
+
+fn error(message: &str) -> serde_json::Value {
+    serde_json::json!({ "error": message })
+}
+
+/// Sends a command to the control API of a running daemon and prints its
+/// response.
+pub fn ctl(config: &Config, command: &CtlCommand) -> Result<()> {
+    let Some(address) = config.daemon().control() else {
+        bail!("The daemon has no control address (daemon.control).");
+    };
+    let (method, path) = match command {
+        CtlCommand::Status => ("GET", "status".to_string()),
+        CtlCommand::Pause => ("POST", "pause".to_string()),
+        CtlCommand::Resume => ("POST", "resume".to_string()),
+        CtlCommand::Backup(args) => ("POST", format!("backup/{}", args.location())),
+        CtlCommand::Forget(args) => ("POST", format!("forget/{}", args.location())),
+        CtlCommand::Check(args) => ("POST", format!("check/{}", args.location())),
+    };
+    let url = format!("http://{address}/{path}");
+    let agent: ureq::Agent = ureq::Agent::config_builder()
+        .timeout_global(Some(READ_TIMEOUT))
+        .http_status_as_error(false)
+        .build()
+        .into();
+    let mut request = ureq::http::Request::builder().method(method).uri(&url);
+    if let Some(token) = config.daemon().control_token() {
+        request = request.header("Authorization", format!("Bearer {token}"));
+    }
+    let request = request.body(()).map_err(|e| anyhow!("{e}"))?;
+    let mut response = agent
+        .run(request)
+        .map_err(|e| anyhow!("Failed to reach the daemon at {address}: {e}"))?;
+    let body = response
+        .body_mut()
+        .read_to_string()
+        .map_err(|e| anyhow!("Failed to read the response of the daemon: {e}"))?;
+    let body: serde_json::Value =
+        serde_json::from_str(&body).map_err(|e| anyhow!("Invalid response of the daemon: {e}"))?;
+    if !response.status().is_success() {
+        let message = body["error"].as_str().unwrap_or("unknown error");
+        bail!("The daemon rejected the request: {message}");
+    }
+
+    if output::is_json() {
+        println!("{body}");
+        return Ok(());
+    }
+    match command {
+        CtlCommand::Status => {
+            let status: Status = serde_json::from_value(body)?;
+            print_status(&status);
+        }
+        CtlCommand::Pause => print_log!(Level::INFO, "Schedules paused."),
+        CtlCommand::Resume => print_log!(Level::INFO, "Schedules resumed."),
+        CtlCommand::Backup(args) | CtlCommand::Forget(args) | CtlCommand::Check(args) => {
+            print_log!(
+                Level::INFO,
+                "Triggered {} of location {}.",
+                path.split('/').next().unwrap_or_default(),
+                args.location()
+            )
+        }
+    }
+    Ok(())
+}
+
+fn print_status(status: &Status) {
+    let time = |t: &DateTime<Local>| t.format("%Y-%m-%d %H:%M:%S").to_string();
+    let state = if status.paused { "paused" } else { "active" };
+    print_log!(Level::INFO, "Schedules are {state}.");
+    if !status.scheduled.is_empty() {
+        print_log!(Level::INFO, "Scheduled:");
+        for s in &status.scheduled {
+            let next = s.next.as_ref().map(time).unwrap_or("never".to_string());
+            print_log!(
+                Level::INFO,
+                "  {next}  {} of {} (\"{}\")",
+                s.operation,
+                s.location,
+                s.schedule
+            );
+        }
+    }
+    if !status.running.is_empty() {
+        print_log!(Level::INFO, "Running:");
+        for r in &status.running {
+            print_log!(
+                Level::INFO,
+                "  {}  {} of {}",
+                time(&r.started),
+                r.operation,
+                r.location
+            );
+        }
+    }
+    if !status.history.is_empty() {
+        print_log!(Level::INFO, "Finished:");
+        for r in &status.history {
+            let took = r
+                .duration()
+                .map(|d| config::Duration::from(d).to_string())
+                .unwrap_or_default();
+            let result = match &r.error {
+                Some(err) => format!("FAIL: {err}"),
+                None => "OK".to_string(),
+            };
+            print_log!(
+                Level::INFO,
+                "  {}  {} of {} {result} (took {took})",
+                time(&r.started),
+                r.operation,
+                r.location
+            );
+        }
+    }
+}
@@ -0,0 +1,355 @@
+use crate::cli::{BackupArgs, CheckArgs, ForgetArgs, StatusArgs};
+use crate::config::{Changes, Config, CronExpr, Name};
+use crate::event::Operation;
+use crate::print_log;
+use crate::{control, dashboard, output, redact, signal};
+use anyhow::{Result, bail};
+use chrono::{DateTime, Local};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, SystemTime};
+use tracing::Level;
+
+/// The longest time the scheduler sleeps at once. Waking up regularly keeps
+/// the schedule accurate when the system clock changes or the system resumes
+/// from suspend.
+const MAX_SLEEP: Duration = Duration::from_secs(60);
+
+/// How often the files of the configuration are checked for changes.
+const RELOAD_INTERVAL: Duration = Duration::from_secs(5);
+/// How often operations triggered via the control API are started.
+const CONTROL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A scheduled operation of a location.
+struct Job {
+    location: Name,
+    operation: Operation,
+    cron: CronExpr,
+    next: Option<DateTime<Local>>,
+}
+
+impl Job {
+    /// Computes the next run after the given time, delayed by a random jitter.
+    fn schedule(&mut self, after: &DateTime<Local>, jitter: Duration) {
+        let jitter = Duration::from_millis(fastrand::u64(0..=jitter.as_millis() as u64));
+        self.next = self
+            .cron
+            .next_after(after)
+            .map(|next| next + chrono::Duration::from_std(jitter).unwrap_or_default());
+    }
+}
+
+/// The scheduled operations of all locations. Operations whose schedule has
+/// not changed keep their next run from the previous jobs.
+fn jobs(config: &Config, previous: &[Job]) -> Vec<Job> {
+    let jitter = jitter(config);
+    let now = Local::now();
+    let mut jobs = Vec::new();
+    for (location_name, location) in config.locations() {
+        for (operation, cron) in location.schedule().entries() {
+            let mut job = Job {
+                location: location_name.clone(),
+                operation,
+                cron: cron.clone(),
+                next: None,
+            };
+            match previous.iter().find(|j| {
+                j.location == job.location
+                    && j.operation == job.operation
+                    && j.cron.to_string() == job.cron.to_string()
+            }) {
+                Some(previous) => job.next = previous.next,
+                None => job.schedule(&now, jitter),
+            }
+            jobs.push(job);
+        }
+    }
+    jobs
+}
+
+fn jitter(config: &Config) -> Duration {
+    config.daemon().jitter().map(Into::into).unwrap_or_default()
+}
+
+/// Runs the scheduled operations of all locations until the process is
+/// interrupted. Running operations are completed before it returns. If
+/// enabled, changes of the configuration apply to all later runs.
+pub fn run(config: Config) -> Result<()> {
+    let mut config = Arc::new(config);
+    if config.daemon().dashboard().is_some() {
+        output::keep_recent();
+    }
+    let listener = config
+        .daemon()
+        .control()
+        .map(|address| control::bind(address, "control requests"))
+        .transpose()?;
+    let dashboard = config
+        .daemon()
+        .dashboard()
+        .map(|address| control::bind(address, "dashboard requests"))
+        .transpose()?;
+    let mut jobs = jobs(&config, &[]);
+    if jobs.is_empty() && listener.is_none() {
+        bail!("No location defines a schedule.");
+    }
+
+    print_log!(
+        Level::INFO,
+        "Daemon started with {} scheduled operation(s).",
+        jobs.len()
+    );
+    log_jobs(&jobs);
+
+    let mut files = ConfigFiles::new(config.files());
+    let slots = Semaphore::new(config.daemon().max_concurrent());
+    let state = control::State::default();
+    let token = config.daemon().control_token().map(str::to_string);
+    let served = listener.is_some();
+    std::thread::scope(|scope| {
+        if let Some(listener) = listener {
+            let (state, token) = (&state, token.as_deref());
+            scope.spawn(move || {
+                control::serve(listener, |request| control::handle(request, token, state))
+            });
+        }
+        if let Some(dashboard) = dashboard {
+            let state = &state;
+            scope.spawn(move || {
+                control::serve(dashboard, |request| dashboard::handle(request, state))
+            });
+        }
+        // Starts the operation on its own thread, unless its previous run is
+        // still in progress.
+        let start = |config: &Arc<Config>, location_name: Name, operation, triggered| {
+            if !state.start(&location_name, operation, triggered) {
+                print_log!(
+                    Level::WARN,
+                    "Skip {} of location {location_name}, because the previous run is still in progress.",
+                    operation.as_str()
+                );
+                return;
+            }
+            let (slots, state) = (&slots, &state);
+            let config = Arc::clone(config);
+            scope.spawn(move || {
+                slots.acquire();
+                let mut error = None;
+                if signal::interrupted() {
+                    // Operations that are still waiting for a slot are not
+                    // started anymore.
+                    error = Some("Interrupted.".to_string());
+                } else if let Err(err) = run_job(&config, &location_name, operation) {
+                    print_log!(
+                        Level::ERROR,
+                        "{} of location {location_name} failed: {err}",
+                        operation.label()
+                    );
+                    error = Some(err.to_string());
+                }
+                slots.release();
+                state.finish(&location_name, operation, error);
+            });
+        };
+
+        loop {
+            if signal::interrupted() {
+                break;
+            }
+            let reload = config.daemon().reload();
+            if reload && files.changed() {
+                if let Some(new_config) = reload_config(&config) {
+                    config = Arc::new(new_config);
+                    jobs = self::jobs(&config, &jobs);
+                    log_jobs(&jobs);
+                    slots.resize(config.daemon().max_concurrent());
+                }
+                files = ConfigFiles::new(config.files());
+            }
+            state.set_scheduled(
+                &config,
+                jobs.iter()
+                    .map(|j| (&j.location, j.operation, j.cron.to_string(), j.next)),
+            );
+            for (location_name, operation) in state.take_triggered() {
+                start(&config, location_name, operation, true);
+            }
+            let max_sleep = match (served, reload) {
+                (true, _) => MAX_SLEEP.min(CONTROL_INTERVAL),
+                (false, true) => MAX_SLEEP.min(RELOAD_INTERVAL),
+                (false, false) => MAX_SLEEP,
+            };
+
+            let now = Local::now();
+            let Some(job) = jobs
+                .iter_mut()
+                .filter(|j| j.next.is_some())
+                .min_by_key(|j| j.next)
+            else {
+                if reload || served {
+                    // A changed configuration may schedule operations again
+                    // and operations may be triggered.
+                    signal::sleep(max_sleep);
+                    continue;
+                }
+                print_log!(Level::INFO, "No further scheduled operations.");
+                break;
+            };
+            let next = job.next.expect("Jobs without next run are filtered.");
+            if next > now {
+                signal::sleep((next - now).to_std().unwrap_or_default().min(max_sleep));
+                continue;
+            }
+            job.schedule(&now, jitter(&config));
+
+            if state.paused() {
+                print_log!(
+                    Level::INFO,
+                    "Skip {} of location {}, because schedules are paused.",
+                    job.operation.as_str(),
+                    job.location
+                );
+                continue;
+            }
+            start(&config, job.location.clone(), job.operation, false);
+        }
+    });
+
+    Ok(())
+}
+
+fn log_jobs(jobs: &[Job]) {
+    for job in jobs {
+        print_log!(
+            Level::DEBUG,
+            "{} of location {} scheduled at \"{}\".",
+            job.operation.label(),
+            job.location,
+            job.cron
+        );
+    }
+}
+
+/// Reads the configuration again from its file. An invalid configuration is
+/// reported and not used.
+fn reload_config(current: &Config) -> Option<Config> {
+    let config = match Config::new(current.sources(), current.settings()) {
+        Ok(config) => config,
+        Err(err) => {
+            print_log!(
+                Level::WARN,
+                "Failed to reload configuration, keep the current one: {err}"
+            );
+            return None;
+        }
+    };
+    for secret in config.secrets() {
+        redact::add_secret(secret);
+    }
+    print_log!(
+        Level::INFO,
+        "Configuration reloaded: {}.",
+        describe(&config.changes(current))
+    );
+    Some(config)
+}
+
+/// A summary of the changes, e.g. "location docs added; repository nas
+/// changed".
+fn describe(changes: &Changes) -> String {
+    let mut parts = Vec::new();
+    for (kind, section) in [
+        ("location", &changes.locations),
+        ("repository", &changes.repos),
+    ] {
+        for (verb, names) in [
+            ("added", &section.added),
+            ("removed", &section.removed),
+            ("changed", &section.changed),
+        ] {
+            if !names.is_empty() {
+                parts.push(format!("{kind} {} {verb}", names.join(", ")));
+            }
+        }
+    }
+    if changes.settings {
+        parts.push("other settings changed".to_string());
+    }
+    if parts.is_empty() {
+        "no changes".to_string()
+    } else {
+        parts.join("; ")
+    }
+}
+
+/// The modification times of the files of a configuration.
+struct ConfigFiles(Vec<(PathBuf, Option<SystemTime>)>);
+
+impl ConfigFiles {
+    fn new(files: &[PathBuf]) -> Self {
+        Self(files.iter().map(|f| (f.clone(), modified(f))).collect())
+    }
+
+    /// Whether any file has been modified, created or removed since.
+    fn changed(&self) -> bool {
+        self.0.iter().any(|(file, time)| modified(file) != *time)
+    }
+}
+
+fn modified(file: &Path) -> Option<SystemTime> {
+    std::fs::metadata(file).and_then(|m| m.modified()).ok()
+}
+
+fn run_job(config: &Config, location_name: &Name, operation: Operation) -> Result<()> {
+    let selection = vec![location_name.clone().into()];
+    crate::with_report(operation.label(), |report| match operation {
+        Operation::Backup => crate::backup(config, &BackupArgs::new(selection, false), report),
+        Operation::Forget => crate::forget(config, &ForgetArgs::new(selection, false), report),
+        Operation::Check => crate::check(config, &CheckArgs::new(selection), report),
+        Operation::Status => crate::status(config, &StatusArgs::new(selection)),
+        // Schedules do not support copies and maintenance, see
+        // `Schedule::entries`.
+        Operation::Copy | Operation::Maintenance => {
+            unreachable!("{operation} is not scheduled")
+        }
+    })
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Limits the number of operations that run at the same time.
+struct Semaphore {
+    /// The number of operations that hold a permit and the number of permits.
+    state: Mutex<(usize, usize)>,
+    released: Condvar,
+}
+
+impl Semaphore {
+    fn new(permits: usize) -> Self {
+        Self {
+            state: Mutex::new((0, permits)),
+            released: Condvar::new(),
+        }
+    }
+
+    fn acquire(&self) {
+        let mut state = lock(&self.state);
+        while state.0 >= state.1 {
+            state = self.released.wait(state).unwrap_or_else(|e| e.into_inner());
+        }
+        state.0 += 1;
+    }
+
+    fn release(&self) {
+        lock(&self.state).0 -= 1;
+        self.released.notify_one();
+    }
+
+    /// Changes the number of permits. Operations that hold a permit keep it.
+    fn resize(&self, permits: usize) {
+        lock(&self.state).1 = permits;
+        self.released.notify_all();
+    }
+}
@@ -0,0 +1,145 @@
+use crate::config::{self, Config};
+use crate::control::{Request, Response, State, Status};
+use crate::report::{HTML_STYLE, escape_html};
+use crate::{output, print_log, state};
+use chrono::{DateTime, Local};
+use tracing::Level;
+
+/// How often the page reloads itself, in seconds.
+const REFRESH_INTERVAL: u32 = 60;
+
+/// Answers a request to the dashboard, a read-only page with the state of the
+/// daemon.
+pub fn handle(request: &Request, state: &State) -> Response {
+    match (request.method(), request.path()) {
+        ("GET", "/") => Response::html(200, state.with_status(page)),
+        ("GET", _) => Response::html(404, "Not found.".to_string()),
+        _ => Response::html(405, "Method not allowed.".to_string()),
+    }
+}
+
+fn page(status: &Status) -> String {
+    let mut html = String::new();
+    html.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+    html.push_str(&format!(
+        "<meta http-equiv=\"refresh\" content=\"{REFRESH_INTERVAL}\">\n"
+    ));
+    html.push_str("<title>aresticrat</title>\n");
+    html.push_str(HTML_STYLE);
+    html.push_str("</head>\n<body>\n<h1>aresticrat</h1>\n");
+    html.push_str(&format!(
+        "<p>{}. Schedules are {}.</p>\n",
+        time(&Local::now()),
+        if status.paused() { "paused" } else { "active" }
+    ));
+    if let Some(config) = status.config() {
+        locations(&mut html, config);
+    }
+
+    html.push_str("<h2>Upcoming</h2>\n<table>\n<tr><th>Time</th><th>Operation</th><th>Location</th><th>Schedule</th></tr>\n");
+    for scheduled in status.scheduled() {
+        html.push_str(&format!(
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+            scheduled.next().as_ref().map_or("never".to_string(), time),
+            escape_html(scheduled.operation()),
+            escape_html(scheduled.location()),
+            escape_html(scheduled.schedule()),
+        ));
+    }
+    html.push_str("</table>\n");
+
+    html.push_str("<h2>Runs</h2>\n<table>\n<tr><th>Started</th><th>Operation</th><th>Location</th><th>Status</th><th>Duration</th></tr>\n");
+    for run in status.running() {
+        html.push_str(&format!(
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>RUNNING</td><td></td></tr>\n",
+            time(&run.started()),
+            escape_html(run.operation()),
+            escape_html(run.location()),
+        ));
+    }
+    for run in status.history() {
+        let (class, label) = match run.error() {
+            Some(_) => ("fail", "FAIL"),
+            None => ("ok", "OK"),
+        };
+        html.push_str(&format!(
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td class=\"{class}\" title=\"{}\">{label}</td><td>{}</td></tr>\n",
+            time(&run.started()),
+            escape_html(run.operation()),
+            escape_html(run.location()),
+            escape_html(run.error().unwrap_or_default()),
+            run.duration()
+                .map(|d| config::Duration::from(d).to_string())
+                .unwrap_or_default(),
+        ));
+    }
+    html.push_str("</table>\n");
+
+    html.push_str("<h2>Log</h2>\n<pre>");
+    for (time, level, message) in output::recent() {
+        html.push_str(&format!(
+            "{} {:<5} {}\n",
+            time.format("%Y-%m-%d %H:%M:%S"),
+            level,
+            escape_html(&message)
+        ));
+    }
+    html.push_str("</pre>\n</body>\n</html>\n");
+    html
+}
+
+/// The table of the last backup of every location to every repository, as
+/// recorded in the state file.
+fn locations(html: &mut String, config: &Config) {
+    let state = match state::load() {
+        Ok(state) => state,
+        Err(err) => {
+            print_log!(Level::WARN, "Failed to read state file: {err}");
+            html.push_str(&format!(
+                "<p class=\"fail\">Failed to read state file: {}</p>\n",
+                escape_html(&err.to_string())
+            ));
+            return;
+        }
+    };
+    let mut location_names: Vec<_> = config.locations().keys().collect();
+    location_names.sort();
+
+    html.push_str("<h2>Locations</h2>\n<table>\n<tr><th>Location</th><th>Repository</th><th>Last backup</th><th>Status</th><th>Snapshot age</th></tr>\n");
+    for location_name in location_names {
+        let max_age = crate::get_status_options(location_name, config).max_age();
+        for repo_name in config.locations()[location_name].repos() {
+            let backup = state.backup(location_name, repo_name);
+            let (class, label) = match backup {
+                None => ("", "NEVER"),
+                Some(backup) if backup.error().is_some() => ("fail", "FAIL"),
+                Some(_) => ("ok", "OK"),
+            };
+            let age = backup
+                .and_then(|b| b.last_success())
+                .and_then(|t| (Local::now() - t).to_std().ok());
+            let age_class = match (age, max_age) {
+                (Some(age), Some(max_age)) if age > max_age.into() => "fail",
+                _ => "",
+            };
+            html.push_str(&format!(
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td class=\"{class}\" title=\"{}\">{label}</td><td class=\"{age_class}\">{}</td></tr>\n",
+                escape_html(location_name.as_str()),
+                escape_html(repo_name.as_str()),
+                backup
+                    .and_then(|b| b.last_run())
+                    .as_ref()
+                    .map(time)
+                    .unwrap_or_default(),
+                escape_html(backup.and_then(|b| b.error()).unwrap_or_default()),
+                age.map(|age| config::Duration::from(age).to_string())
+                    .unwrap_or_default(),
+            ));
+        }
+    }
+    html.push_str("</table>\n");
+}
+
+fn time(time: &DateTime<Local>) -> String {
+    time.format("%Y-%m-%d %H:%M:%S").to_string()
+}
@@ -0,0 +1,107 @@
+use crate::config::{CommandSeq, Database, DatabaseKind, Source};
+use std::path::Path;
+
+/// The dumps of all databases of a location. Every database is stored in a
+/// snapshot of its own, so that it can be restored independently.
+pub fn sources(kind: DatabaseKind, database: &Database) -> Vec<Source> {
+    database
+        .databases()
+        .iter()
+        .map(|name| source(kind, database, name))
+        .collect()
+}
+
+fn source(kind: DatabaseKind, database: &Database, name: &str) -> Source {
+    let ((args, filename), password_var) = match kind {
+        DatabaseKind::Postgres => (postgres(database, name), Some("PGPASSWORD")),
+        DatabaseKind::Mysql => (mysql(database, name), Some("MYSQL_PWD")),
+        DatabaseKind::Sqlite => (sqlite(database, name), None),
+    };
+    // Passwords are passed in the environment, so that they do not appear
+    // in the process list.
+    let env = password_var
+        .zip(database.password())
+        .map(|(var, password)| (var.to_string(), password.to_string()))
+        .into_iter()
+        .collect();
+    Source::Command {
+        command: CommandSeq::from_vec(args).expect("Dump commands have a program."),
+        filename: Some(filename),
+        env,
+    }
+}
+
+/// Dumps a PostgreSQL database with pg_dump. Compressed dumps use the custom
+/// format, which pg_restore reads.
+fn postgres(database: &Database, name: &str) -> (Vec<String>, String) {
+    let mut args = vec!["pg_dump".to_string()];
+    connection_args(database, "--username", &mut args);
+    if database.compress() {
+        args.push("--format=custom".to_string());
+    }
+    args.extend_from_slice(database.dump_args());
+    args.push("--dbname".to_string());
+    args.push(name.to_string());
+    let extension = if database.compress() { "dump" } else { "sql" };
+    (args, format!("{name}.{extension}"))
+}
+
+/// Dumps a MySQL or MariaDB database with mysqldump. The dump includes the
+/// statement that creates the database.
+fn mysql(database: &Database, name: &str) -> (Vec<String>, String) {
+    let mut args: Vec<_> = [
+        "mysqldump",
+        "--single-transaction",
+        "--routines",
+        "--triggers",
+    ]
+    .map(String::from)
+    .to_vec();
+    connection_args(database, "--user", &mut args);
+    args.extend_from_slice(database.dump_args());
+    args.push("--databases".to_string());
+    args.push(name.to_string());
+    compressed(database, args, format!("{name}.sql"))
+}
+
+/// Dumps an SQLite database file as SQL statements.
+fn sqlite(database: &Database, path: &str) -> (Vec<String>, String) {
+    let mut args = vec!["sqlite3".to_string(), "-readonly".to_string()];
+    args.extend_from_slice(database.dump_args());
+    args.push(path.to_string());
+    args.push(".dump".to_string());
+    let stem = Path::new(path)
+        .file_stem()
+        .map_or_else(|| path.into(), |s| s.to_string_lossy());
+    compressed(database, args, format!("{stem}.sql"))
+}
+
+fn connection_args(database: &Database, user_option: &str, args: &mut Vec<String>) {
+    if let Some(host) = database.host() {
+        args.push("--host".to_string());
+        args.push(host.to_string());
+    }
+    if let Some(port) = database.port() {
+        args.push("--port".to_string());
+        args.push(port.to_string());
+    }
+    if let Some(user) = database.user() {
+        args.push(user_option.to_string());
+        args.push(user.to_string());
+    }
+}
+
+/// Pipes the dump through gzip, if compression is enabled. The shell exits
+/// with the status of the dump, so that a failed dump fails the backup instead
+/// of storing a truncated one. (Not every shell supports `set -o pipefail`.)
+fn compressed(database: &Database, args: Vec<String>, filename: String) -> (Vec<String>, String) {
+    if !database.compress() {
+        return (args, filename);
+    }
+    let script = format!(
+        "exec 4>&1; status=$({{ {{ {}; echo $? >&3; }} | gzip >&4; }} 3>&1); exit $status",
+        shell_words::join(&args)
+    );
+    let args = vec!["sh".to_string(), "-c".to_string(), script];
+    (args, format!("{filename}.gz"))
+}
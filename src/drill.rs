@@ -0,0 +1,342 @@
+use crate::cli::DrillArgs;
+use crate::config::{self, Config, Name};
+use crate::exit::{self, Status};
+use crate::report::format_size;
+use crate::restic_api;
+use crate::style::{self, Color};
+use crate::{output, print_log, signal};
+use anyhow::{Result, bail};
+use serde::Serialize;
+use std::fs::File;
+use std::io::{ErrorKind, Read};
+use std::path::{Component, Path, PathBuf};
+use std::time::Instant;
+use tracing::Level;
+
+/// The result of the restore of a location from a repository.
+#[derive(Default, Serialize)]
+struct Drill {
+    location: String,
+    repository: String,
+    snapshot: Option<String>,
+    /// The number and size of the restored files.
+    files: u64,
+    bytes: u64,
+    /// Restored files that are identical to their live files.
+    identical: u64,
+    /// Restored files whose live files have been modified since the snapshot.
+    modified: u64,
+    /// Restored files whose live files have been deleted since the snapshot.
+    deleted: u64,
+    /// Restored files that differ from their live files, although those have
+    /// not been modified since the snapshot.
+    differing: Vec<PathBuf>,
+    duration: f64,
+    error: Option<String>,
+}
+
+impl Drill {
+    fn success(&self) -> bool {
+        self.error.is_none() && self.differing.is_empty()
+    }
+}
+
+/// Restores the latest snapshot of the location from each of its repositories
+/// and compares the restored files with the live files of the location's
+/// paths.
+pub fn run(config: &Config, args: &DrillArgs) -> Result<()> {
+    let location_name = args.location();
+    let Some(location) = config.locations().get(location_name) else {
+        bail!("Location {location_name} is not defined.");
+    };
+    let repo_names: Vec<&Name> = if args.repos().is_empty() {
+        location.repos().iter().collect()
+    } else {
+        for repo_name in args.repos() {
+            if !location.repos().contains(repo_name) {
+                bail!("Location {location_name} is not backed up to repository {repo_name}.");
+            }
+        }
+        args.repos().iter().collect()
+    };
+    let api = restic_api::Api::new(
+        config.executable().to_string(),
+        crate::restic_verbosity(),
+        crate::restic_progress_fps(),
+        config.retry().clone(),
+    );
+    // Only files below the paths of the location have live counterparts,
+    // not e.g. the output of its commands.
+    let live_paths: Vec<PathBuf> = location
+        .paths()
+        .iter()
+        .filter_map(|path| path.canonicalize().ok())
+        .collect();
+
+    let mut drills = Vec::new();
+    for repo_name in repo_names {
+        if signal::interrupted() {
+            break;
+        }
+        let mut drill = Drill {
+            location: location_name.to_string(),
+            repository: repo_name.to_string(),
+            ..Default::default()
+        };
+        let started = Instant::now();
+        if let Err(err) = restore(
+            config,
+            &api,
+            location_name,
+            repo_name,
+            &live_paths,
+            args,
+            &mut drill,
+        ) {
+            drill.error = Some(err.to_string());
+        }
+        drill.duration = started.elapsed().as_secs_f64();
+        print_drill(&drill, args.dry_run());
+        drills.push(drill);
+    }
+
+    if output::is_json() {
+        println!("{}", serde_json::to_string(&drills)?);
+    }
+    let failed = drills.iter().filter(|drill| !drill.success()).count();
+    if failed > 0 {
+        return Err(exit::Error::new(
+            Status::Failure,
+            format!("{failed} drill(s) of location {location_name} failed."),
+        )
+        .into());
+    }
+    Ok(())
+}
+
+fn restore(
+    config: &Config,
+    api: &restic_api::Api,
+    location_name: &Name,
+    repo_name: &Name,
+    live_paths: &[PathBuf],
+    args: &DrillArgs,
+    drill: &mut Drill,
+) -> Result<()> {
+    let Some(repo) = crate::resolve_location_repository(repo_name, location_name, config) else {
+        bail!("Repository {repo_name} is not defined.");
+    };
+    let Some(snapshot) = api.latest_snapshot(&repo, crate::get_tag(location_name))? else {
+        bail!("Repository {repo_name} has no snapshot of location {location_name}.");
+    };
+    drill.snapshot = Some(snapshot.short_id.clone());
+    print_log!(
+        Level::INFO,
+        "Restore snapshot {} of location {location_name} from repository {repo_name} ...",
+        snapshot.short_id
+    );
+
+    let target = Target::new(args.target(), location_name, repo_name)?;
+    api.restore(
+        &repo,
+        &snapshot.short_id,
+        target.path(),
+        args.verify(),
+        args.restic_args(),
+        args.dry_run(),
+    )?;
+    if !args.dry_run() {
+        compare(target.path(), target.path(), live_paths, drill)?;
+    }
+    Ok(())
+}
+
+/// The directory the snapshot is restored into. A temporary directory is
+/// removed when it is dropped.
+struct Target {
+    path: PathBuf,
+    temporary: bool,
+}
+
+impl Target {
+    fn new(dir: Option<&Path>, location_name: &Name, repo_name: &Name) -> Result<Self> {
+        if let Some(dir) = dir {
+            let path = dir.join(format!("{location_name}@{repo_name}"));
+            std::fs::create_dir_all(&path)?;
+            return Ok(Self {
+                path,
+                temporary: false,
+            });
+        }
+        let path = std::env::temp_dir().join(format!(
+            "aresticrat-drill-{location_name}-{}-{:08x}",
+            std::process::id(),
+            fastrand::u32(..)
+        ));
+        create_private_dir(&path)?;
+        Ok(Self {
+            path,
+            temporary: true,
+        })
+    }
+
+    fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for Target {
+    fn drop(&mut self) {
+        if self.temporary {
+            if let Err(err) = std::fs::remove_dir_all(&self.path) {
+                print_log!(
+                    Level::WARN,
+                    "Failed to remove restored files in {}: {err}",
+                    self.path.display()
+                );
+            }
+        }
+    }
+}
+
+/// Creates a directory that only the current user can access, because the
+/// restored files may be confidential.
+#[cfg(unix)]
+fn create_private_dir(path: &Path) -> std::io::Result<()> {
+    use std::os::unix::fs::DirBuilderExt;
+    std::fs::DirBuilder::new().mode(0o700).create(path)
+}
+
+#[cfg(not(unix))]
+fn create_private_dir(path: &Path) -> std::io::Result<()> {
+    std::fs::create_dir(path)
+}
+
+/// Compares the restored files below the directory with their live files.
+fn compare(root: &Path, dir: &Path, live_paths: &[PathBuf], drill: &mut Drill) -> Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        if signal::interrupted() {
+            bail!("Interrupted.");
+        }
+        let restored = entry?.path();
+        let metadata = restored.symlink_metadata()?;
+        if metadata.is_dir() {
+            compare(root, &restored, live_paths, drill)?;
+            continue;
+        }
+        if !metadata.is_file() {
+            continue;
+        }
+        drill.files += 1;
+        drill.bytes += metadata.len();
+
+        let Some(live) = restored.strip_prefix(root).ok().and_then(live_path) else {
+            continue;
+        };
+        if !live_paths.iter().any(|path| live.starts_with(path)) {
+            continue;
+        }
+        let live_metadata = match live.symlink_metadata() {
+            Ok(live_metadata) => live_metadata,
+            Err(err) if err.kind() == ErrorKind::NotFound => {
+                drill.deleted += 1;
+                continue;
+            }
+            Err(err) => return Err(err.into()),
+        };
+        // restic restores the modification times, so a live file with a
+        // different one has been modified since the snapshot.
+        if live_metadata.modified().ok() != metadata.modified().ok() {
+            drill.modified += 1;
+        } else if live_metadata.len() == metadata.len() && same_content(&restored, &live)? {
+            drill.identical += 1;
+        } else {
+            print_log!(
+                Level::ERROR,
+                "Restored file {} differs from the live file, although it has not been modified since the snapshot.",
+                live.display()
+            );
+            drill.differing.push(live);
+        }
+    }
+    Ok(())
+}
+
+/// The live path of a file restored relative to the target directory, e.g.
+/// `/home/user/file` for `home/user/file` or `C:\file` for `C\file`.
+fn live_path(relative: &Path) -> Option<PathBuf> {
+    let mut components = relative.components();
+    let first = match components.next()? {
+        Component::Normal(first) => first.to_str()?,
+        _ => return None,
+    };
+    let root = if cfg!(windows) {
+        PathBuf::from(format!("{first}:\\"))
+    } else {
+        Path::new("/").join(first)
+    };
+    Some(root.join(components.as_path()))
+}
+
+fn same_content(a: &Path, b: &Path) -> std::io::Result<bool> {
+    let (mut a, mut b) = (File::open(a)?, File::open(b)?);
+    let mut buf_a = vec![0; 64 * 1024];
+    let mut buf_b = vec![0; 64 * 1024];
+    loop {
+        let n = read_full(&mut a, &mut buf_a)?;
+        if n != read_full(&mut b, &mut buf_b)? || buf_a[..n] != buf_b[..n] {
+            return Ok(false);
+        }
+        if n == 0 {
+            return Ok(true);
+        }
+    }
+}
+
+/// Reads until the buffer is full or the end of the file is reached.
+fn read_full(file: &mut File, buf: &mut [u8]) -> std::io::Result<usize> {
+    let mut n = 0;
+    while n < buf.len() {
+        match file.read(&mut buf[n..])? {
+            0 => break,
+            read => n += read,
+        }
+    }
+    Ok(n)
+}
+
+fn print_drill(drill: &Drill, dry_run: bool) {
+    let name = format!("{}@{}", drill.location, drill.repository);
+    let took = config::Duration::from(std::time::Duration::from_secs_f64(drill.duration));
+    if let Some(err) = &drill.error {
+        print_log!(
+            Level::ERROR,
+            "{name}: {}: {err}",
+            style::paint_stderr("FAILED", Color::Red)
+        );
+    } else if dry_run {
+        print_log!(
+            Level::INFO,
+            "{name}: {} (dry run of snapshot {}, took {took})",
+            style::paint("OK", Color::Green),
+            drill.snapshot.as_deref().unwrap_or_default()
+        );
+    } else {
+        let status = if drill.success() {
+            style::paint("OK", Color::Green)
+        } else {
+            style::paint("DIFFERS", Color::Red)
+        };
+        print_log!(
+            Level::INFO,
+            "{name}: {status}: restored {} files ({}) of snapshot {} in {took}: {} identical, {} modified and {} deleted since, {} differing",
+            drill.files,
+            format_size(drill.bytes),
+            drill.snapshot.as_deref().unwrap_or_default(),
+            drill.identical,
+            drill.modified,
+            drill.deleted,
+            drill.differing.len()
+        );
+    }
+}
@@ -0,0 +1,256 @@
+use crate::cli::{BackupArgs, CheckArgs, DueArgs, ForgetArgs, MaintenanceArgs};
+use crate::config::{self, Config, CronExpr, LocationRepo, Name};
+use crate::event::Operation;
+use crate::report::Report;
+use crate::style::{self, Color};
+use crate::{output, pipeline, print_log, signal, state};
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Local};
+use serde::Serialize;
+use std::collections::HashSet;
+use tracing::Level;
+
+/// An operation that runs according to a schedule or an interval.
+#[derive(Serialize)]
+struct Item {
+    operation: &'static str,
+    /// The location of a backup or the location whose settings determine the
+    /// check or prune of the repository.
+    location: Name,
+    repository: Name,
+    /// The time of the last successful run, if any.
+    last: Option<DateTime<Local>>,
+    /// The time from which the operation is pending. Operations that have
+    /// never run are pending right away.
+    due: Option<DateTime<Local>>,
+    pending: bool,
+    /// How a pending prune runs: with the forget of the location or with the
+    /// maintenance of the repository.
+    #[serde(skip)]
+    with_forget: bool,
+}
+
+/// Prints the scheduled operations of the selected locations and whether
+/// they are pending.
+pub fn show(config: &Config, args: &DueArgs) -> Result<()> {
+    let items = items(config, args.selected_locations())?;
+    if output::is_json() {
+        println!("{}", serde_json::to_string(&items)?);
+        return Ok(());
+    }
+    if items.is_empty() {
+        print_log!(
+            Level::INFO,
+            "No location has a schedule or interval for backup, check or prune."
+        );
+    }
+    let time = |t: &DateTime<Local>| t.format("%Y-%m-%d %H:%M").to_string();
+    for item in &items {
+        let status = match (item.pending, &item.due) {
+            (true, Some(due)) => format!(
+                "{} since {}",
+                style::paint("PENDING", Color::Red),
+                time(due)
+            ),
+            (true, None) => style::paint("PENDING", Color::Red),
+            (false, Some(due)) => format!("due at {}", time(due)),
+            (false, None) => "not due".to_string(),
+        };
+        let last = item.last.as_ref().map_or("never".to_string(), time);
+        print_log!(
+            Level::INFO,
+            "{}@{}: {} {status} (last: {last})",
+            item.location,
+            item.repository,
+            item.operation
+        );
+    }
+    Ok(())
+}
+
+/// Runs the pending operations of the selected locations: first the backups,
+/// then the prunes and the checks of the repositories. Later operations still
+/// run after earlier ones have failed.
+pub fn run(config: &Config, args: &DueArgs, report: &mut Report) -> Result<()> {
+    let pending: Vec<_> = items(config, args.selected_locations())?
+        .into_iter()
+        .filter(|item| item.pending)
+        .collect();
+    if pending.is_empty() {
+        print_log!(Level::INFO, "No operation is pending.");
+        return Ok(());
+    }
+    let selection = |operation: &str, with_forget: bool| -> Vec<LocationRepo> {
+        pending
+            .iter()
+            .filter(|item| item.operation == operation && item.with_forget == with_forget)
+            .map(|item| LocationRepo::new(item.location.clone(), Some(item.repository.clone())))
+            .collect()
+    };
+    let mut failed = Vec::new();
+
+    let backups = selection(Operation::Backup.as_str(), false);
+    if !backups.is_empty() {
+        print_log!(Level::INFO, "Run pending backups ...");
+        let backup_args = BackupArgs::new(backups, args.dry_run());
+        if let Err(err) = crate::backup(config, &backup_args, report) {
+            print_log!(Level::ERROR, "Pending backups failed: {err}");
+            failed.push(("backup".to_string(), crate::error_status(&err)));
+        }
+    }
+
+    let forgets = selection(PRUNE, true);
+    if !forgets.is_empty() && !signal::interrupted() {
+        print_log!(Level::INFO, "Run pending prunes with forget ...");
+        let forget_args = ForgetArgs::new(forgets, args.dry_run());
+        if let Err(err) = crate::forget(config, &forget_args, report) {
+            print_log!(Level::ERROR, "Pending forgets failed: {err}");
+            failed.push(("forget".to_string(), crate::error_status(&err)));
+        }
+    }
+
+    // Checks and maintenance have no dry run.
+    let maintained: Vec<_> = selection(PRUNE, false)
+        .into_iter()
+        .filter_map(|location_repo| location_repo.repo().cloned())
+        .collect();
+    if !maintained.is_empty() && !signal::interrupted() {
+        print_log!(Level::INFO, "Run pending prunes with maintenance ...");
+        if args.dry_run() {
+            print_log!(Level::INFO, "Skipped maintenance in dry run.");
+        } else if let Err(err) =
+            crate::maintenance(config, &MaintenanceArgs::new(maintained.clone()), report)
+        {
+            print_log!(Level::ERROR, "Pending maintenance failed: {err}");
+            failed.push(("maintenance".to_string(), crate::error_status(&err)));
+        }
+    }
+
+    // The maintenance has already checked its repositories.
+    let checks: Vec<_> = selection(Operation::Check.as_str(), false)
+        .into_iter()
+        .filter(|location_repo| {
+            location_repo
+                .repo()
+                .is_none_or(|repo| !maintained.contains(repo))
+        })
+        .collect();
+    if !checks.is_empty() && !signal::interrupted() {
+        print_log!(Level::INFO, "Run pending checks ...");
+        if args.dry_run() {
+            print_log!(Level::INFO, "Skipped check in dry run.");
+        } else if let Err(err) = crate::check(config, &CheckArgs::new(checks), report) {
+            print_log!(Level::ERROR, "Pending checks failed: {err}");
+            failed.push(("check".to_string(), crate::error_status(&err)));
+        }
+    }
+
+    pipeline::conclude("Pending operations", &failed)
+}
+
+const PRUNE: &str = "prune";
+
+/// The scheduled operations of the selected locations, sorted by location and
+/// repository. Checks and prunes belong to the repository and are listed once,
+/// with the first location that uses the repository.
+fn items(config: &Config, selection: &[LocationRepo]) -> Result<Vec<Item>> {
+    let state = state::load().map_err(|e| anyhow!("Failed to read state file: {e}"))?;
+    let now = Local::now();
+
+    let mut selected: Vec<_> = crate::resolve_selection(selection, config)?
+        .into_iter()
+        .map(|(location_name, repo_names)| {
+            let mut repo_names: Vec<_> = repo_names.into_iter().collect();
+            repo_names.sort();
+            (location_name, repo_names)
+        })
+        .collect();
+    selected.sort();
+
+    let mut items = Vec::new();
+    let mut seen = HashSet::new();
+    for (location_name, repo_names) in selected {
+        let location = &config.locations()[&location_name];
+        let schedule = location.schedule();
+        let check_opts = crate::get_check_options(&location_name, config);
+        for repo_name in repo_names {
+            let mut item = |operation, last, cron, interval, with_forget| {
+                if let Some((due, pending)) = due(last, cron, interval, &now) {
+                    items.push(Item {
+                        operation,
+                        location: location_name.clone(),
+                        repository: repo_name.clone(),
+                        last,
+                        due,
+                        pending,
+                        with_forget,
+                    });
+                }
+            };
+            let last_backup = state
+                .backup(&location_name, &repo_name)
+                .and_then(|backup| backup.last_success());
+            item(
+                Operation::Backup.as_str(),
+                last_backup,
+                schedule.backup(),
+                location.min_interval(),
+                false,
+            );
+
+            let Some(repo) = config.repos().get(&repo_name) else {
+                continue;
+            };
+            if !seen.insert(repo_name.clone()) {
+                continue;
+            }
+            let repo_state = state.repo(&repo_name);
+            item(
+                Operation::Check.as_str(),
+                repo_state.last_check(),
+                schedule.check(),
+                repo.check().interval().or(check_opts.interval()),
+                false,
+            );
+            let forget_opts = crate::get_repo_forget_options(&location_name, &repo_name, config);
+            let with_forget = forget_opts.prune() && forget_opts.has_policy();
+            let prune_interval = if with_forget {
+                forget_opts.prune_interval()
+            } else {
+                config.maintenance().prune_interval()
+            };
+            item(
+                PRUNE,
+                repo_state.last_prune(),
+                None,
+                prune_interval,
+                with_forget,
+            );
+        }
+    }
+    Ok(items)
+}
+
+/// The time from which an operation is pending and whether it is pending
+/// now. The operation is due at the first occurrence of the schedule or at
+/// the end of the interval after the last run, whichever comes first.
+/// Operations without schedule and interval are never due.
+fn due(
+    last: Option<DateTime<Local>>,
+    cron: Option<&CronExpr>,
+    interval: Option<config::Duration>,
+    now: &DateTime<Local>,
+) -> Option<(Option<DateTime<Local>>, bool)> {
+    if cron.is_none() && interval.is_none() {
+        return None;
+    }
+    let Some(last) = last else {
+        return Some((None, true));
+    };
+    let by_schedule = cron.and_then(|cron| cron.next_after(&last));
+    let by_interval = interval.and_then(|interval| {
+        last.checked_add_signed(chrono::Duration::from_std(interval.into()).ok()?)
+    });
+    let due = by_schedule.into_iter().chain(by_interval).min();
+    Some((due, due.is_some_and(|due| due <= *now)))
+}
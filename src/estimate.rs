@@ -0,0 +1,152 @@
+use crate::cli::EstimateArgs;
+use crate::config::{Config, ExtraArgs};
+use crate::exit::{self, Status};
+use crate::print_log;
+use crate::report::format_size;
+use crate::restic_api::{self, Estimate};
+use crate::style::{self, Color};
+use anyhow::Result;
+use tracing::Level;
+
+const HEADER: [&str; 7] = [
+    "LOCATION",
+    "REPOSITORY",
+    "NEW",
+    "CHANGED",
+    "UNCHANGED",
+    "PROCESSED",
+    "ADDED",
+];
+
+/// Runs a dry-run backup of the selected locations to each of their
+/// repositories and prints a table of what would be added. Nothing is
+/// written, so hooks do not run.
+pub fn run(config: &Config, args: &EstimateArgs) -> Result<()> {
+    let api = restic_api::Api::new(
+        config.executable().to_string(),
+        crate::restic_verbosity(),
+        crate::restic_progress_fps(),
+        config.retry().clone(),
+    );
+
+    let m = crate::resolve_selection(args.selected_locations(), config)?;
+    let mut location_names: Vec<_> = m.keys().collect();
+    location_names.sort();
+
+    let mut rows = Vec::new();
+    let mut total = Estimate::default();
+    let mut failures = Vec::new();
+    for location_name in location_names {
+        let location = &config.locations()[location_name];
+        let mut options = crate::get_backup_options(location_name, config);
+        let sources = match crate::location_sources(location_name, location) {
+            Ok(Some(sources)) => sources,
+            Ok(None) => continue,
+            Err(failure) => {
+                print_log!(Level::ERROR, "{failure}");
+                failures.push(failure.status);
+                continue;
+            }
+        };
+        let _excludes = if options.exclude_gitignored() {
+            match crate::gitignore_excludes(location_name, &sources, &mut options) {
+                Ok(excludes) => Some(excludes),
+                Err(failure) => {
+                    print_log!(Level::ERROR, "{failure}");
+                    failures.push(failure.status);
+                    continue;
+                }
+            }
+        } else {
+            None
+        };
+
+        let tag = crate::get_tag(location_name);
+        let mut repo_names: Vec<_> = m[location_name].iter().collect();
+        repo_names.sort();
+        for repo_name in repo_names {
+            let Some(repo) = crate::resolve_location_repository(repo_name, location_name, config)
+            else {
+                print_log!(
+                    Level::WARN,
+                    "Location {location_name} refers to an undefined repository {repo_name}."
+                );
+                continue;
+            };
+            print_log!(Level::INFO, "Estimate {location_name}@{repo_name} ...");
+            let restic_args =
+                crate::location_args(location_name, config, ExtraArgs::backup, args.restic_args());
+            match api.estimate(&repo, &sources, &tag, &options, &restic_args) {
+                Ok(estimate) => {
+                    rows.push(row(location_name.as_str(), repo_name.as_str(), &estimate));
+                    total.merge(estimate);
+                }
+                Err(err) => {
+                    failures.push(api.exit_status(&err));
+                    print_log!(
+                        Level::ERROR,
+                        "{location_name}@{repo_name}: {}: {err}",
+                        style::paint_stderr("FAILED", Color::Red)
+                    );
+                }
+            }
+        }
+    }
+
+    if rows.len() > 1 {
+        rows.push(row("TOTAL", "", &total));
+    }
+    print_table(&rows);
+
+    if !failures.is_empty() {
+        return Err(exit::Error::new(
+            Status::combine(failures.iter().copied()),
+            format!("{} estimate(s) failed.", failures.len()),
+        )
+        .into());
+    }
+    Ok(())
+}
+
+fn row(location: &str, repo: &str, estimate: &Estimate) -> [String; 7] {
+    [
+        location.to_string(),
+        repo.to_string(),
+        estimate.files_new.to_string(),
+        estimate.files_changed.to_string(),
+        estimate.files_unmodified.to_string(),
+        format_size(estimate.bytes_processed),
+        format_size(estimate.bytes_added),
+    ]
+}
+
+/// Prints the rows below the header. Columns of numbers are right-aligned.
+fn print_table(rows: &[[String; 7]]) {
+    if rows.is_empty() {
+        return;
+    }
+    let mut widths = HEADER.map(str::len);
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+    let line = |cells: &[String; 7]| {
+        let cells: Vec<_> = cells
+            .iter()
+            .zip(widths)
+            .enumerate()
+            .map(|(i, (cell, width))| match i {
+                0 | 1 => format!("{cell:width$}"),
+                _ => format!("{cell:>width$}"),
+            })
+            .collect();
+        cells.join("  ").trim_end().to_string()
+    };
+
+    print_log!(Level::INFO, "");
+    print_log!(Level::INFO, "{}", line(&HEADER.map(String::from)));
+    for row in rows {
+        print_log!(Level::INFO, "{}", line(row));
+    }
+}
@@ -0,0 +1,156 @@
+use crate::config::Name;
+use crate::redact;
+use std::fmt::Display;
+use std::time::Duration;
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Operation {
+    Backup,
+    Forget,
+    Check,
+    Copy,
+    /// Repository maintenance: removal of stale locks, check, prune and cache
+    /// cleanup.
+    Maintenance,
+    /// Comparison of the age of the latest snapshots with the max-age setting.
+    Status,
+}
+
+impl Operation {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Operation::Backup => "backup",
+            Operation::Forget => "forget",
+            Operation::Check => "check",
+            Operation::Copy => "copy",
+            Operation::Maintenance => "maintenance",
+            Operation::Status => "status",
+        }
+    }
+
+    /// The capitalized name for use in messages.
+    pub fn label(&self) -> &'static str {
+        match self {
+            Operation::Backup => "Backup",
+            Operation::Forget => "Forget",
+            Operation::Check => "Check",
+            Operation::Copy => "Copy",
+            Operation::Maintenance => "Maintenance",
+            Operation::Status => "Status",
+        }
+    }
+}
+
+impl Display for Operation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum EventKind {
+    Started,
+    Succeeded,
+    Failed,
+}
+
+impl EventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EventKind::Started => "started",
+            EventKind::Succeeded => "succeeded",
+            EventKind::Failed => "failed",
+        }
+    }
+}
+
+/// Something that happened during a run.
+///
+/// Events without location refer to the whole run, events without repository
+/// refer to all repositories of a location.
+#[derive(Clone, Debug)]
+pub struct Event {
+    pub kind: EventKind,
+    pub operation: Operation,
+    pub location: Option<Name>,
+    pub repo: Option<Name>,
+    pub duration: Option<Duration>,
+    pub bytes_added: Option<u64>,
+    pub error: Option<String>,
+}
+
+impl Event {
+    pub fn started(operation: Operation, location: Option<&Name>, repo: Option<&Name>) -> Self {
+        Self {
+            kind: EventKind::Started,
+            operation,
+            location: location.cloned(),
+            repo: repo.cloned(),
+            duration: None,
+            bytes_added: None,
+            error: None,
+        }
+    }
+
+    /// Creates a success event if `error` is `None` and a failure event
+    /// otherwise.
+    pub fn finished(
+        operation: Operation,
+        location: Option<&Name>,
+        repo: Option<&Name>,
+        duration: Duration,
+        error: Option<String>,
+    ) -> Self {
+        Self {
+            kind: match error {
+                Some(_) => EventKind::Failed,
+                None => EventKind::Succeeded,
+            },
+            operation,
+            location: location.cloned(),
+            repo: repo.cloned(),
+            duration: Some(duration),
+            bytes_added: None,
+            error: error.map(|e| redact::redact(&e).into_owned()),
+        }
+    }
+
+    pub fn with_bytes_added(mut self, bytes_added: Option<u64>) -> Self {
+        self.bytes_added = bytes_added;
+        self
+    }
+
+    /// The event type, e.g. `backup.failed`.
+    pub fn name(&self) -> String {
+        format!("{}.{}", self.operation, self.kind.as_str())
+    }
+
+    /// The event as JSON object as used by the webhook and the JSON output.
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "event": self.name(),
+            "operation": self.operation.as_str(),
+            "status": self.kind.as_str(),
+            "location": self.location.as_ref().map(|l| l.as_str()),
+            "repo": self.repo.as_ref().map(|r| r.as_str()),
+            "duration": self.duration.map(|d| d.as_secs_f64()),
+            "bytes_added": self.bytes_added,
+            "error": self.error,
+        })
+    }
+
+    /// Returns the value of a template placeholder, e.g. `location`.
+    pub fn var(&self, name: &str) -> Option<String> {
+        match name {
+            "event" => Some(self.name()),
+            "operation" => Some(self.operation.to_string()),
+            "status" => Some(self.kind.as_str().to_string()),
+            "location" => self.location.as_ref().map(Name::to_string),
+            "repo" => self.repo.as_ref().map(Name::to_string),
+            "duration" => self.duration.map(|d| format!("{:.0}", d.as_secs_f64())),
+            "bytes_added" => self.bytes_added.map(|b| b.to_string()),
+            "error" => self.error.clone(),
+            _ => None,
+        }
+    }
+}
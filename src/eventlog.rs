@@ -0,0 +1,92 @@
+use crate::redact;
+use std::fmt::{Debug, Write as _};
+use std::ptr::{null, null_mut};
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+use windows_sys::Win32::Foundation::HANDLE;
+use windows_sys::Win32::System::EventLog::{
+    DeregisterEventSource, EVENTLOG_ERROR_TYPE, EVENTLOG_INFORMATION_TYPE, EVENTLOG_WARNING_TYPE,
+    RegisterEventSourceW, ReportEventW,
+};
+
+const SOURCE_NAME: &str = "aresticrat";
+
+/// A tracing layer that reports events to the Windows Event Log.
+///
+/// Events are written to the application log with the source name
+/// "aresticrat". Errors and warnings keep their severity, all other levels are
+/// reported as information.
+pub struct EventLogLayer {
+    handle: HANDLE,
+}
+
+// Event log handles may be used from any thread.
+unsafe impl Send for EventLogLayer {}
+unsafe impl Sync for EventLogLayer {}
+
+impl EventLogLayer {
+    pub fn new() -> Result<Self, std::io::Error> {
+        let source = to_wide(SOURCE_NAME);
+        let handle = unsafe { RegisterEventSourceW(null(), source.as_ptr()) };
+        if handle.is_null() {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(Self { handle })
+    }
+}
+
+impl Drop for EventLogLayer {
+    fn drop(&mut self) {
+        unsafe { DeregisterEventSource(self.handle) };
+    }
+}
+
+impl<S: Subscriber> Layer<S> for EventLogLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let kind = match *event.metadata().level() {
+            Level::ERROR => EVENTLOG_ERROR_TYPE,
+            Level::WARN => EVENTLOG_WARNING_TYPE,
+            _ => EVENTLOG_INFORMATION_TYPE,
+        };
+
+        let mut visitor = MessageVisitor::default();
+        event.record(&mut visitor);
+        let message = to_wide(&redact::redact(&(visitor.message + &visitor.fields)));
+        let strings = [message.as_ptr()];
+        unsafe {
+            ReportEventW(
+                self.handle,
+                kind,
+                0,
+                0,
+                null_mut(),
+                strings.len() as u16,
+                0,
+                strings.as_ptr(),
+                null(),
+            )
+        };
+    }
+}
+
+/// Collects the message and the remaining fields of an event.
+#[derive(Default)]
+struct MessageVisitor {
+    message: String,
+    fields: String,
+}
+
+impl Visit for MessageVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.message, "{value:?}");
+        } else {
+            let _ = write!(self.fields, " {}={value:?}", field.name());
+        }
+    }
+}
+
+fn to_wide(s: &str) -> Vec<u16> {
+    s.encode_utf16().chain(std::iter::once(0)).collect()
+}
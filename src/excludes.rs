@@ -0,0 +1,301 @@
+use crate::cli::ExplainExcludesArgs;
+use crate::config::{BackupOptions, Config, Size, Source};
+use crate::gitignore;
+use crate::print_log;
+use crate::style::{self, Color};
+use anyhow::{Result, anyhow, bail};
+use globset::{GlobBuilder, GlobMatcher};
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use tracing::Level;
+
+/// The signature that marks a directory as cache, see
+/// <https://bford.info/cachedir/>.
+const CACHEDIR_SIGNATURE: &[u8] = b"Signature: 8a477f597d28d172789f06886806bc55";
+
+/// Prints for every path whether a backup of the location would include it
+/// and which rule excludes it. Directories are walked if requested, but
+/// excluded directories are not entered, just like restic does.
+pub fn explain(config: &Config, args: &ExplainExcludesArgs) -> Result<()> {
+    let location_name = args.location();
+    let Some(location) = config.locations().get(location_name) else {
+        bail!("Location {location_name} is not defined.");
+    };
+    let options = crate::get_backup_options(location_name, config);
+
+    let location_paths: Vec<_> = match crate::location_sources(location_name, location) {
+        Ok(sources) => sources
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|source| match source {
+                Source::Paths(paths) => Some(paths),
+                Source::Command { .. } => None,
+            })
+            .flatten()
+            .collect(),
+        Err(failure) => bail!(failure.to_string()),
+    };
+    let paths = if args.paths().is_empty() {
+        &location_paths
+    } else {
+        args.paths()
+    };
+    if paths.is_empty() {
+        bail!("Location {location_name} has no paths to explain.");
+    }
+
+    let rules = Rules::new(&options, &location_paths)?;
+    for path in paths {
+        let path = std::path::absolute(path)?;
+        explain_path(&rules, &path, args.recursive());
+    }
+    Ok(())
+}
+
+fn explain_path(rules: &Rules, path: &Path, recursive: bool) {
+    match rules.excluded_by(path) {
+        Some(rule) => {
+            print_log!(
+                Level::INFO,
+                "{} {} ({rule})",
+                style::paint("EXCLUDED", Color::Red),
+                path.display()
+            );
+            return;
+        }
+        None => print_log!(
+            Level::INFO,
+            "{} {}",
+            style::paint("INCLUDED", Color::Green),
+            path.display()
+        ),
+    }
+    if recursive && path.is_dir() {
+        let Ok(entries) = std::fs::read_dir(path) else {
+            return;
+        };
+        let mut children: Vec<_> = entries.flatten().map(|e| e.path()).collect();
+        children.sort();
+        for child in children {
+            explain_path(rules, &child, recursive);
+        }
+    }
+}
+
+/// A pattern of an exclude option, described by its origin, e.g.
+/// `exclude-file /etc/excludes:3`.
+struct Pattern {
+    matcher: GlobMatcher,
+    negated: bool,
+    origin: String,
+    text: String,
+}
+
+/// The exclude rules of the backup options, checked in the order restic does.
+struct Rules {
+    patterns: Vec<Pattern>,
+    ipatterns: Vec<Pattern>,
+    if_present: Vec<(String, Option<String>)>,
+    caches: bool,
+    larger_than: Option<(u64, String)>,
+    gitignored: Option<HashSet<PathBuf>>,
+    /// The absolute paths of the location.
+    roots: Vec<PathBuf>,
+}
+
+impl Rules {
+    fn new(options: &BackupOptions, location_paths: &[PathBuf]) -> Result<Self> {
+        let mut patterns = Vec::new();
+        for text in options.exclude() {
+            patterns.push(Pattern::new(text, false, "exclude".to_string())?);
+        }
+        for file in options.exclude_file() {
+            patterns.extend(Pattern::read(file, false, "exclude-file")?);
+        }
+        let mut ipatterns = Vec::new();
+        for text in options.iexclude() {
+            ipatterns.push(Pattern::new(text, true, "iexclude".to_string())?);
+        }
+        for file in options.iexclude_file() {
+            ipatterns.extend(Pattern::read(file, true, "iexclude-file")?);
+        }
+        let if_present = options
+            .exclude_if_present()
+            .iter()
+            .map(|spec| match spec.split_once(':') {
+                Some((name, header)) => (name.to_string(), Some(header.to_string())),
+                None => (spec.clone(), None),
+            })
+            .collect();
+        let larger_than = options
+            .exclude_larger_than()
+            .map(|size| {
+                Size::parse(size)
+                    .map(|bytes| (bytes.into(), size.to_string()))
+                    .map_err(|_| anyhow!("Invalid size {size:?} of exclude-larger-than."))
+            })
+            .transpose()?;
+        let gitignored = options.exclude_gitignored().then(|| {
+            gitignore::ignored_paths(location_paths)
+                .into_iter()
+                .collect()
+        });
+        Ok(Rules {
+            patterns,
+            ipatterns,
+            if_present,
+            caches: options.exclude_caches(),
+            larger_than,
+            gitignored,
+            roots: location_paths
+                .iter()
+                .filter_map(|p| std::path::absolute(p).ok())
+                .collect(),
+        })
+    }
+
+    /// The rule that excludes the path, if any.
+    fn excluded_by(&self, path: &Path) -> Option<String> {
+        for patterns in [&self.patterns, &self.ipatterns] {
+            if let Some(pattern) = last_match(patterns, path) {
+                if !pattern.negated {
+                    return Some(format!("{} {:?}", pattern.origin, pattern.text));
+                }
+            }
+        }
+
+        // Directory rules exclude the directory itself and thereby everything
+        // below it. restic only looks into the directories it backs up.
+        let dirs = path
+            .ancestors()
+            .filter(|dir| self.roots.iter().any(|root| dir.starts_with(root)));
+        for dir in dirs {
+            if !dir.is_dir() {
+                continue;
+            }
+            for (name, header) in &self.if_present {
+                if has_file(&dir.join(name), header.as_deref().map(str::as_bytes)) {
+                    return Some(in_dir(dir, path, format!("exclude-if-present {name:?}")));
+                }
+            }
+            if self.caches && has_file(&dir.join("CACHEDIR.TAG"), Some(CACHEDIR_SIGNATURE)) {
+                return Some(in_dir(dir, path, "exclude-caches".to_string()));
+            }
+        }
+
+        if let Some((limit, size)) = &self.larger_than {
+            if path.is_file() && path.metadata().is_ok_and(|m| m.len() > *limit) {
+                return Some(format!("exclude-larger-than {size}"));
+            }
+        }
+        if let Some(ignored) = &self.gitignored {
+            if let Some(dir) = path.ancestors().find(|p| ignored.contains(*p)) {
+                return Some(in_dir(dir, path, "exclude-gitignored".to_string()));
+            }
+        }
+        None
+    }
+}
+
+impl Pattern {
+    /// Patterns that do not start with a separator match at any depth, and a
+    /// pattern that matches a directory matches everything below it.
+    fn new(text: &str, case_insensitive: bool, origin: String) -> Result<Self> {
+        let (negated, glob) = match text.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, text),
+        };
+        let glob = if glob.starts_with('/') {
+            glob.to_string()
+        } else {
+            format!("**/{glob}")
+        };
+        let matcher = GlobBuilder::new(glob.trim_end_matches('/'))
+            .literal_separator(true)
+            .backslash_escape(cfg!(unix))
+            .case_insensitive(case_insensitive)
+            .build()
+            .map_err(|e| anyhow!("Invalid pattern {text:?} ({origin}): {e}"))?
+            .compile_matcher();
+        Ok(Pattern {
+            matcher,
+            negated,
+            origin,
+            text: text.to_string(),
+        })
+    }
+
+    /// Reads the patterns of an exclude file. Like restic, empty lines and
+    /// comments are skipped and environment variables are expanded.
+    fn read(file: &Path, case_insensitive: bool, option: &str) -> Result<Vec<Self>> {
+        let content = std::fs::read_to_string(file)
+            .map_err(|e| anyhow!("Failed to read {}: {e}", file.display()))?;
+        let mut patterns = Vec::new();
+        for (i, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let origin = format!("{option} {}:{}", file.display(), i + 1);
+            patterns.push(Pattern::new(&expand_env(line), case_insensitive, origin)?);
+        }
+        Ok(patterns)
+    }
+
+    fn matches(&self, path: &Path) -> bool {
+        path.ancestors().any(|p| self.matcher.is_match(p))
+    }
+}
+
+/// The last pattern that matches the path. A later negated pattern
+/// re-includes a path that an earlier one excludes.
+fn last_match<'a>(patterns: &'a [Pattern], path: &Path) -> Option<&'a Pattern> {
+    patterns.iter().rev().find(|p| p.matches(path))
+}
+
+fn in_dir(dir: &Path, path: &Path, rule: String) -> String {
+    if dir == path {
+        rule
+    } else {
+        format!("{rule} in {}", dir.display())
+    }
+}
+
+/// Whether the file exists and starts with the header, if any.
+fn has_file(path: &Path, header: Option<&[u8]>) -> bool {
+    let Some(header) = header else {
+        return path.exists();
+    };
+    let mut buf = vec![0; header.len()];
+    File::open(path)
+        .and_then(|mut f| f.read_exact(&mut buf))
+        .is_ok_and(|()| buf == header)
+}
+
+/// Expands `$VAR` and `${VAR}`. Undefined variables expand to nothing.
+fn expand_env(line: &str) -> String {
+    let mut result = String::with_capacity(line.len());
+    let mut rest = line;
+    while let Some(i) = rest.find('$') {
+        result.push_str(&rest[..i]);
+        rest = &rest[i + 1..];
+        let (name, after) = match rest.strip_prefix('{') {
+            Some(braced) => match braced.find('}') {
+                Some(end) => (&braced[..end], &braced[end + 1..]),
+                None => ("", braced),
+            },
+            None => {
+                let end = rest
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                    .unwrap_or(rest.len());
+                (&rest[..end], &rest[end..])
+            }
+        };
+        result.push_str(&std::env::var(name).unwrap_or_default());
+        rest = after;
+    }
+    result.push_str(rest);
+    result
+}
@@ -0,0 +1,162 @@
+use std::fmt::Display;
+use thiserror::Error;
+
+/// The exit statuses of aresticrat. Every class of failures has its own
+/// status, so that scripts and monitoring can tell them apart.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Status {
+    Success,
+    Failure,
+    Usage,
+    Incomplete,
+    Config,
+    Unavailable,
+    InvalidKey,
+    Hook,
+    Outdated,
+    Overlap,
+    Interrupted,
+}
+
+impl Status {
+    const ALL: [Status; 11] = [
+        Status::Success,
+        Status::Failure,
+        Status::Usage,
+        Status::Incomplete,
+        Status::Config,
+        Status::Unavailable,
+        Status::InvalidKey,
+        Status::Hook,
+        Status::Outdated,
+        Status::Overlap,
+        Status::Interrupted,
+    ];
+
+    pub fn code(self) -> i32 {
+        match self {
+            Status::Success => 0,
+            Status::Failure => 1,
+            // Chosen by clap for invalid arguments.
+            Status::Usage => 2,
+            Status::Incomplete => 3,
+            Status::Config => 4,
+            Status::Unavailable => 5,
+            Status::InvalidKey => 6,
+            Status::Hook => 7,
+            Status::Outdated => 8,
+            // EX_TEMPFAIL of sysexits.h.
+            Status::Overlap => 75,
+            // The status of shells for processes terminated by SIGINT.
+            Status::Interrupted => 130,
+        }
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            Status::Success => "All operations succeeded.",
+            Status::Failure => "An operation failed for another reason.",
+            Status::Usage => "The command line arguments are invalid.",
+            Status::Incomplete => "A backup is incomplete, because some files could not be read.",
+            Status::Config => "The configuration is invalid.",
+            Status::Unavailable => "A repository does not exist or cannot be reached.",
+            Status::InvalidKey => "The password of a repository is wrong.",
+            Status::Hook => "A hook could not be run.",
+            Status::Outdated => "A location has no snapshot or the latest one is too old.",
+            Status::Overlap => "Another run holds a lock.",
+            Status::Interrupted => "The run has been interrupted by a signal.",
+        }
+    }
+
+    /// The common status of several failures. Failures of different classes
+    /// result in the generic failure status.
+    pub fn combine(statuses: impl IntoIterator<Item = Status>) -> Status {
+        let mut statuses = statuses.into_iter();
+        let Some(first) = statuses.next() else {
+            return Status::Success;
+        };
+        if statuses.all(|s| s == first) {
+            first
+        } else {
+            Status::Failure
+        }
+    }
+}
+
+/// The description of all exit statuses for the help text.
+pub fn help() -> String {
+    let mut help = "Exit Status:\n".to_string();
+    for status in Status::ALL {
+        help.push_str(&format!(
+            "  {:>3}  {}\n",
+            status.code(),
+            status.description()
+        ));
+    }
+    help.push_str("Failures of different classes result in exit status 1.");
+    help
+}
+
+/// A failed item of a run, e.g. the backup of a location to a repository.
+#[derive(Clone, Debug)]
+pub struct Failure {
+    pub status: Status,
+    pub message: String,
+    /// The failed command including its arguments, if any.
+    pub command: Option<String>,
+    pub exit_code: Option<i32>,
+    /// The last lines of the error output of the failed command.
+    pub stderr: Option<String>,
+}
+
+impl Failure {
+    pub fn new(status: Status, message: String) -> Self {
+        Self {
+            status,
+            message,
+            command: None,
+            exit_code: None,
+            stderr: None,
+        }
+    }
+
+    /// Adds the details of the command that caused the failure.
+    pub fn with_command(
+        mut self,
+        command: impl Into<String>,
+        exit_code: Option<i32>,
+        stderr: Option<String>,
+    ) -> Self {
+        self.command = Some(command.into());
+        self.exit_code = exit_code;
+        self.stderr = stderr;
+        self
+    }
+}
+
+impl Display for Failure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+/// An error that ends aresticrat with a specific exit status.
+#[derive(Debug, Error)]
+#[error("{message}")]
+pub struct Error {
+    status: Status,
+    message: String,
+}
+
+impl Error {
+    pub fn new(status: Status, message: impl Display) -> Self {
+        Self {
+            status,
+            message: message.to_string(),
+        }
+    }
+
+    pub fn status(&self) -> Status {
+        self.status
+    }
+}
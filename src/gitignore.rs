@@ -0,0 +1,132 @@
+use crate::print_log;
+use ignore::Match;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use tracing::Level;
+
+/// The paths below the given ones that are ignored by .gitignore files. The
+/// paths are walked like git does: the rules of a .gitignore file apply to its
+/// directory and below, deeper files take precedence and ignored directories
+/// are not entered.
+pub fn ignored_paths(paths: &[PathBuf]) -> Vec<PathBuf> {
+    let mut ignored = Vec::new();
+    for path in paths {
+        let Ok(path) = std::path::absolute(path) else {
+            continue;
+        };
+        if path.is_dir() {
+            walk(&path, &mut Vec::new(), &mut ignored);
+        }
+    }
+    ignored
+}
+
+fn walk(dir: &Path, rules: &mut Vec<Gitignore>, ignored: &mut Vec<PathBuf>) {
+    let gitignore = dir.join(".gitignore");
+    let has_rules = gitignore.is_file();
+    if has_rules {
+        let mut builder = GitignoreBuilder::new(dir);
+        if let Some(err) = builder.add(&gitignore) {
+            print_log!(
+                Level::DEBUG,
+                "Failed to read {}: {err}",
+                gitignore.display()
+            );
+        }
+        match builder.build() {
+            Ok(matcher) => rules.push(matcher),
+            Err(err) => {
+                print_log!(
+                    Level::DEBUG,
+                    "Failed to read {}: {err}",
+                    gitignore.display()
+                );
+                rules.push(Gitignore::empty());
+            }
+        }
+    }
+
+    // Unreadable directories are left to restic, which reports them.
+    if let Ok(entries) = std::fs::read_dir(dir) {
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let is_dir = entry.file_type().is_ok_and(|t| t.is_dir());
+            let matched = rules
+                .iter()
+                .rev()
+                .map(|rules| rules.matched(&path, is_dir))
+                .find(|m| !m.is_none());
+            match matched {
+                Some(Match::Ignore(_)) => ignored.push(path),
+                _ if is_dir && entry.file_name() != ".git" => walk(&path, rules, ignored),
+                _ => {}
+            }
+        }
+    }
+
+    if has_rules {
+        rules.pop();
+    }
+}
+
+/// Exclude patterns that match exactly the given paths. They are written to a
+/// temporary restic exclude file, which is removed when the value is dropped.
+/// restic expands environment variables in exclude files, so patterns that
+/// contain `$` are kept apart and must be passed as arguments.
+pub struct Excludes {
+    file: PathBuf,
+    patterns: Vec<String>,
+}
+
+impl Excludes {
+    pub fn write(name: &str, paths: &[PathBuf]) -> std::io::Result<Self> {
+        let file = std::env::temp_dir().join(format!(
+            "aresticrat-{}-{name}-excludes.txt",
+            std::process::id()
+        ));
+        let mut excludes = Excludes {
+            file,
+            patterns: Vec::new(),
+        };
+        let mut writer = BufWriter::new(File::create(&excludes.file)?);
+        for path in paths {
+            let pattern = pattern(path);
+            if pattern.contains('$') {
+                excludes.patterns.push(pattern);
+            } else {
+                writeln!(writer, "{pattern}")?;
+            }
+        }
+        writer.flush()?;
+        Ok(excludes)
+    }
+
+    pub fn file(&self) -> &Path {
+        &self.file
+    }
+
+    pub fn patterns(&self) -> &[String] {
+        &self.patterns
+    }
+}
+
+impl Drop for Excludes {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.file);
+    }
+}
+
+/// An exclude pattern that matches the path literally.
+fn pattern(path: &Path) -> String {
+    let path = path.to_string_lossy();
+    let mut pattern = String::with_capacity(path.len());
+    for c in path.chars() {
+        if matches!(c, '*' | '?' | '[' | '\\') && cfg!(unix) {
+            pattern.push('\\');
+        }
+        pattern.push(c);
+    }
+    pattern
+}
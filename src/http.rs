@@ -0,0 +1,19 @@
+use base64::Engine;
+use std::time::Duration;
+
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Creates an HTTP client with the default settings of this application.
+pub fn agent() -> ureq::Agent {
+    ureq::Agent::config_builder()
+        .timeout_global(Some(TIMEOUT))
+        .build()
+        .into()
+}
+
+/// Returns the value of an `Authorization` header for HTTP basic auth.
+pub fn basic_auth(username: &str, password: &str) -> String {
+    let credentials =
+        base64::engine::general_purpose::STANDARD.encode(format!("{username}:{password}"));
+    format!("Basic {credentials}")
+}
@@ -0,0 +1,96 @@
+use crate::print_log;
+use std::process::Child;
+use tracing::Level;
+
+/// Prevents the system from going to sleep until the value is dropped, e.g. so
+/// that a laptop does not suspend in the middle of an upload.
+///
+/// On Linux, the inhibitor lock is held by `systemd-inhibit`, which runs `cat`
+/// until its input is closed. On macOS, `caffeinate` is used. Both end with
+/// aresticrat, even if it is killed. On Windows, the execution state of the
+/// current thread is set, so the value must be dropped on the same thread.
+pub struct SleepInhibitor {
+    child: Option<Child>,
+}
+
+impl SleepInhibitor {
+    /// Prevents sleep for the given reason. Failures are logged, but do not
+    /// prevent the run.
+    pub fn acquire(why: &str) -> Option<Self> {
+        match Self::start(why) {
+            Ok(inhibitor) => {
+                print_log!(Level::DEBUG, "Prevent system sleep: {why}.");
+                Some(inhibitor)
+            }
+            Err(err) => {
+                print_log!(Level::WARN, "Failed to prevent system sleep: {err}");
+                None
+            }
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    fn start(why: &str) -> std::io::Result<Self> {
+        use std::process::{Command, Stdio};
+
+        let child = Command::new("systemd-inhibit")
+            .args(["--what=sleep:idle", "--who=aresticrat", "--mode=block"])
+            .arg(format!("--why={why}"))
+            .arg("cat")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .spawn()?;
+        Ok(SleepInhibitor { child: Some(child) })
+    }
+
+    #[cfg(target_os = "macos")]
+    fn start(_why: &str) -> std::io::Result<Self> {
+        use std::process::{Command, Stdio};
+
+        let child = Command::new("caffeinate")
+            .arg("-i")
+            .arg("-w")
+            .arg(std::process::id().to_string())
+            .stdin(Stdio::null())
+            .spawn()?;
+        Ok(SleepInhibitor { child: Some(child) })
+    }
+
+    #[cfg(windows)]
+    fn start(_why: &str) -> std::io::Result<Self> {
+        use windows_sys::Win32::System::Power::{
+            ES_CONTINUOUS, ES_SYSTEM_REQUIRED, SetThreadExecutionState,
+        };
+
+        if unsafe { SetThreadExecutionState(ES_CONTINUOUS | ES_SYSTEM_REQUIRED) } == 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(SleepInhibitor { child: None })
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+    fn start(_why: &str) -> std::io::Result<Self> {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "not supported on this platform",
+        ))
+    }
+}
+
+impl Drop for SleepInhibitor {
+    fn drop(&mut self) {
+        #[cfg(windows)]
+        {
+            use windows_sys::Win32::System::Power::{ES_CONTINUOUS, SetThreadExecutionState};
+            unsafe { SetThreadExecutionState(ES_CONTINUOUS) };
+        }
+        if let Some(mut child) = self.child.take() {
+            // Closing the input ends cat and thereby systemd-inhibit.
+            // caffeinate has no input and is terminated.
+            if child.stdin.take().is_none() {
+                let _ = child.kill();
+            }
+            let _ = child.wait();
+        }
+    }
+}
@@ -0,0 +1,120 @@
+use crate::config::{self, OnOverlap};
+use crate::exit::Status;
+use crate::print_log;
+use crate::signal;
+use fs4::fs_std::FileExt;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use thiserror::Error;
+use tracing::Level;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// An exclusive lock on a lock file. The lock is released when the value is
+/// dropped or the process exits.
+pub struct Lock {
+    _file: File,
+}
+
+impl Lock {
+    /// Locks a configuration file, so that only a single run uses it at a time.
+    pub fn config(settings: &config::Lock, path: &Path) -> Result<Self, Error> {
+        let path = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        Self::acquire(
+            settings,
+            "config",
+            &path.to_string_lossy(),
+            format!("the configuration {}", path.display()),
+        )
+    }
+
+    /// Locks a repository, so that only a single run of any configuration uses
+    /// it at a time.
+    pub fn repository(settings: &config::Lock, name: &str, path: &str) -> Result<Self, Error> {
+        Self::acquire(settings, "repo", path, format!("the repository {name}"))
+    }
+
+    fn acquire(
+        settings: &config::Lock,
+        kind: &str,
+        key: &str,
+        subject: String,
+    ) -> Result<Self, Error> {
+        let dir = settings
+            .dir()
+            .map(Path::to_path_buf)
+            .unwrap_or_else(std::env::temp_dir);
+        std::fs::create_dir_all(&dir).map_err(|e| Error::Io(dir.clone(), e))?;
+        let path = dir.join(format!("aresticrat-{kind}-{:016x}.lock", fnv1a(key)));
+        let file = File::options()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&path)
+            .map_err(|e| Error::Io(path.clone(), e))?;
+
+        let timeout: Duration = settings.timeout().into();
+        let started = Instant::now();
+        let mut waiting = false;
+        loop {
+            if FileExt::try_lock_exclusive(&file).map_err(|e| Error::Io(path.clone(), e))? {
+                return Ok(Self { _file: file });
+            }
+            match settings.on_overlap() {
+                OnOverlap::Skip => return Err(Error::Skipped(subject)),
+                OnOverlap::Fail => return Err(Error::Busy(subject)),
+                OnOverlap::Wait if started.elapsed() >= timeout => {
+                    return Err(Error::Timeout(subject));
+                }
+                OnOverlap::Wait => {
+                    if !waiting {
+                        print_log!(Level::INFO, "Another run uses {subject}. Waiting ...");
+                        waiting = true;
+                    }
+                    if signal::sleep(POLL_INTERVAL) {
+                        return Err(Error::Interrupted(subject));
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// A stable hash of the key, so that lock file names do not depend on the
+/// length or characters of paths.
+fn fnv1a(key: &str) -> u64 {
+    key.bytes().fold(0xcbf29ce484222325, |hash, b| {
+        (hash ^ b as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Failed to lock {0}: {1}")]
+    Io(PathBuf, std::io::Error),
+    #[error("Another run uses {0}.")]
+    Busy(String),
+    #[error("Another run uses {0}. Skip.")]
+    Skipped(String),
+    #[error("Timed out waiting for another run that uses {0}.")]
+    Timeout(String),
+    #[error("Interrupted while waiting for another run that uses {0}.")]
+    Interrupted(String),
+}
+
+impl Error {
+    /// Whether the lock is held by another run.
+    pub fn is_overlap(&self) -> bool {
+        !matches!(self, Error::Io(..) | Error::Interrupted(_))
+    }
+
+    /// The exit status of runs that end because of this error.
+    pub fn status(&self) -> Status {
+        match self {
+            Error::Interrupted(_) => Status::Interrupted,
+            _ if self.is_overlap() => Status::Overlap,
+            _ => Status::Failure,
+        }
+    }
+}
@@ -1,7 +1,15 @@
-use anyhow::Result;
+use anyhow::{Result, bail};
 use clap::Parser as ClapParser;
-use cli::{Args, BackupArgs, Command, ExecArgs, ForgetArgs, VerifyArgs};
-use config::{BackupOptions, CommandSeq, Config, ForgetOptions, LocationRepo, Name};
+use cli::{
+    Args, BackupArgs, CheckArgs, Command, ConfigCommand, ConfigShowArgs, CopyArgs, ExecArgs,
+    ForgetArgs, KeyCommand, KeyStoreArgs, MaintenanceArgs, PinArgs, ScheduleCommand, StatusArgs,
+    VerifyArgs,
+};
+use config::{
+    BackupOptions, CheckOptions, CommandSeq, Config, CopyOptions, ExtraArgs, ForgetOptions, Hook,
+    HookOptions, HookOutput, Location, LocationRepo, MissingPaths, Name, OnHookFailure, Source,
+    StatusOptions,
+};
 use std::{
     collections::{HashMap, HashSet},
     env,
@@ -9,31 +17,100 @@ use std::{
     io::{BufRead, ErrorKind, IsTerminal},
     path::{Path, PathBuf},
     sync::OnceLock,
+    time::Instant,
 };
 use tracing::{Level, level_filters::LevelFilter};
 use tracing_subscriber::{EnvFilter, Layer, layer::SubscriberExt, util::SubscriberInitExt};
 
-use crate::{config::Environment, restic_api::Repository};
+use crate::{
+    config::Environment,
+    event::{Event, Operation},
+    exit::{Failure, Status},
+    metrics::Sample,
+    notify::Notifier,
+    report::{Outcome, Report},
+    restic_api::Repository,
+    style::Color,
+};
 
 mod cli;
+mod conditions;
 mod config;
+mod container;
+mod control;
+mod daemon;
+mod dashboard;
+mod database;
+mod drill;
+mod due;
+mod estimate;
+mod event;
+#[cfg(windows)]
+mod eventlog;
+mod excludes;
+mod exit;
+mod gitignore;
+mod http;
+mod inhibit;
+mod lock;
+mod metrics;
+mod notify;
+mod output;
+mod pipeline;
+mod priority;
+mod probe;
+mod redact;
+mod repo_hooks;
+mod report;
 mod restic_api;
 mod run;
+mod schedule;
+mod secrets;
+mod signal;
+mod space;
+mod state;
+mod style;
+mod template;
+mod verify;
+mod watch;
 
 const ENV_PREFIX: &str = "ARESTICRAT";
 
 const DEFAULT_VERBOSITY: usize = 3;
 static VERBOSITY: OnceLock<usize> = OnceLock::new();
+/// Whether only results are printed, but not the output of commands.
+static SUMMARY_ONLY: OnceLock<bool> = OnceLock::new();
+/// The number of progress updates per second of restic, if any.
+static PROGRESS_FPS: OnceLock<Option<f64>> = OnceLock::new();
+/// The shell that runs shell hooks.
+static HOOK_SHELL: OnceLock<CommandSeq> = OnceLock::new();
+/// Whether the whole run is a dry run (global --dry-run).
+static DRY_RUN: OnceLock<bool> = OnceLock::new();
 
 fn verbosity() -> usize {
     *VERBOSITY.get().expect("Verbosity state not initialized.")
 }
 
+/// Whether the output of restic and hooks is printed while they run.
+fn print_command_output() -> bool {
+    verbosity() >= DEFAULT_VERBOSITY && !SUMMARY_ONLY.get().copied().unwrap_or(false)
+}
+
 fn restic_verbosity() -> usize {
     let v = verbosity();
     v - DEFAULT_VERBOSITY.min(v)
 }
 
+fn restic_progress_fps() -> Option<f64> {
+    *PROGRESS_FPS.get().expect("Progress state not initialized.")
+}
+
+/// Whether hooks are printed instead of run, because the whole run is a dry
+/// run.
+fn dry_run() -> bool {
+    DRY_RUN.get().copied().unwrap_or(false)
+}
+
 fn init_verbosity(quiet: bool, inc: usize) {
     let mut verbosity: usize = DEFAULT_VERBOSITY;
     if quiet {
@@ -49,7 +126,17 @@ fn main() -> Result<()> {
         return about();
     }
 
-    init_verbosity(args.quiet(), args.verbose() as usize);
+    output::init(args.output());
+    style::set(args.color());
+    // A JSON document of the verify command replaces the human-readable
+    // output.
+    let json_document = matches!(args.command(), Command::Verify(verify) if verify.json());
+    if output::is_json() || json_document {
+        init_verbosity(true, 0);
+    } else {
+        init_verbosity(args.quiet(), args.verbose() as usize);
+    }
+    let _ = SUMMARY_ONLY.set(args.summary());
 
     if let Some(wd) = args.working_dir() {
         env::set_current_dir(wd)?;
@@ -57,167 +144,2275 @@ fn main() -> Result<()> {
 
     load_env_files(args.env_files())?;
 
-    setup_logger();
+    setup_logger(&args);
 
-    if let Err(err) = handle_command(args) {
-        print_log!(Level::ERROR, "{err}");
-        std::process::exit(1);
+    let status = match handle_command(args) {
+        Ok(()) => Status::Success,
+        Err(err) => {
+            if let Some(err @ lock::Error::Skipped(_)) = err.downcast_ref::<lock::Error>() {
+                print_log!(Level::INFO, "{err}");
+            } else {
+                print_log!(Level::ERROR, "{err}");
+            }
+            error_status(&err)
+        }
+    };
+    if signal::interrupted() {
+        std::process::exit(Status::Interrupted.code());
+    }
+    if status != Status::Success {
+        std::process::exit(status.code());
     }
 
     Ok(())
 }
 
-fn handle_command(args: Args) -> Result<()> {
-    let config = config::Config::new(args.config_file())?;
+fn handle_command(mut args: Args) -> Result<()> {
+    let config = config::Config::new(args.config_files(), args.settings())
+        .map_err(|e| exit::Error::new(Status::Config, e))?;
+
+    redact::init(config.redact().env());
+    for secret in config.secrets() {
+        redact::add_secret(secret);
+    }
+
+    let timestamp = chrono::Local::now().format("%Y-%m-%dT%H-%M-%S").to_string();
+    let render_path = |template: &str| {
+        template::render(
+            template,
+            |name| match name {
+                "command" => Some(args.command().name().to_string()),
+                "timestamp" => Some(timestamp.clone()),
+                _ => None,
+            },
+            str::to_string,
+        )
+    };
+    if let Some(log_file) = args.log_file().or(config.log_file()) {
+        let path = render_path(log_file);
+        run::set_log_file(Path::new(&path))
+            .map_err(|e| anyhow::anyhow!("Failed to open log file {path}: {e}"))?;
+    }
+    if let Some(report_file) = args.report().or(config.report()) {
+        report::set_file(PathBuf::from(render_path(report_file)));
+    }
+    let progress = args.progress().unwrap_or(config.progress());
+    let _ = PROGRESS_FPS.set(
+        progress
+            .fps()
+            .map(|fps| config.progress_fps().unwrap_or(fps)),
+    );
+    let _ = HOOK_SHELL.set(config.shell().clone());
+    let _ = DRY_RUN.set(args.dry_run());
+    if args.dry_run() && !args.command_mut().make_dry_run() {
+        print_log!(Level::INFO, "Skipped {} in dry run.", args.command().name());
+        return Ok(());
+    }
+    priority::set(config.priority().clone());
+    state::set_file(match config.state_file() {
+        Some(path) => path.to_path_buf(),
+        None => state::default_file(args.config_file()),
+    });
+
+    if matches!(
+        args.command(),
+        Command::Backup(_)
+            | Command::Forget(_)
+            | Command::Check(_)
+            | Command::Copy(_)
+            | Command::Maintenance(_)
+            | Command::Run(_)
+            | Command::Daemon
+            | Command::Watch(_)
+            | Command::Drill(_)
+    ) || matches!(args.command(), Command::Due(due_args) if due_args.run())
+    {
+        signal::init();
+    }
+
+    // Daemon and watch mode lock single repositories only, so that they can
+    // run alongside one-off commands.
+    let _lock = match args.command() {
+        Command::Backup(_)
+        | Command::Forget(_)
+        | Command::Check(_)
+        | Command::Copy(_)
+        | Command::Maintenance(_)
+        | Command::Run(_) => Some(lock::Lock::config(config.lock(), args.config_file())?),
+        Command::Due(due_args) if due_args.run() => {
+            Some(lock::Lock::config(config.lock(), args.config_file())?)
+        }
+        _ => None,
+    };
 
+    let hook_env =
+        |dry_run: bool| HookEnv::new(args.command().name()).with("ARESTICRAT_DRY_RUN", dry_run);
     match args.command() {
-        Command::Backup(backup_args) => backup(&config, backup_args)?,
+        Command::Backup(backup_args) => run_command(
+            &config,
+            Operation::Backup.label(),
+            hook_env(backup_args.dry_run()),
+            |report| backup(&config, backup_args, report),
+        )?,
+        Command::Check(check_args) => run_command(
+            &config,
+            Operation::Check.label(),
+            hook_env(false),
+            |report| check(&config, check_args, report),
+        )?,
+        Command::Copy(copy_args) => run_command(
+            &config,
+            Operation::Copy.label(),
+            hook_env(false),
+            |report| copy(&config, copy_args, report),
+        )?,
+        Command::Config(ConfigCommand::Show(show_args)) => config_show(&config, show_args)?,
+        Command::Ctl(ctl_command) => control::ctl(&config, ctl_command)?,
+        Command::Daemon => daemon::run(config)?,
+        Command::Drill(drill_args) => drill::run(&config, drill_args)?,
+        Command::Due(due_args) if due_args.run() => run_command(
+            &config,
+            "Pending operations",
+            hook_env(due_args.dry_run()),
+            |report| due::run(&config, due_args, report),
+        )?,
+        Command::Due(due_args) => due::show(&config, due_args)?,
+        Command::Estimate(estimate_args) => estimate::run(&config, estimate_args)?,
         Command::Exec(exec_args) => exec(&config, exec_args)?,
-        Command::Forget(forget_args) => forget(&config, forget_args)?,
+        Command::ExplainExcludes(explain_args) => excludes::explain(&config, explain_args)?,
+        Command::Forget(forget_args) => run_command(
+            &config,
+            Operation::Forget.label(),
+            hook_env(forget_args.dry_run()),
+            |report| forget(&config, forget_args, report),
+        )?,
+        Command::Key(KeyCommand::Store(key_args)) => key_store(&config, key_args)?,
+        Command::Maintenance(maintenance_args) => run_command(
+            &config,
+            Operation::Maintenance.label(),
+            hook_env(false),
+            |report| maintenance(&config, maintenance_args, report),
+        )?,
+        Command::Pin(pin_args) => pin(&config, pin_args)?,
+        Command::Schedule(ScheduleCommand::Install(install_args)) => {
+            schedule::install(&config, &args, install_args)?
+        }
+        Command::Schedule(ScheduleCommand::Show(show_args)) => {
+            schedule::show(&config, &args, show_args)?
+        }
+        Command::Run(run_args) => {
+            let label = match run_args.pipeline() {
+                Some(name) => format!("Pipeline {name}"),
+                None => "Maintenance cycle".to_string(),
+            };
+            run_command(&config, &label, hook_env(run_args.dry_run()), |report| {
+                pipeline::run(&config, run_args, report)
+            })?
+        }
+        Command::Status(status_args) => status(&config, status_args)?,
         Command::Verify(verify_args) => verify(&config, verify_args)?,
+        Command::Watch(watch_args) => watch::run(&config, watch_args)?,
         Command::License => panic!("Command must be handled earlier."),
     }
 
     Ok(())
 }
 
-fn backup(config: &Config, args: &BackupArgs) -> Result<()> {
-    let api = restic_api::Api::new(config.executable().to_string(), restic_verbosity());
+fn backup(config: &Config, args: &BackupArgs, report: &mut Report) -> Result<()> {
+    let notifier = if args.dry_run() {
+        Notifier::default()
+    } else {
+        Notifier::new(config)
+    };
+
+    let started = Instant::now();
+    notifier.notify(&Event::started(Operation::Backup, None, None));
+    let result = backup_locations(config, args, &notifier, report);
+    finish_run(&notifier, report, Operation::Backup, started, &result);
+
+    report_failures(Operation::Backup, &result?)
+}
+
+/// Sends the event that concludes a run of the given operation and adds its
+/// errors to the report.
+fn finish_run(
+    notifier: &Notifier,
+    report: &mut Report,
+    operation: Operation,
+    started: Instant,
+    result: &Result<Vec<Failure>>,
+) {
+    let errors = match result {
+        Ok(failures) => failures.iter().map(ToString::to_string).collect(),
+        Err(err) => vec![err.to_string()],
+    };
+    let error = (!errors.is_empty()).then(|| errors.join("\n"));
+    notifier.notify(&Event::finished(
+        operation,
+        None,
+        None,
+        started.elapsed(),
+        error,
+    ));
+    output::summary(operation, started.elapsed(), &errors);
+    report.add_errors(&errors);
+}
+
+/// The exit status of runs that end because of the given error.
+fn error_status(err: &anyhow::Error) -> Status {
+    if let Some(err) = err.downcast_ref::<lock::Error>() {
+        err.status()
+    } else if let Some(err) = err.downcast_ref::<exit::Error>() {
+        err.status()
+    } else {
+        Status::Failure
+    }
+}
+
+/// Runs operations with a new report, which is printed and written to the
+/// report file afterwards.
+fn with_report(label: &str, run: impl FnOnce(&mut Report) -> Result<()>) -> Result<()> {
+    let mut report = Report::new(label);
+    let result = run(&mut report);
+    repo_hooks::leave_all(result.is_ok());
+    report.finish();
+    result
+}
+
+/// Runs a one-off command with a new report between the global run hooks, which
+/// work like the hooks of a location, but run once per invocation.
+fn run_command(
+    config: &Config,
+    label: &str,
+    env: HookEnv,
+    run: impl FnOnce(&mut Report) -> Result<()>,
+) -> Result<()> {
+    let hooks = config.options().hooks();
+    let hook_error = |name: &str, err: &dyn std::fmt::Display| {
+        anyhow::Error::from(exit::Error::new(
+            Status::Hook,
+            format!("{label} failed: {name} hook failed: {err}"),
+        ))
+    };
+
+    match run_hooks("IF", hooks.r#if(), &env) {
+        Ok(status) if status.success() => {}
+        Ok(status) => {
+            print_log!(Level::INFO, "IF hook failed ({status}). Skip run.");
+            return Ok(());
+        }
+        Err(err) => return Err(hook_error("IF", &err)),
+    }
+
+    let _inhibitor = if config.prevent_sleep() {
+        inhibit::SleepInhibitor::acquire(&format!("{label} in progress"))
+    } else {
+        None
+    };
+    let result = match run_required_hooks("BEFORE", hooks.before(), &env) {
+        Ok(()) => with_report(label, run).and_then(|()| {
+            if signal::interrupted() {
+                return Ok(());
+            }
+            run_required_hooks("AFTER", hooks.after(), &env.concluded(true))
+                .map_err(|err| hook_error("AFTER", &err))
+        }),
+        Err(err) => Err(hook_error("BEFORE", &err)),
+    };
+
+    let env = match &result {
+        Ok(()) => env.concluded(true),
+        Err(err) => env.concluded(false).with("ARESTICRAT_ERROR", err),
+    };
+    if result.is_err() {
+        run_final_hooks("ON-FAILURE", hooks.on_failure(), &env);
+    }
+    run_final_hooks("FINALLY", hooks.finally(), &env);
+    result
+}
+
+/// Fails with a report of all failed items of a run, if any. Runs continue
+/// after failures, so that a single failure does not affect other locations
+/// and repositories.
+fn report_failures(operation: Operation, failures: &[Failure]) -> Result<()> {
+    if failures.is_empty() {
+        return Ok(());
+    }
+    let items: Vec<_> = failures
+        .iter()
+        .map(|failure| format!("- {}", failure.message.replace('\n', "\n  ")))
+        .collect();
+    let status = Status::combine(failures.iter().map(|f| f.status));
+    Err(exit::Error::new(
+        status,
+        format!(
+            "{} finished with {} failure(s):\n{}",
+            operation.label(),
+            failures.len(),
+            items.join("\n")
+        ),
+    )
+    .into())
+}
+
+/// The messages of the failures as error of an event.
+fn join_failures(failures: &[Failure]) -> Option<String> {
+    (!failures.is_empty()).then(|| {
+        failures
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join("\n")
+    })
+}
+
+/// Runs the backup of all selected locations and returns the failed
+/// repository backups.
+fn backup_locations(
+    config: &Config,
+    args: &BackupArgs,
+    notifier: &Notifier,
+    report: &mut Report,
+) -> Result<Vec<Failure>> {
+    let api = restic_api::Api::new(
+        config.executable().to_string(),
+        restic_verbosity(),
+        restic_progress_fps(),
+        config.retry().clone(),
+    );
 
     let m = resolve_selection(args.selected_locations(), config)?;
+    let mut metrics = load_metrics(config)?;
+    let mut errors = Vec::new();
 
-    for (location_name, repo_names) in &m {
+    for (location_name, repo_names) in ordered_locations(&m, config) {
+        if signal::interrupted() {
+            break;
+        }
         let location = &config.locations()[location_name];
         let _span = tracing::info_span!("Backup", location = location_name.as_str()).entered();
 
         let tag = get_tag(location_name);
         let backup_opts = get_backup_options(location_name, config);
 
+        if let Some(reason) = conditions::skip_reason(location) {
+            print_log!(
+                Level::INFO,
+                "Skip backup of location {location_name}, because {reason}."
+            );
+            for (location_name, repo_name) in location_items(location_name, repo_names) {
+                report.add_not_run(
+                    Operation::Backup,
+                    location_name,
+                    repo_name,
+                    Outcome::Skipped,
+                );
+            }
+            continue;
+        }
+        let (repo_names, fresh_repo_names) = due_backups(&api, location_name, repo_names, config);
+        for repo_name in &fresh_repo_names {
+            report.add_not_run(
+                Operation::Backup,
+                Some(location_name),
+                repo_name,
+                Outcome::Skipped,
+            );
+        }
+        if repo_names.is_empty() {
+            continue;
+        }
+        let repo_names = &repo_names;
+
         print_log!(Level::INFO, "Backup location {location_name} ...");
 
-        let if_status = run_hooks("IF", backup_opts.hooks().r#if())?;
-        if !if_status.success() {
-            print_log!(Level::INFO, "IF hook failed. Skip location.");
+        let mut location_errors = run_hooked(
+            Operation::Backup,
+            &format!("location {location_name}"),
+            &location_items(location_name, repo_names),
+            &location.backup_hooks(backup_opts.hooks()),
+            HookEnv::new(Operation::Backup.as_str())
+                .with("ARESTICRAT_LOCATION", location_name)
+                .with("ARESTICRAT_DRY_RUN", args.dry_run()),
+            report,
+            |report, env| {
+                let mut failures = Vec::new();
+                let location_started = Instant::now();
+                notifier.notify(&Event::started(
+                    Operation::Backup,
+                    Some(location_name),
+                    None,
+                ));
+
+                let mut location_errors = Vec::new();
+                let mut successful_repo_names = Vec::new();
+                let mut snapshot_ids = Vec::new();
+                // The paths command runs once, so that all repositories store
+                // the same paths.
+                let mut backup_opts = backup_opts.clone();
+                let mut _excludes = None;
+                let prepared = location_sources(location_name, location).and_then(|sources| {
+                    if let Some(sources) = &sources {
+                        if backup_opts.exclude_gitignored() {
+                            _excludes = Some(gitignore_excludes(
+                                location_name,
+                                sources,
+                                &mut backup_opts,
+                            )?);
+                        }
+                    }
+                    Ok(sources)
+                });
+                let sources = match prepared {
+                    Ok(Some(sources)) => Some(sources),
+                    Ok(None) => {
+                        for repo_name in repo_names {
+                            report.add_not_run(
+                                Operation::Backup,
+                                Some(location_name),
+                                repo_name,
+                                Outcome::Skipped,
+                            );
+                        }
+                        None
+                    }
+                    Err(failure) => {
+                        print_log!(Level::ERROR, "{failure}");
+                        for repo_name in repo_names {
+                            report.add_not_run(
+                                Operation::Backup,
+                                Some(location_name),
+                                repo_name,
+                                Outcome::Fail,
+                            );
+                        }
+                        location_errors.push(failure);
+                        None
+                    }
+                };
+                for repo_name in repo_names {
+                    if signal::interrupted() {
+                        break;
+                    }
+                    let Some(sources) = &sources else {
+                        break;
+                    };
+                    if let Some(repo) =
+                        resolve_location_repository(repo_name, location_name, config)
+                    {
+                        let _repo_lock = match lock_repository(&repo, Operation::Backup, config) {
+                            Ok(Some(lock)) => lock,
+                            Ok(None) => {
+                                report.add_not_run(
+                                    Operation::Backup,
+                                    Some(location_name),
+                                    repo_name,
+                                    Outcome::Skipped,
+                                );
+                                continue;
+                            }
+                            Err(err) => {
+                                report.add_not_run(
+                                    Operation::Backup,
+                                    Some(location_name),
+                                    repo_name,
+                                    Outcome::Fail,
+                                );
+                                location_errors.push(Failure::new(
+                                err.status(),
+                                format!(
+                                    "Backup of location {location_name} to repository {repo_name} failed: {err}"
+                                ),
+                            ));
+                                continue;
+                            }
+                        };
+                        if let Err(message) = check_free_space(&repo, config) {
+                            print_log!(Level::ERROR, "{message}");
+                            report.add_not_run(
+                                Operation::Backup,
+                                Some(location_name),
+                                repo_name,
+                                Outcome::Fail,
+                            );
+                            location_errors.push(Failure::new(
+                                Status::Failure,
+                                format!(
+                                    "Backup of location {location_name} to repository {repo_name} failed: {message}"
+                                ),
+                            ));
+                            continue;
+                        }
+                        print_log!(Level::INFO, "Backup to repository {repo_name} ...");
+                        notifier.notify(&Event::started(
+                            Operation::Backup,
+                            Some(location_name),
+                            Some(repo_name),
+                        ));
+                        let started = Instant::now();
+                        let result = api.backup(
+                            &repo,
+                            sources,
+                            &tag,
+                            &backup_opts,
+                            &location_args(
+                                location_name,
+                                config,
+                                ExtraArgs::backup,
+                                args.restic_args(),
+                            ),
+                            args.dry_run(),
+                        );
+                        let sample = match &result {
+                            Ok(summary) => Sample {
+                                exit_code: summary.exit_code,
+                                success: true,
+                                duration: started.elapsed(),
+                                bytes_added: summary.bytes_added,
+                            },
+                            Err(err) => Sample {
+                                exit_code: err.exit_code(),
+                                success: false,
+                                duration: started.elapsed(),
+                                bytes_added: None,
+                            },
+                        };
+                        if !args.dry_run() {
+                            metrics.record(Operation::Backup, location_name, repo_name, &sample);
+                        }
+                        let error = result.as_ref().err().map(describe_api_error);
+                        if !args.dry_run() {
+                            if let Err(err) =
+                                state::record_backup(location_name, repo_name, error.as_deref())
+                            {
+                                print_log!(Level::WARN, "Failed to update state file: {err}");
+                            }
+                            if let (Ok(summary), Some(repo_config)) =
+                                (&result, config.repos().get(repo_name))
+                            {
+                                if let Err(err) = state::record_snapshots(
+                                    repo_name,
+                                    repo_config.replicate_to(),
+                                    &summary.snapshot_ids,
+                                ) {
+                                    print_log!(Level::WARN, "Failed to update state file: {err}");
+                                }
+                            }
+                        }
+                        let event = Event::finished(
+                            Operation::Backup,
+                            Some(location_name),
+                            Some(repo_name),
+                            sample.duration,
+                            error.clone(),
+                        )
+                        .with_bytes_added(sample.bytes_added);
+                        notifier.notify(&event);
+                        let outcome = match &result {
+                            Ok(summary) if restic_api::is_incomplete(summary) => Outcome::Warn,
+                            Ok(_) => Outcome::Ok,
+                            Err(_) => Outcome::Fail,
+                        };
+                        report.add(&event, outcome);
+                        match result {
+                            Ok(summary) => {
+                                print_log!(Level::INFO, "Backup to repository {repo_name} done.");
+                                if restic_api::is_incomplete(&summary) {
+                                    // The snapshot has been created, so the location
+                                    // is not considered failed.
+                                    print_log!(
+                                        Level::WARN,
+                                        "Some files of location {location_name} could not be read."
+                                    );
+                                    failures.push(Failure::new(
+                                    Status::Incomplete,
+                                    format!(
+                                        "Backup of location {location_name} to repository {repo_name} is incomplete, because some files could not be read."
+                                    ),
+                                ));
+                                }
+                                successful_repo_names.push(repo_name.clone());
+                                snapshot_ids.extend(summary.snapshot_ids.iter().cloned());
+                            }
+                            Err(err) => {
+                                print_log!(
+                                    Level::ERROR,
+                                    "Backup to repository {repo_name} failed: {err}"
+                                );
+                                location_errors.push(api.failure(
+                                &err,
+                                format!(
+                                    "Backup of location {location_name} to repository {repo_name} failed: {}",
+                                    error.unwrap_or_default()
+                                ),
+                            ));
+                            }
+                        }
+                    } else {
+                        print_log!(
+                            Level::WARN,
+                            "Location {location_name} refers to an undefined repository {repo_name}."
+                        )
+                    }
+                }
+
+                notifier.notify(&Event::finished(
+                    Operation::Backup,
+                    Some(location_name),
+                    None,
+                    location_started.elapsed(),
+                    join_failures(&location_errors),
+                ));
+                failures.append(&mut location_errors);
+                env.set("ARESTICRAT_SNAPSHOT_ID", snapshot_ids.join(" "));
+
+                if args.dry_run() && backup_opts.forget() {
+                    print_log!(
+                        Level::INFO,
+                        "Skipped forget for location {location_name} in dry run."
+                    );
+                } else if backup_opts.forget() && !signal::interrupted() {
+                    print_log!(Level::INFO, "Forget for location {location_name} ...");
+                    let mut forget_errors = forget_location(
+                        &api,
+                        location_name,
+                        &successful_repo_names,
+                        config,
+                        &mut metrics,
+                        notifier,
+                        report,
+                        &ForgetArgs::new(Vec::new(), args.dry_run()),
+                    )?;
+                    failures.append(&mut forget_errors);
+                }
+                Ok(failures)
+            },
+        )?;
+        errors.append(&mut location_errors);
+    }
+
+    save_metrics(config, &metrics)?;
+    Ok(errors)
+}
+
+/// The sources of a location backup including the paths listed by its paths
+/// command, if any. Missing paths are handled according to the policy of the
+/// location; `None` means that the location is skipped.
+fn location_sources(
+    location_name: &Name,
+    location: &Location,
+) -> Result<Option<Vec<Source>>, Failure> {
+    let mut sources = location.sources();
+    if let Some(command) = location.paths_command() {
+        let paths = run_paths_command(location_name, location, command)?;
+        for source in &mut sources {
+            if let Source::Paths(static_paths) = source {
+                static_paths.extend(paths.iter().cloned());
+            }
+        }
+    }
+    let Some(policy) = location.missing_paths() else {
+        return Ok(Some(sources));
+    };
+
+    for source in &mut sources {
+        let Source::Paths(paths) = source else {
             continue;
+        };
+        // Dangling symbolic links exist for restic, which stores the link.
+        let (present, missing): (Vec<_>, Vec<_>) = paths
+            .drain(..)
+            .partition(|path| path.symlink_metadata().is_ok());
+        *paths = present;
+        for path in &missing {
+            let path = path.display();
+            match policy {
+                MissingPaths::Fail => {
+                    return Err(Failure::new(
+                        Status::Failure,
+                        format!("Path {path} of location {location_name} does not exist."),
+                    ));
+                }
+                MissingPaths::SkipPath => {
+                    print_log!(Level::WARN, "Path {path} does not exist. Skip path.");
+                }
+                MissingPaths::SkipLocation => {
+                    print_log!(
+                        Level::INFO,
+                        "Path {path} does not exist. Skip location {location_name}."
+                    );
+                    return Ok(None);
+                }
+            }
         }
+        if paths.is_empty() && !missing.is_empty() {
+            print_log!(
+                Level::WARN,
+                "No path of location {location_name} exists. Skip location."
+            );
+            return Ok(None);
+        }
+    }
+    Ok(Some(sources))
+}
 
-        let mut successful_repo_names = Vec::new();
-        for repo_name in repo_names {
-            if let Some(repo) = resolve_repository(repo_name, config) {
-                print_log!(Level::INFO, "Backup to repository {repo_name} ...");
-                match api.backup(&repo, location.paths(), &tag, &backup_opts, args.dry_run()) {
+/// Excludes the paths that are ignored by .gitignore files below the paths
+/// of the sources. The excludes remain valid as long as the returned value.
+fn gitignore_excludes(
+    location_name: &Name,
+    sources: &[Source],
+    options: &mut BackupOptions,
+) -> Result<gitignore::Excludes, Failure> {
+    let paths: Vec<_> = sources
+        .iter()
+        .filter_map(|source| match source {
+            Source::Paths(paths) => Some(paths.as_slice()),
+            Source::Command { .. } => None,
+        })
+        .flatten()
+        .cloned()
+        .collect();
+    let ignored = gitignore::ignored_paths(&paths);
+    print_log!(
+        Level::DEBUG,
+        "Exclude {} paths ignored by .gitignore files.",
+        ignored.len()
+    );
+    let excludes = gitignore::Excludes::write(location_name.as_str(), &ignored).map_err(|e| {
+        Failure::new(
+            Status::Failure,
+            format!("Failed to write the excludes of location {location_name}: {e}"),
+        )
+    })?;
+    options.add_excludes(excludes.patterns(), excludes.file());
+    Ok(excludes)
+}
+
+/// Runs the paths command of a location and returns the listed paths.
+fn run_paths_command(
+    location_name: &Name,
+    location: &Location,
+    command: &CommandSeq,
+) -> Result<Vec<PathBuf>, Failure> {
+    let mut cmd = command.to_command();
+    let failure = |details: String| {
+        Failure::new(
+            Status::Failure,
+            format!("Paths command of location {location_name} failed: {details}"),
+        )
+    };
+    let output = run::run_output(&mut cmd, true).map_err(|e| failure(e.to_string()))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        return Err(failure(output.status.to_string()).with_command(
+            run::describe(&cmd),
+            output.status.code(),
+            Some(stderr).filter(|s| !s.is_empty()),
+        ));
+    }
+
+    let paths: Vec<_> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(PathBuf::from)
+        .collect();
+    if paths.is_empty() && location.paths().is_empty() {
+        return Err(failure("no paths listed".to_string()));
+    }
+    print_log!(Level::DEBUG, "Paths command listed {} paths.", paths.len());
+    Ok(paths)
+}
+
+/// Runs an operation of a location (or repository) between its hooks and
+/// returns its failures:
+/// - IF hooks decide whether the operation runs at all,
+/// - BEFORE hooks must succeed for the operation to run,
+/// - AFTER hooks run after a successful operation,
+/// - ON-FAILURE hooks run if the operation or any of its hooks failed,
+/// - FINALLY hooks always run, even if aresticrat is interrupted.
+///
+/// The items are added to the report if the operation does not run. The
+/// operation may add context for its later hooks to the environment, e.g. the
+/// IDs of created snapshots.
+fn run_hooked(
+    operation: Operation,
+    subject: &str,
+    items: &[(Option<&Name>, &Name)],
+    hooks: &HookOptions,
+    mut env: HookEnv,
+    report: &mut Report,
+    run: impl FnOnce(&mut Report, &mut HookEnv) -> Result<Vec<Failure>>,
+) -> Result<Vec<Failure>> {
+    let label = operation.label();
+    let hook_failure = |name: &str, err: &str| {
+        print_log!(Level::ERROR, "{name} hook failed: {err}");
+        Failure::new(
+            Status::Hook,
+            format!("{label} of {subject} failed: {name} hook failed: {err}"),
+        )
+    };
+    let add_not_run = |report: &mut Report, outcome: Outcome| {
+        for (location_name, repo_name) in items {
+            report.add_not_run(operation, *location_name, repo_name, outcome);
+        }
+    };
+
+    match run_hooks("IF", hooks.r#if(), &env) {
+        Ok(status) if status.success() => {}
+        Ok(status) => {
+            print_log!(Level::INFO, "IF hook failed ({status}). Skip {subject}.");
+            add_not_run(report, Outcome::Skipped);
+            return Ok(Vec::new());
+        }
+        Err(err) => {
+            add_not_run(report, Outcome::Fail);
+            return Ok(vec![hook_failure("IF", &err.to_string())]);
+        }
+    }
+
+    // Incomplete backups have created a snapshot, so they do not fail the
+    // operation.
+    let failed = |failures: &[Failure]| failures.iter().any(|f| f.status != Status::Incomplete);
+
+    let mut failures = Vec::new();
+    if let Err(err) = run_required_hooks("BEFORE", hooks.before(), &env) {
+        add_not_run(report, Outcome::Fail);
+        failures.push(hook_failure("BEFORE", &err));
+    } else {
+        let result = run(report, &mut env);
+        match result {
+            Ok(mut run_failures) => failures.append(&mut run_failures),
+            Err(err) => {
+                let env = env.concluded(false).with("ARESTICRAT_ERROR", &err);
+                run_final_hooks("ON-FAILURE", hooks.on_failure(), &env);
+                run_final_hooks("FINALLY", hooks.finally(), &env);
+                return Err(err);
+            }
+        }
+        if !failed(&failures) && !signal::interrupted() {
+            if let Err(err) = run_required_hooks("AFTER", hooks.after(), &env.concluded(true)) {
+                failures.push(hook_failure("AFTER", &err));
+            }
+        }
+    }
+
+    let env = if failed(&failures) {
+        env.failed(&failures)
+    } else {
+        env.concluded(true)
+    };
+    if failed(&failures) {
+        run_final_hooks("ON-FAILURE", hooks.on_failure(), &env);
+    }
+    run_final_hooks("FINALLY", hooks.finally(), &env);
+    Ok(failures)
+}
+
+/// Splits the repositories into those that are due for a backup of the
+/// location and those whose latest snapshot of the location is younger than
+/// the minimum interval of the location.
+fn due_backups(
+    api: &restic_api::Api,
+    location_name: &Name,
+    repo_names: &HashSet<Name>,
+    config: &Config,
+) -> (HashSet<Name>, Vec<Name>) {
+    let Some(min_interval) = config.locations()[location_name].min_interval() else {
+        return (repo_names.clone(), Vec::new());
+    };
+    let tag = get_tag(location_name);
+    let now = chrono::Utc::now();
+    let mut due = HashSet::new();
+    let mut fresh = Vec::new();
+    for repo_name in repo_names {
+        let Some(repo) = resolve_location_repository(repo_name, location_name, config) else {
+            due.insert(repo_name.clone());
+            continue;
+        };
+        match api.latest_snapshot(&repo, &tag) {
+            Ok(Some(snapshot)) => {
+                let age = (now - snapshot.time.to_utc()).to_std().unwrap_or_default();
+                if age < min_interval.into() {
+                    print_log!(
+                        Level::INFO,
+                        "Skip backup of location {location_name} to repository {repo_name}, because its latest snapshot is only {} old.",
+                        config::Duration::from(age)
+                    );
+                    fresh.push(repo_name.clone());
+                    continue;
+                }
+            }
+            Ok(None) => {}
+            Err(err) => print_log!(
+                Level::WARN,
+                "Failed to determine the latest snapshot of location {location_name} in repository {repo_name}: {err}"
+            ),
+        }
+        due.insert(repo_name.clone());
+    }
+    (due, fresh)
+}
+
+/// The report items of a location for [`run_hooked`].
+fn location_items<'a>(
+    location_name: &'a Name,
+    repo_names: impl IntoIterator<Item = &'a Name>,
+) -> Vec<(Option<&'a Name>, &'a Name)> {
+    repo_names
+        .into_iter()
+        .map(|repo_name| (Some(location_name), repo_name))
+        .collect()
+}
+
+/// Runs hooks that must succeed for the operation to continue, e.g. the
+/// BEFORE hooks of a location. Fails with the exit status of the failed
+/// command or the error that prevented its start.
+fn run_required_hooks(name: &str, hooks: &[Hook], env: &HookEnv) -> Result<(), String> {
+    match run_hooks(name, hooks, env) {
+        Ok(status) if status.success() => Ok(()),
+        Ok(status) => Err(status.to_string()),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+/// Runs hooks that conclude the operation of a location, e.g. the FINALLY
+/// hooks. Failures are reported, but do not fail the run.
+fn run_final_hooks(name: &str, hooks: &[Hook], env: &HookEnv) {
+    match run_hooks(name, hooks, env) {
+        Ok(status) if !status.success() => {
+            print_log!(Level::WARN, "{name} hook failed ({status}).")
+        }
+        Ok(_) => {}
+        Err(err) => print_log!(Level::WARN, "{name} hook failed: {err}"),
+    }
+}
+
+/// Locks a repository for the current run, runs its BEFORE hooks on its first
+/// use and probes whether it is reachable. Returns `None` if the repository is
+/// skipped, because another run uses it or it is unreachable.
+fn lock_repository(
+    repo: &Repository,
+    operation: Operation,
+    config: &Config,
+) -> Result<Option<lock::Lock>, exit::Error> {
+    let lock = match lock::Lock::repository(config.lock(), repo.name.as_str(), &repo.path) {
+        Ok(lock) => lock,
+        Err(err @ lock::Error::Skipped(_)) => {
+            print_log!(Level::INFO, "{err}");
+            return Ok(None);
+        }
+        Err(err) => {
+            print_log!(Level::ERROR, "{err}");
+            return Err(exit::Error::new(err.status(), err));
+        }
+    };
+    if let Some(repo_config) = config.repos().get(&repo.name) {
+        let env = HookEnv::new(operation.as_str()).with("ARESTICRAT_REPO", &repo.name);
+        repo_hooks::enter(&repo.name, repo_config.hooks(), env).map_err(|err| {
+            print_log!(Level::ERROR, "BEFORE hook failed: {err}");
+            exit::Error::new(
+                Status::Hook,
+                format!("BEFORE hook of repository {} failed: {err}", repo.name),
+            )
+        })?;
+    }
+    // The BEFORE hooks may wake up the server.
+    if let Some(probe) = config.repos().get(&repo.name).and_then(|r| r.probe()) {
+        match probe::check(repo.name.as_str(), &repo.path, probe) {
+            Ok(()) => {}
+            Err(err @ probe::Error::Skipped(..)) => {
+                print_log!(Level::WARN, "{err}");
+                return Ok(None);
+            }
+            Err(err @ probe::Error::Interrupted(_)) => {
+                print_log!(Level::ERROR, "{err}");
+                return Err(exit::Error::new(Status::Interrupted, err));
+            }
+            Err(err) => {
+                print_log!(Level::ERROR, "{err}");
+                return Err(exit::Error::new(Status::Unavailable, err));
+            }
+        }
+    }
+    Ok(Some(lock))
+}
+
+/// Fails if a volume to which a backup to the repository writes has less than
+/// the configured free space, so that the backup does not fail halfway.
+fn check_free_space(repo: &Repository, config: &Config) -> Result<(), String> {
+    let Some(repo_config) = config.repos().get(&repo.name) else {
+        return Ok(());
+    };
+    match repo_config.min_free_space().or(config.min_free_space()) {
+        Some(min) => space::check(repo, repo_config.local_path().as_deref(), min),
+        None => Ok(()),
+    }
+}
+
+/// Formats an API error including the error output of restic.
+fn describe_api_error(err: &restic_api::Error) -> String {
+    match err.stderr() {
+        Some(stderr) => format!("{err}\n{stderr}"),
+        None => err.to_string(),
+    }
+}
+
+/// The context of hooks, which is passed to their commands as environment
+/// variables, e.g. `ARESTICRAT_LOCATION`.
+#[derive(Clone, Debug, Default)]
+struct HookEnv {
+    vars: Vec<(&'static str, String)>,
+}
+
+impl HookEnv {
+    /// The context of the hooks of a command or operation, e.g. "backup".
+    fn new(command: &str) -> Self {
+        Self::default().with("ARESTICRAT_COMMAND", command)
+    }
+
+    fn with(mut self, key: &'static str, value: impl ToString) -> Self {
+        self.set(key, value);
+        self
+    }
+
+    /// Sets a variable, replacing its earlier value.
+    fn set(&mut self, key: &'static str, value: impl ToString) {
+        self.vars.retain(|(k, _)| *k != key);
+        self.vars.push((key, value.to_string()));
+    }
+
+    /// The context of hooks that conclude an operation with the given result.
+    fn concluded(&self, success: bool) -> Self {
+        let status = if success { "success" } else { "failure" };
+        self.clone().with("ARESTICRAT_STATUS", status)
+    }
+
+    /// The context of hooks that conclude a failed operation. It describes
+    /// the failures and the first failed command, if any.
+    fn failed(&self, failures: &[Failure]) -> Self {
+        let messages: Vec<_> = failures.iter().map(|f| f.message.as_str()).collect();
+        let mut env = self
+            .concluded(false)
+            .with("ARESTICRAT_ERROR", messages.join("\n"));
+        if let Some(failure) = failures.iter().find(|f| f.command.is_some()) {
+            env.set(
+                "ARESTICRAT_FAILED_COMMAND",
+                failure.command.as_deref().unwrap_or_default(),
+            );
+            if let Some(exit_code) = failure.exit_code {
+                env.set("ARESTICRAT_EXIT_CODE", exit_code);
+            }
+            if let Some(stderr) = &failure.stderr {
+                env.set("ARESTICRAT_STDERR", stderr);
+            }
+        }
+        env
+    }
+}
+
+/// Runs the commands of hooks one after another and returns the status of
+/// the first failed one. Commands whose failures are to be warned about or
+/// ignored do not fail the hooks.
+fn run_hooks(
+    name: &str,
+    hooks: &[Hook],
+    env: &HookEnv,
+) -> Result<std::process::ExitStatus, std::io::Error> {
+    if hooks.is_empty() {
+        return Ok(Default::default());
+    }
+
+    print_log!(Level::INFO, "Running {name} hooks ...");
+    let shell = HOOK_SHELL.get().expect("Hook shell not initialized.");
+    for hook in hooks {
+        let mut cmd = hook.command().to_command(shell);
+        if dry_run() {
+            print_log!(Level::INFO, "Dry run: skip {}", run::describe(&cmd));
+            continue;
+        }
+        if let Some(cwd) = hook.cwd() {
+            cmd.current_dir(cwd);
+        }
+        // The context cannot be overridden by the variables of a hook.
+        cmd.envs(hook.env());
+        cmd.envs(env.vars.iter().map(|(k, v)| (k, v)));
+        let output = hook.output();
+        let options = run::OutputOptions {
+            print: match output {
+                HookOutput::Auto => print_command_output(),
+                HookOutput::Stream => true,
+                HookOutput::Capture | HookOutput::Discard => false,
+            },
+            capture_stdout: false,
+            log: output != HookOutput::Discard,
+            label: hook.name(),
+        };
+        let result = run::run_output_with(&mut cmd, &options, hook.timeout().map(Into::into));
+        let failure = match &result {
+            Ok(output) if output.status.success() => continue,
+            Ok(output) => output.status.to_string(),
+            Err(err) => err.to_string(),
+        };
+        // Captured output is only shown if it explains a failure.
+        if let (Ok(captured), HookOutput::Capture) = (&result, output) {
+            if hook.on_failure() != OnHookFailure::Ignore {
+                let prefix = hook.name().map(|n| format!("[{n}] ")).unwrap_or_default();
+                let text = [&captured.stdout, &captured.stderr].map(|o| String::from_utf8_lossy(o));
+                for line in text.iter().flat_map(|t| t.lines()) {
+                    print_log!(Level::WARN, "{prefix}{line}");
+                }
+            }
+        }
+        let result = result.map(|output| output.status);
+        let program = hook
+            .name()
+            .map(str::to_string)
+            .unwrap_or_else(|| hook.command().program(shell).to_string());
+        match hook.on_failure() {
+            OnHookFailure::Abort => return result,
+            OnHookFailure::Warn => {
+                print_log!(Level::WARN, "{name} hook {program} failed: {failure}")
+            }
+            OnHookFailure::Ignore => {
+                print_log!(Level::DEBUG, "{name} hook {program} failed: {failure}")
+            }
+        }
+    }
+    Ok(Default::default())
+}
+
+fn check(config: &Config, args: &CheckArgs, report: &mut Report) -> Result<()> {
+    let notifier = Notifier::new(config);
+
+    let started = Instant::now();
+    notifier.notify(&Event::started(Operation::Check, None, None));
+    let result = check_locations(config, args, &notifier, report);
+    finish_run(&notifier, report, Operation::Check, started, &result);
+
+    report_failures(Operation::Check, &result?)
+}
+
+/// Checks the repositories of all selected locations and returns the failed
+/// checks. Repositories shared by several locations are checked only once.
+fn check_locations(
+    config: &Config,
+    args: &CheckArgs,
+    notifier: &Notifier,
+    report: &mut Report,
+) -> Result<Vec<Failure>> {
+    let api = restic_api::Api::new(
+        config.executable().to_string(),
+        restic_verbosity(),
+        restic_progress_fps(),
+        config.retry().clone(),
+    );
+
+    let m = resolve_selection(args.selected_locations(), config)?;
+    let mut checked = HashSet::new();
+    let mut errors = Vec::new();
+
+    for (location_name, repo_names) in ordered_locations(&m, config) {
+        if signal::interrupted() {
+            break;
+        }
+        print_log!(Level::INFO, "Check for location {location_name} ...");
+
+        let check_opts = get_check_options(location_name, config);
+        let mut location_errors = run_hooked(
+            Operation::Check,
+            &format!("location {location_name}"),
+            &location_items(location_name, repo_names),
+            check_opts.hooks(),
+            HookEnv::new(Operation::Check.as_str()).with("ARESTICRAT_LOCATION", location_name),
+            report,
+            |report, _| {
+                let location_started = Instant::now();
+                notifier.notify(&Event::started(Operation::Check, Some(location_name), None));
+
+                let mut location_errors = Vec::new();
+                for repo_name in repo_names {
+                    if signal::interrupted() {
+                        break;
+                    }
+                    if !checked.insert(repo_name.clone()) {
+                        print_log!(
+                            Level::DEBUG,
+                            "Repository {repo_name} has already been checked."
+                        );
+                        continue;
+                    }
+                    if let Some(repo) =
+                        resolve_location_repository(repo_name, location_name, config)
+                    {
+                        let _repo_lock = match lock_repository(&repo, Operation::Check, config) {
+                            Ok(Some(lock)) => lock,
+                            Ok(None) => {
+                                report.add_not_run(
+                                    Operation::Check,
+                                    Some(location_name),
+                                    repo_name,
+                                    Outcome::Skipped,
+                                );
+                                continue;
+                            }
+                            Err(err) => {
+                                report.add_not_run(
+                                    Operation::Check,
+                                    Some(location_name),
+                                    repo_name,
+                                    Outcome::Fail,
+                                );
+                                location_errors.push(Failure::new(
+                                    err.status(),
+                                    format!("Check of repository {repo_name} failed: {err}"),
+                                ));
+                                continue;
+                            }
+                        };
+                        print_log!(Level::INFO, "Check repository {repo_name} ...");
+                        notifier.notify(&Event::started(
+                            Operation::Check,
+                            Some(location_name),
+                            Some(repo_name),
+                        ));
+                        let started = Instant::now();
+                        let restic_args = location_args(
+                            location_name,
+                            config,
+                            ExtraArgs::check,
+                            args.restic_args(),
+                        );
+                        let result = api.check(&repo, &check_opts, &restic_args);
+                        let error = result.as_ref().err().map(describe_api_error);
+                        let event = Event::finished(
+                            Operation::Check,
+                            Some(location_name),
+                            Some(repo_name),
+                            started.elapsed(),
+                            error.clone(),
+                        );
+                        notifier.notify(&event);
+                        report.add(&event, Outcome::of(&result));
+                        match result {
+                            Ok(_) => {
+                                print_log!(Level::INFO, "Check of repository {repo_name} done.");
+                                if let Err(err) = state::record_check(repo_name) {
+                                    print_log!(Level::WARN, "Failed to update state file: {err}");
+                                }
+                            }
+                            Err(err) => {
+                                print_log!(
+                                    Level::ERROR,
+                                    "Check of repository {repo_name} failed: {err}"
+                                );
+                                location_errors.push(api.failure(
+                                    &err,
+                                    format!(
+                                        "Check of repository {repo_name} failed: {}",
+                                        error.unwrap_or_default()
+                                    ),
+                                ));
+                            }
+                        }
+                    } else {
+                        print_log!(
+                            Level::WARN,
+                            "Location {location_name} refers to an undefined repository {repo_name}."
+                        )
+                    }
+                }
+
+                notifier.notify(&Event::finished(
+                    Operation::Check,
+                    Some(location_name),
+                    None,
+                    location_started.elapsed(),
+                    join_failures(&location_errors),
+                ));
+                Ok(location_errors)
+            },
+        )?;
+        errors.append(&mut location_errors);
+    }
+
+    Ok(errors)
+}
+
+fn copy(config: &Config, args: &CopyArgs, report: &mut Report) -> Result<()> {
+    let notifier = Notifier::new(config);
+
+    let started = Instant::now();
+    notifier.notify(&Event::started(Operation::Copy, None, None));
+    let result = copy_locations(config, args, &notifier, report);
+    finish_run(&notifier, report, Operation::Copy, started, &result);
+
+    report_failures(Operation::Copy, &result?)
+}
+
+/// Copies the snapshots of all selected locations from their repositories to
+/// the target repository and returns the failed copies.
+fn copy_locations(
+    config: &Config,
+    args: &CopyArgs,
+    notifier: &Notifier,
+    report: &mut Report,
+) -> Result<Vec<Failure>> {
+    let api = restic_api::Api::new(
+        config.executable().to_string(),
+        restic_verbosity(),
+        restic_progress_fps(),
+        config.retry().clone(),
+    );
+
+    let target_name = args.target();
+    let Some(target) = resolve_repository(target_name, config) else {
+        bail!("Repository {target_name} is not defined.");
+    };
+    let m = resolve_selection(args.selected_locations(), config)?;
+    let Some(_target_lock) = lock_repository(&target, Operation::Copy, config)? else {
+        return Ok(Vec::new());
+    };
+    let mut errors = Vec::new();
+
+    for (location_name, repo_names) in ordered_locations(&m, config) {
+        if signal::interrupted() {
+            break;
+        }
+        let tag = get_tag(location_name);
+
+        print_log!(
+            Level::INFO,
+            "Copy of location {location_name} to repository {target_name} ..."
+        );
+
+        let copy_opts = get_copy_options(location_name, config);
+        let mut location_errors = run_hooked(
+            Operation::Copy,
+            &format!("location {location_name}"),
+            &location_items(
+                location_name,
+                repo_names.iter().filter(|r| *r != target_name),
+            ),
+            copy_opts.hooks(),
+            HookEnv::new(Operation::Copy.as_str())
+                .with("ARESTICRAT_LOCATION", location_name)
+                .with("ARESTICRAT_REPO", target_name),
+            report,
+            |report, _| {
+                let location_started = Instant::now();
+                notifier.notify(&Event::started(Operation::Copy, Some(location_name), None));
+
+                let mut location_errors = Vec::new();
+                for repo_name in repo_names.iter().filter(|r| *r != target_name) {
+                    if signal::interrupted() {
+                        break;
+                    }
+                    if let Some(repo) =
+                        resolve_location_repository(repo_name, location_name, config)
+                    {
+                        let _repo_lock = match lock_repository(&repo, Operation::Copy, config) {
+                            Ok(Some(lock)) => lock,
+                            Ok(None) => {
+                                report.add_not_run(
+                                    Operation::Copy,
+                                    Some(location_name),
+                                    repo_name,
+                                    Outcome::Skipped,
+                                );
+                                continue;
+                            }
+                            Err(err) => {
+                                report.add_not_run(
+                                    Operation::Copy,
+                                    Some(location_name),
+                                    repo_name,
+                                    Outcome::Fail,
+                                );
+                                location_errors.push(Failure::new(
+                                err.status(),
+                                format!(
+                                    "Copy of location {location_name} from repository {repo_name} failed: {err}"
+                                ),
+                            ));
+                                continue;
+                            }
+                        };
+                        print_log!(Level::INFO, "Copy from repository {repo_name} ...");
+                        notifier.notify(&Event::started(
+                            Operation::Copy,
+                            Some(location_name),
+                            Some(repo_name),
+                        ));
+                        let started = Instant::now();
+                        let restic_args = location_args(
+                            location_name,
+                            config,
+                            ExtraArgs::copy,
+                            args.restic_args(),
+                        );
+                        let result = api.copy(&repo, &target, &tag, &restic_args);
+                        let error = result.as_ref().err().map(describe_api_error);
+                        let event = Event::finished(
+                            Operation::Copy,
+                            Some(location_name),
+                            Some(repo_name),
+                            started.elapsed(),
+                            error.clone(),
+                        );
+                        notifier.notify(&event);
+                        report.add(&event, Outcome::of(&result));
+                        match result {
+                            Ok(_) => {
+                                print_log!(Level::INFO, "Copy from repository {repo_name} done.");
+                            }
+                            Err(err) => {
+                                print_log!(
+                                    Level::ERROR,
+                                    "Copy from repository {repo_name} failed: {err}"
+                                );
+                                location_errors.push(api.failure(
+                                &err,
+                                format!(
+                                    "Copy of location {location_name} from repository {repo_name} failed: {}",
+                                    error.unwrap_or_default()
+                                ),
+                            ));
+                            }
+                        }
+                    } else {
+                        print_log!(
+                            Level::WARN,
+                            "Location {location_name} refers to an undefined repository {repo_name}."
+                        )
+                    }
+                }
+
+                notifier.notify(&Event::finished(
+                    Operation::Copy,
+                    Some(location_name),
+                    None,
+                    location_started.elapsed(),
+                    join_failures(&location_errors),
+                ));
+                Ok(location_errors)
+            },
+        )?;
+        errors.append(&mut location_errors);
+    }
+
+    Ok(errors)
+}
+
+/// Copies the pending snapshots of the repositories of the selected locations
+/// to their replicas, see [`state::record_snapshots`].
+fn replicate(
+    config: &Config,
+    selection: &[LocationRepo],
+    dry_run: bool,
+    report: &mut Report,
+) -> Result<()> {
+    let notifier = if dry_run {
+        Notifier::default()
+    } else {
+        Notifier::new(config)
+    };
+    let api = restic_api::Api::new(
+        config.executable().to_string(),
+        restic_verbosity(),
+        restic_progress_fps(),
+        config.retry().clone(),
+    );
+    let state = state::load().map_err(|e| anyhow::anyhow!("Failed to read state file: {e}"))?;
+    let mut repo_names: Vec<_> = resolve_selection(selection, config)?
+        .into_values()
+        .flatten()
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+    repo_names.sort();
+
+    let mut errors = Vec::new();
+    for repo_name in &repo_names {
+        let Some(repo_config) = config.repos().get(repo_name) else {
+            continue;
+        };
+        for target_name in repo_config.replicate_to() {
+            if signal::interrupted() {
+                break;
+            }
+            let pending = state.replication(repo_name, target_name).pending().to_vec();
+            if pending.is_empty() {
+                continue;
+            }
+            print_log!(
+                Level::INFO,
+                "Replicate {} snapshot(s) of repository {repo_name} to repository {target_name} ...",
+                pending.len()
+            );
+            if dry_run {
+                print_log!(Level::INFO, "Skipped replication in dry run.");
+                continue;
+            }
+            let (Some(source), Some(target)) = (
+                resolve_repository(repo_name, config),
+                resolve_repository(target_name, config),
+            ) else {
+                continue;
+            };
+            let locks = match lock_repository(&source, Operation::Copy, config) {
+                Ok(Some(source_lock)) => lock_repository(&target, Operation::Copy, config)
+                    .map(|target_lock| target_lock.map(|target_lock| (source_lock, target_lock))),
+                result => result.map(|_| None),
+            };
+            let _locks = match locks {
+                Ok(Some(locks)) => locks,
+                Ok(None) => {
+                    report.add_not_run(Operation::Copy, None, repo_name, Outcome::Skipped);
+                    continue;
+                }
+                Err(err) => {
+                    report.add_not_run(Operation::Copy, None, repo_name, Outcome::Fail);
+                    errors.push(Failure::new(
+                        err.status(),
+                        format!(
+                            "Replication of repository {repo_name} to repository {target_name} failed: {err}"
+                        ),
+                    ));
+                    continue;
+                }
+            };
+            notifier.notify(&Event::started(Operation::Copy, None, Some(repo_name)));
+            let started = Instant::now();
+            let result =
+                existing_snapshots(&api, &source, target_name, pending).and_then(|pending| {
+                    if !pending.is_empty() {
+                        api.copy_snapshots(&source, &target, &pending)?;
+                    }
+                    Ok(pending)
+                });
+            let error = result.as_ref().err().map(describe_api_error);
+            let event = Event::finished(
+                Operation::Copy,
+                None,
+                Some(repo_name),
+                started.elapsed(),
+                error.clone(),
+            );
+            notifier.notify(&event);
+            report.add(&event, Outcome::of(&result));
+            match result {
+                Ok(pending) => {
+                    if let Err(err) = state::record_copy(repo_name, target_name, &pending) {
+                        print_log!(Level::WARN, "Failed to update state file: {err}");
+                    }
+                    print_log!(
+                        Level::INFO,
+                        "Replication of repository {repo_name} to repository {target_name} done."
+                    );
+                }
+                Err(err) => {
+                    print_log!(
+                        Level::ERROR,
+                        "Replication of repository {repo_name} to repository {target_name} failed: {err}"
+                    );
+                    errors.push(api.failure(
+                        &err,
+                        format!(
+                            "Replication of repository {repo_name} to repository {target_name} failed: {}",
+                            error.unwrap_or_default()
+                        ),
+                    ));
+                }
+            }
+        }
+    }
+    report_failures(Operation::Copy, &errors)
+}
+
+/// The pending snapshots that still exist in the source repository. The
+/// others have been forgotten since their backup, e.g. while the replica was
+/// unavailable, and are no longer pending, because restic could never copy
+/// them.
+fn existing_snapshots(
+    api: &restic_api::Api,
+    source: &Repository,
+    target_name: &Name,
+    pending: Vec<String>,
+) -> Result<Vec<String>, restic_api::Error> {
+    let snapshots = api.snapshots(source)?;
+    let (existing, missing): (Vec<_>, Vec<_>) = pending
+        .into_iter()
+        .partition(|id| snapshots.iter().any(|snapshot| &snapshot.short_id == id));
+    if !missing.is_empty() {
+        print_log!(
+            Level::WARN,
+            "Snapshot(s) {} of repository {} no longer exist and are not copied to repository {target_name}.",
+            missing.join(", "),
+            source.name
+        );
+        if let Err(err) = state::drop_pending(&source.name, target_name, &missing) {
+            print_log!(Level::WARN, "Failed to update state file: {err}");
+        }
+    }
+    Ok(existing)
+}
+
+fn exec(config: &Config, args: &ExecArgs) -> Result<()> {
+    let new_api = || {
+        restic_api::Api::new(
+            config.executable().to_string(),
+            restic_verbosity(),
+            restic_progress_fps(),
+            config.retry().clone(),
+        )
+    };
+    for location_name in args.locations() {
+        if !config.locations().contains_key(location_name) {
+            bail!("Location {location_name} is not defined.");
+        }
+    }
+    let mut repo_names = args.repos().to_vec();
+    if repo_names.is_empty() {
+        for location_name in args.locations() {
+            for repo_name in config.locations()[location_name].repos() {
+                if !repo_names.contains(repo_name) {
+                    repo_names.push(repo_name.clone());
+                }
+            }
+        }
+    }
+    if (*repo_names).as_ref().is_empty() && args.locations().is_empty() {
+        repo_names = config.repos().keys().cloned().collect();
+    }
+    let mut restic_args = exec_args(args);
+    let json = restic_args.iter().any(|arg| arg == "--json");
+    if args.merge_json() && !json {
+        restic_args.push("--json".to_string());
+    }
+    // The JSON documents of concurrent commands would be interleaved.
+    let merge_json = args.merge_json() || (args.parallel() && json);
+
+    let exec_repo = |api: &restic_api::Api, repo_name: &Name| {
+        let Some(repo) = resolve_repository(repo_name, config) else {
+            print_log!(
+                Level::WARN,
+                "Argument refers to an undefined repository {repo_name}."
+            );
+            return None;
+        };
+        let options = run::OutputOptions {
+            print: verbosity() >= DEFAULT_VERBOSITY,
+            capture_stdout: merge_json,
+            log: true,
+            label: args.parallel().then_some(repo_name.as_str()),
+        };
+        Some(
+            api.exec(&repo, &restic_args, &options)
+                .map(|output| output.stdout)
+                .map_err(|err| {
+                    print_log!(
+                        Level::ERROR,
+                        "Execution for repository {repo_name} failed: {err}"
+                    );
+                    api.exit_status(&err)
+                }),
+        )
+    };
+    let results: Vec<_> = if args.parallel() {
+        std::thread::scope(|scope| {
+            let handles: Vec<_> = repo_names
+                .iter()
+                .map(|repo_name| (repo_name, scope.spawn(|| exec_repo(&new_api(), repo_name))))
+                .collect();
+            handles
+                .into_iter()
+                .map(|(repo_name, handle)| (repo_name, handle.join().expect("exec panicked")))
+                .collect()
+        })
+    } else {
+        let api = new_api();
+        repo_names
+            .iter()
+            .map(|repo_name| (repo_name, exec_repo(&api, repo_name)))
+            .collect()
+    };
+
+    let mut failed = Vec::new();
+    let mut outputs = serde_json::Map::new();
+    for (repo_name, result) in results {
+        match result {
+            Some(Ok(stdout)) if merge_json => {
+                outputs.insert(repo_name.to_string(), parse_json_output(&stdout));
+            }
+            Some(Err(status)) => failed.push((repo_name.as_str(), status)),
+            Some(Ok(_)) | None => {}
+        }
+    }
+    if merge_json {
+        let outputs = serde_json::Value::Object(outputs);
+        let json = if args.merge_json() {
+            serde_json::to_string_pretty(&outputs).expect("JSON values are serializable.")
+        } else {
+            outputs.to_string()
+        };
+        println!("{}", redact::redact(&json));
+    }
+
+    if !failed.is_empty() {
+        let (repo_names, statuses): (Vec<_>, Vec<_>) = failed.into_iter().unzip();
+        return Err(exit::Error::new(
+            Status::combine(statuses),
+            format!(
+                "Execution failed for repositories {}.",
+                repo_names.join(", ")
+            ),
+        )
+        .into());
+    }
+
+    Ok(())
+}
+
+/// The JSON output of a restic command: one document or, e.g. for the messages
+/// of a backup, one document per line. Other output is kept as a string.
+fn parse_json_output(stdout: &[u8]) -> serde_json::Value {
+    if let Ok(value) = serde_json::from_slice(stdout) {
+        return value;
+    }
+    let text = String::from_utf8_lossy(stdout);
+    let lines: Option<Vec<_>> = text
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| serde_json::from_str(line).ok())
+        .collect();
+    match lines {
+        Some(lines) => serde_json::Value::Array(lines),
+        None => serde_json::Value::String(text.into_owned()),
+    }
+}
+
+/// The restic commands that filter snapshots by tag.
+const TAG_FILTER_COMMANDS: [&str; 11] = [
+    "copy",
+    "dump",
+    "find",
+    "forget",
+    "ls",
+    "mount",
+    "restore",
+    "rewrite",
+    "snapshots",
+    "stats",
+    "tag",
+];
+
+/// The arguments of the restic command of exec. The tags of the selected
+/// locations follow the command name if the command filters by tag.
+fn exec_args(args: &ExecArgs) -> Vec<String> {
+    let mut restic_args = args.args().to_vec();
+    if args.locations().is_empty() {
+        return restic_args;
+    }
+    let Some(index) = restic_args.iter().position(|arg| !arg.starts_with('-')) else {
+        return restic_args;
+    };
+    if !TAG_FILTER_COMMANDS.contains(&restic_args[index].as_str()) {
+        print_log!(
+            Level::WARN,
+            "restic {} does not filter snapshots by tag. The locations only select repositories.",
+            restic_args[index]
+        );
+        return restic_args;
+    }
+    let tags = args
+        .locations()
+        .iter()
+        .flat_map(|location_name| ["--tag".to_string(), get_tag(location_name)]);
+    restic_args.splice(index + 1..index + 1, tags);
+    restic_args
+}
+
+fn maintenance(config: &Config, args: &MaintenanceArgs, report: &mut Report) -> Result<()> {
+    let notifier = Notifier::new(config);
+
+    let started = Instant::now();
+    notifier.notify(&Event::started(Operation::Maintenance, None, None));
+    let result = maintain_repositories(config, args, &notifier, report);
+    finish_run(&notifier, report, Operation::Maintenance, started, &result);
+
+    report_failures(Operation::Maintenance, &result?)
+}
+
+/// Maintains the selected repositories and returns the failed ones.
+fn maintain_repositories(
+    config: &Config,
+    args: &MaintenanceArgs,
+    notifier: &Notifier,
+    report: &mut Report,
+) -> Result<Vec<Failure>> {
+    let api = restic_api::Api::new(
+        config.executable().to_string(),
+        restic_verbosity(),
+        restic_progress_fps(),
+        config.retry().clone(),
+    );
+    let mut repo_names = args.repos().to_vec();
+    if repo_names.is_empty() {
+        repo_names = config.repos().keys().cloned().collect();
+        repo_names.sort();
+    }
+
+    let mut errors = Vec::new();
+    for repo_name in &repo_names {
+        if signal::interrupted() {
+            break;
+        }
+        let Some(repo) = resolve_repository(repo_name, config) else {
+            print_log!(
+                Level::WARN,
+                "Argument refers to an undefined repository {repo_name}."
+            );
+            continue;
+        };
+        let _repo_lock = match lock_repository(&repo, Operation::Maintenance, config) {
+            Ok(Some(lock)) => lock,
+            Ok(None) => {
+                report.add_not_run(Operation::Maintenance, None, repo_name, Outcome::Skipped);
+                continue;
+            }
+            Err(err) => {
+                report.add_not_run(Operation::Maintenance, None, repo_name, Outcome::Fail);
+                errors.push(Failure::new(
+                    err.status(),
+                    format!("Maintenance of repository {repo_name} failed: {err}"),
+                ));
+                continue;
+            }
+        };
+
+        print_log!(Level::INFO, "Maintenance of repository {repo_name} ...");
+        let mut repo_errors = run_hooked(
+            Operation::Maintenance,
+            &format!("repository {repo_name}"),
+            &[(None, repo_name)],
+            config.maintenance().hooks(),
+            HookEnv::new(Operation::Maintenance.as_str()).with("ARESTICRAT_REPO", repo_name),
+            report,
+            |report, _| {
+                let mut failures = Vec::new();
+                notifier.notify(&Event::started(
+                    Operation::Maintenance,
+                    None,
+                    Some(repo_name),
+                ));
+                let started = Instant::now();
+                let result = maintain_repository(&api, repo_name, &repo, config, args);
+                let error = result.as_ref().err().map(describe_api_error);
+                let event = Event::finished(
+                    Operation::Maintenance,
+                    None,
+                    Some(repo_name),
+                    started.elapsed(),
+                    error.clone(),
+                );
+                notifier.notify(&event);
+                report.add(&event, Outcome::of(&result));
+                match result {
                     Ok(_) => {
-                        print_log!(Level::INFO, "Backup to repository {repo_name} done.");
-                        successful_repo_names.push(repo_name.clone());
+                        print_log!(Level::INFO, "Maintenance of repository {repo_name} done.");
                     }
                     Err(err) => {
                         print_log!(
                             Level::ERROR,
-                            "Backup to repository {repo_name} failed: {err}"
+                            "Maintenance of repository {repo_name} failed: {err}"
                         );
+                        failures.push(api.failure(
+                            &err,
+                            format!(
+                                "Maintenance of repository {repo_name} failed: {}",
+                                error.unwrap_or_default()
+                            ),
+                        ));
                     }
                 }
-            } else {
+                Ok(failures)
+            },
+        )?;
+        errors.append(&mut repo_errors);
+    }
+
+    Ok(errors)
+}
+
+/// Runs the maintenance steps of a repository until the first failure.
+fn maintain_repository(
+    api: &restic_api::Api,
+    repo_name: &Name,
+    repo: &Repository,
+    config: &Config,
+    args: &MaintenanceArgs,
+) -> Result<(), restic_api::Error> {
+    let settings = config.maintenance();
+
+    print_log!(Level::INFO, "Remove stale locks ...");
+    api.unlock(repo)?;
+
+    print_log!(Level::INFO, "Check ...");
+    let subset = args
+        .read_data_subset()
+        .or(config.repos()[repo_name].check().read_data_subset())
+        .or(settings.read_data_subset());
+    let check_args: Vec<String> = match subset {
+        Some(subset) => vec![format!("--read-data-subset={subset}")],
+        None => Vec::new(),
+    };
+    let check_opts = config.options().check().cloned().unwrap_or_default();
+    api.check(repo, &check_opts, &check_args)?;
+    if let Err(err) = state::record_check(repo_name) {
+        print_log!(Level::WARN, "Failed to update state file: {err}");
+    }
+
+    let last_prune = match state::load() {
+        Ok(state) => state.repo(repo_name).last_prune(),
+        Err(err) => {
+            print_log!(Level::WARN, "Failed to read state file: {err}");
+            None
+        }
+    };
+    let prune_due = match (settings.prune_interval(), last_prune) {
+        (Some(interval), Some(last_prune)) => {
+            (chrono::Local::now() - last_prune)
+                .to_std()
+                .unwrap_or_default()
+                >= interval.into()
+        }
+        _ => true,
+    };
+    if prune_due {
+        print_log!(Level::INFO, "Prune ...");
+        api.prune(repo, settings.prune_timeout())?;
+        if let Err(err) = state::record_prune(repo_name) {
+            print_log!(Level::WARN, "Failed to update state file: {err}");
+        }
+    } else {
+        print_log!(
+            Level::INFO,
+            "Prune of repository {repo_name} is not due yet."
+        );
+    }
+
+    print_log!(Level::INFO, "Clean up cache ...");
+    api.cache_cleanup(repo)
+}
+
+fn pin(config: &Config, args: &PinArgs) -> Result<()> {
+    let api = restic_api::Api::new(
+        config.executable().to_string(),
+        restic_verbosity(),
+        restic_progress_fps(),
+        config.retry().clone(),
+    );
+    let location_name = args.location();
+    let Some(location) = config.locations().get(location_name) else {
+        bail!("Location {location_name} is not defined.");
+    };
+    let pin_tag = match args.tag() {
+        Some(tag) => tag,
+        None => match config.pin_tags().first() {
+            Some(tag) => tag.as_str(),
+            None => bail!("No pin tag is given and the pin-tags setting is empty."),
+        },
+    };
+    if !args.unpin() && !config.pin_tags().iter().any(|tag| tag == pin_tag) {
+        print_log!(
+            Level::WARN,
+            "Tag {pin_tag} is not one of the pin-tags, so forget does not keep the snapshot."
+        );
+    }
+    let repo_names = match args.repos() {
+        [] => location.repos().as_slice(),
+        repo_names => repo_names,
+    };
+    let snapshot = args.snapshot().unwrap_or("latest");
+    let tag = get_tag(location_name);
+
+    let mut failed = Vec::new();
+    for repo_name in repo_names {
+        if !location.repos().contains(repo_name) {
+            print_log!(
+                Level::WARN,
+                "Location {location_name} does not use repository {repo_name}."
+            );
+            continue;
+        }
+        let Some(repo) = resolve_location_repository(repo_name, location_name, config) else {
+            print_log!(
+                Level::WARN,
+                "Location {location_name} refers to an undefined repository {repo_name}."
+            );
+            continue;
+        };
+        match api.tag_snapshot(&repo, &tag, snapshot, pin_tag, args.unpin()) {
+            Ok(()) if args.unpin() => print_log!(
+                Level::INFO,
+                "Removed tag {pin_tag} from snapshot {snapshot} of location {location_name} in repository {repo_name}."
+            ),
+            Ok(()) => print_log!(
+                Level::INFO,
+                "Pinned snapshot {snapshot} of location {location_name} in repository {repo_name} with tag {pin_tag}."
+            ),
+            Err(err) => {
                 print_log!(
-                    Level::WARN,
-                    "Location {location_name} refers to an undefined repository {repo_name}."
-                )
+                    Level::ERROR,
+                    "Tagging snapshot {snapshot} in repository {repo_name} failed: {err}"
+                );
+                failed.push((repo_name.as_str(), api.exit_status(&err)));
             }
         }
-
-        if !args.dry_run() && backup_opts.forget() {
-            forget_location(
-                &api,
-                location_name,
-                &successful_repo_names,
-                config,
-                args.dry_run(),
-            )?;
-        }
     }
-    Ok(())
-}
 
-fn run_hooks(name: &str, hooks: &[CommandSeq]) -> Result<std::process::ExitStatus, std::io::Error> {
-    if hooks.is_empty() {
-        return Ok(Default::default());
+    if !failed.is_empty() {
+        let (repo_names, statuses): (Vec<_>, Vec<_>) = failed.into_iter().unzip();
+        return Err(exit::Error::new(
+            Status::combine(statuses),
+            format!("Pin failed for repositories {}.", repo_names.join(", ")),
+        )
+        .into());
     }
 
-    print_log!(Level::INFO, "Running {name} hooks ...");
-    run::run_sequential(hooks.iter().map(|c| c.to_command()), false)
+    Ok(())
 }
 
-fn exec(config: &Config, args: &ExecArgs) -> Result<()> {
-    let api = restic_api::Api::new(config.executable().to_string(), restic_verbosity());
-    let mut repo_names = args.repos().to_vec();
-    if (*repo_names).as_ref().is_empty() {
-        repo_names = config.repos().keys().cloned().collect();
+fn config_show(config: &Config, args: &ConfigShowArgs) -> Result<()> {
+    if args.resolved() {
+        let json = serde_json::to_string_pretty(config.resolved())?;
+        println!("{}", redact::redact(&json));
+        return Ok(());
     }
-
-    for repo_name in (*repo_names).as_ref() {
-        if let Some(repo) = resolve_repository(repo_name, config) {
-            match api.exec(&repo, args.args()) {
-                Ok(_) => {}
-                Err(err) => {
-                    print_log!(
-                        Level::ERROR,
-                        "Execution for repository {repo_name} failed: {err}"
-                    );
-                }
-            }
+    for (i, file) in config.files().iter().enumerate() {
+        if i < config.sources().len() {
+            println!("{}", file.display());
         } else {
-            print_log!(
-                Level::WARN,
-                "Argument refers to an undefined repository {repo_name}."
-            )
+            println!("{} (SOPS)", file.display());
         }
     }
+    Ok(())
+}
+
+fn key_store(config: &Config, args: &KeyStoreArgs) -> Result<()> {
+    let repo_name = args.repo();
+    let Some(repo) = config.repos().get(repo_name) else {
+        bail!("Repository {repo_name} is not defined.");
+    };
+    let Some(entry) = repo.password_keyring() else {
+        bail!("Repository {repo_name} does not define a key-keyring entry.");
+    };
+
+    let key = if std::io::stdin().is_terminal() {
+        rpassword::prompt_password(format!("Key of repository {repo_name}: "))?
+    } else {
+        let mut line = String::new();
+        std::io::stdin().read_line(&mut line)?;
+        line.trim_end_matches(['\r', '\n']).to_string()
+    };
+    if key.is_empty() {
+        bail!("The key must not be empty.");
+    }
+
+    keyring::Entry::new(entry.service(), entry.account())
+        .and_then(|e| e.set_password(&key))
+        .map_err(|e| anyhow::anyhow!("Failed to write keyring entry {entry}: {e}"))?;
+    print_log!(
+        Level::INFO,
+        "Key of repository {repo_name} saved in keyring entry {entry}."
+    );
 
     Ok(())
 }
 
-fn forget(config: &Config, args: &ForgetArgs) -> Result<()> {
-    let api = restic_api::Api::new(config.executable().to_string(), restic_verbosity());
+fn forget(config: &Config, args: &ForgetArgs, report: &mut Report) -> Result<()> {
+    let notifier = if args.dry_run() {
+        Notifier::default()
+    } else {
+        Notifier::new(config)
+    };
+
+    let started = Instant::now();
+    notifier.notify(&Event::started(Operation::Forget, None, None));
+    let result = forget_locations(config, args, &notifier, report);
+    finish_run(&notifier, report, Operation::Forget, started, &result);
+
+    report_failures(Operation::Forget, &result?)
+}
+
+/// Runs the forget of all selected locations and returns the failed
+/// repository forgets.
+fn forget_locations(
+    config: &Config,
+    args: &ForgetArgs,
+    notifier: &Notifier,
+    report: &mut Report,
+) -> Result<Vec<Failure>> {
+    let api = restic_api::Api::new(
+        config.executable().to_string(),
+        restic_verbosity(),
+        restic_progress_fps(),
+        config.retry().clone(),
+    );
 
     let m = resolve_selection(args.selected_locations(), config)?;
+    let mut metrics = load_metrics(config)?;
+    let mut errors = Vec::new();
 
-    for (location_name, repo_names) in &m {
-        forget_location(&api, location_name, repo_names, config, args.dry_run())?;
+    for (location_name, repo_names) in ordered_locations(&m, config) {
+        if signal::interrupted() {
+            break;
+        }
+        print_log!(Level::INFO, "Forget for location {location_name} ...");
+        let forget_opts = get_forget_options(location_name, config);
+        let mut location_errors = run_hooked(
+            Operation::Forget,
+            &format!("location {location_name}"),
+            &location_items(location_name, repo_names),
+            forget_opts.hooks(),
+            HookEnv::new(Operation::Forget.as_str())
+                .with("ARESTICRAT_LOCATION", location_name)
+                .with("ARESTICRAT_DRY_RUN", args.dry_run()),
+            report,
+            |report, _| {
+                forget_location(
+                    &api,
+                    location_name,
+                    repo_names,
+                    config,
+                    &mut metrics,
+                    notifier,
+                    report,
+                    args,
+                )
+            },
+        )?;
+        errors.append(&mut location_errors);
     }
 
-    Ok(())
+    save_metrics(config, &metrics)?;
+    Ok(errors)
 }
 
+/// Runs the forget of a single location and returns the failed repository
+/// forgets. The hooks of the location are run by the caller.
+#[allow(clippy::too_many_arguments)]
 fn forget_location<'a, R: IntoIterator<Item = &'a Name>>(
     api: &restic_api::Api,
     location_name: &Name,
     repo_names: R,
     config: &Config,
-    dry_run: bool,
-) -> Result<()> {
-    print_log!(Level::INFO, "Forget for location {location_name} ...");
-
+    metrics: &mut metrics::Registry,
+    notifier: &Notifier,
+    report: &mut Report,
+    args: &ForgetArgs,
+) -> Result<Vec<Failure>> {
     let tag = get_tag(location_name);
-    let forget_opts = get_forget_options(location_name, config);
 
-    let if_status = run_hooks("IF", forget_opts.hooks().r#if())?;
-    if !if_status.success() {
-        print_log!(Level::INFO, "IF hook failed. Skip location.");
-        return Ok(());
-    }
+    let location_started = Instant::now();
+    notifier.notify(&Event::started(
+        Operation::Forget,
+        Some(location_name),
+        None,
+    ));
 
+    let mut errors = Vec::new();
     for repo_name in repo_names {
-        if let Some(repo) = resolve_repository(repo_name, config) {
+        if signal::interrupted() {
+            break;
+        }
+        if let Some(repo) = resolve_location_repository(repo_name, location_name, config) {
+            let _repo_lock = match lock_repository(&repo, Operation::Forget, config) {
+                Ok(Some(lock)) => lock,
+                Ok(None) => {
+                    report.add_not_run(
+                        Operation::Forget,
+                        Some(location_name),
+                        repo_name,
+                        Outcome::Skipped,
+                    );
+                    continue;
+                }
+                Err(err) => {
+                    report.add_not_run(
+                        Operation::Forget,
+                        Some(location_name),
+                        repo_name,
+                        Outcome::Fail,
+                    );
+                    errors.push(Failure::new(
+                        err.status(),
+                        format!(
+                            "Forget of location {location_name} from repository {repo_name} failed: {err}"
+                        ),
+                    ));
+                    continue;
+                }
+            };
             print_log!(Level::INFO, "Forget from repository {repo_name} ...");
-            match api.forget(&repo, &tag, &forget_opts, dry_run) {
+            let forget_opts = get_repo_forget_options(location_name, repo_name, config)
+                .with_keep_tags(config.pin_tags());
+            let prune = forget_opts.prune() && prune_due(repo_name, &forget_opts);
+            notifier.notify(&Event::started(
+                Operation::Forget,
+                Some(location_name),
+                Some(repo_name),
+            ));
+            let started = Instant::now();
+            let result = api.forget(
+                &repo,
+                &tag,
+                &forget_opts,
+                prune,
+                &location_args(location_name, config, ExtraArgs::forget, args.restic_args()),
+                args.dry_run(),
+            );
+            if !args.dry_run() {
+                let sample = Sample {
+                    exit_code: result.as_ref().map_or_else(|e| e.exit_code(), |_| Some(0)),
+                    success: result.is_ok(),
+                    duration: started.elapsed(),
+                    bytes_added: None,
+                };
+                metrics.record(Operation::Forget, location_name, repo_name, &sample);
+            }
+            let error = result.as_ref().err().map(describe_api_error);
+            let event = Event::finished(
+                Operation::Forget,
+                Some(location_name),
+                Some(repo_name),
+                started.elapsed(),
+                error.clone(),
+            );
+            notifier.notify(&event);
+            report.add(&event, Outcome::of(&result));
+            match result {
                 Ok(_) => {
                     print_log!(Level::INFO, "Forget from repository {repo_name} done.");
+                    if !args.dry_run() && forget_opts.prune() {
+                        if let Err(err) = state::record_forget(repo_name, prune) {
+                            print_log!(Level::WARN, "Failed to update state file: {err}");
+                        }
+                    }
                 }
                 Err(err) => {
                     print_log!(
                         Level::ERROR,
                         "Forget from repository {repo_name} failed: {err}"
                     );
+                    errors.push(api.failure(
+                        &err,
+                        format!(
+                            "Forget of location {location_name} from repository {repo_name} failed: {}",
+                            error.unwrap_or_default()
+                        ),
+                    ));
                 }
             }
         } else {
@@ -228,26 +2423,251 @@ fn forget_location<'a, R: IntoIterator<Item = &'a Name>>(
         }
     }
 
+    notifier.notify(&Event::finished(
+        Operation::Forget,
+        Some(location_name),
+        None,
+        location_started.elapsed(),
+        join_failures(&errors),
+    ));
+
+    Ok(errors)
+}
+
+fn status(config: &Config, args: &StatusArgs) -> Result<()> {
+    let api = restic_api::Api::new(
+        config.executable().to_string(),
+        restic_verbosity(),
+        restic_progress_fps(),
+        config.retry().clone(),
+    );
+    let started = Instant::now();
+
+    let m = resolve_selection(args.selected_locations(), config)?;
+    let mut location_names: Vec<_> = m.keys().collect();
+    location_names.sort();
+
+    let mut locked = 0;
+    let mut failed = Vec::new();
+    let mut stale: Vec<(&Name, &Name, String)> = Vec::new();
+    let now = chrono::Utc::now();
+    for location_name in location_names {
+        let tag = get_tag(location_name);
+        let max_age = get_status_options(location_name, config).max_age();
+
+        let mut repo_names: Vec<_> = m[location_name].iter().collect();
+        repo_names.sort();
+        for repo_name in repo_names {
+            let Some(repo) = resolve_repository(repo_name, config) else {
+                print_log!(
+                    Level::WARN,
+                    "Location {location_name} refers to an undefined repository {repo_name}."
+                );
+                continue;
+            };
+
+            let snapshot = match api.latest_snapshot(&repo, &tag) {
+                Err(err) if err.is_locked() => {
+                    match handle_locked_repository(
+                        &api,
+                        repo_name,
+                        &repo,
+                        config,
+                        args.unlock_stale(),
+                    ) {
+                        Ok(()) => api.latest_snapshot(&repo, &tag),
+                        Err(locks) => {
+                            locked += 1;
+                            print_log!(
+                                Level::ERROR,
+                                "{location_name}@{repo_name}: {}: {locks}",
+                                style::paint_stderr("LOCKED", Color::Red)
+                            );
+                            continue;
+                        }
+                    }
+                }
+                result => result,
+            };
+            let snapshot = match snapshot {
+                Ok(snapshot) => snapshot,
+                Err(err) => {
+                    failed.push(api.exit_status(&err));
+                    print_log!(
+                        Level::ERROR,
+                        "{location_name}@{repo_name}: {}: {err}",
+                        style::paint_stderr("FAILED", Color::Red)
+                    );
+                    continue;
+                }
+            };
+
+            match snapshot {
+                Some(snapshot) => {
+                    let age = (now - snapshot.time.to_utc()).to_std().unwrap_or_default();
+                    let age = config::Duration::from(age);
+                    let age_str = match max_age {
+                        Some(max_age) if age > max_age => {
+                            stale.push((
+                                location_name,
+                                repo_name,
+                                format!(
+                                    "Latest snapshot {} is {age} old, which exceeds the maximum age of {max_age}.",
+                                    snapshot.short_id
+                                ),
+                            ));
+                            style::paint(age, Color::Red)
+                        }
+                        Some(_) => style::paint(age, Color::Green),
+                        None => age.to_string(),
+                    };
+                    print_log!(
+                        Level::INFO,
+                        "{location_name}@{repo_name}: {age_str} ago (snapshot {})",
+                        snapshot.short_id
+                    );
+                }
+                None => {
+                    if let Some(max_age) = max_age {
+                        stale.push((
+                            location_name,
+                            repo_name,
+                            format!("No snapshot, although the maximum age is {max_age}."),
+                        ));
+                    }
+                    print_log!(
+                        Level::INFO,
+                        "{location_name}@{repo_name}: {}",
+                        style::paint("NO SNAPSHOT", Color::Red)
+                    )
+                }
+            }
+        }
+    }
+
+    let stale_message = format!(
+        "{} location/repository combination(s) exceed the maximum snapshot age.",
+        stale.len()
+    );
+    notify_stale(config, &stale, started, &stale_message);
+
+    let mut messages = Vec::new();
+    if locked > 0 {
+        messages.push(format!(
+            "{locked} location/repository combination(s) are locked."
+        ));
+    }
+    if !failed.is_empty() {
+        messages.push(format!(
+            "{} location/repository combination(s) failed.",
+            failed.len()
+        ));
+    }
+    if !stale.is_empty() {
+        messages.push(stale_message);
+    }
+    if !messages.is_empty() {
+        let statuses = std::iter::repeat_n(Status::Overlap, locked)
+            .chain(failed)
+            .chain(std::iter::repeat_n(Status::Outdated, stale.len()));
+        return Err(exit::Error::new(Status::combine(statuses), messages.join(" ")).into());
+    }
+
     Ok(())
 }
 
+/// Sends failure events for the location/repository combinations whose latest
+/// snapshot is missing or too old: one per combination, one per location and
+/// one for the whole status run. Nothing is sent if all snapshots are fresh.
+fn notify_stale(
+    config: &Config,
+    stale: &[(&Name, &Name, String)],
+    started: Instant,
+    message: &str,
+) {
+    if stale.is_empty() {
+        return;
+    }
+    let notifier = Notifier::new(config);
+    let mut location_names: Vec<_> = stale
+        .iter()
+        .map(|(location_name, ..)| *location_name)
+        .collect();
+    location_names.dedup();
+    for location_name in location_names {
+        let mut errors = Vec::new();
+        for (_, repo_name, error) in stale.iter().filter(|(l, ..)| *l == location_name) {
+            notifier.notify(&Event::finished(
+                Operation::Status,
+                Some(location_name),
+                Some(repo_name),
+                started.elapsed(),
+                Some(error.clone()),
+            ));
+            errors.push(format!("{repo_name}: {error}"));
+        }
+        notifier.notify(&Event::finished(
+            Operation::Status,
+            Some(location_name),
+            None,
+            started.elapsed(),
+            Some(errors.join("\n")),
+        ));
+    }
+    notifier.notify(&Event::finished(
+        Operation::Status,
+        None,
+        None,
+        started.elapsed(),
+        Some(message.to_string()),
+    ));
+}
+
 fn verify(config: &Config, args: &VerifyArgs) -> Result<()> {
-    let api = restic_api::Api::new(config.executable().to_string(), restic_verbosity());
+    let api = restic_api::Api::new(
+        config.executable().to_string(),
+        restic_verbosity(),
+        restic_progress_fps(),
+        config.retry().clone(),
+    );
 
-    for repo_name in config.repos().keys() {
+    let mut results = verify::Results::new(args.warn_only());
+    let mut failed = Vec::new();
+    let mut repo_names: Vec<_> = config.repos().keys().collect();
+    repo_names.sort();
+    for repo_name in repo_names {
+        let repo_config = &config.repos()[repo_name];
         if let Some(repo) = resolve_repository(repo_name, config) {
+            if let Err(err) = verify_rclone_remote(repo_config) {
+                results.add_repository(repo_name, "FAILED", Some(err.to_string()));
+                failed.push(Status::Config);
+                continue;
+            }
             let status = match api.status(&repo) {
                 Result::Ok(status) => status,
                 Err(err) => {
-                    print_log!(Level::ERROR, "Repository {repo_name}: FAILED: {err}");
+                    results.add_repository(repo_name, "FAILED", Some(err.to_string()));
+                    failed.push(api.exit_status(&err));
                     continue;
                 }
             };
 
             use restic_api::RepoStatus::*;
             match status {
-                Ok => {
-                    print_log!(Level::INFO, "Repository {repo_name}: OK")
+                Ok => match verify_data(&api, repo_name, &repo, config, args) {
+                    Result::Ok(()) => results.add_repository(repo_name, "OK", None),
+                    Err(err) => {
+                        results.add_repository(repo_name, "CHECK FAILED", Some(err.to_string()));
+                        failed.push(api.exit_status(&err));
+                    }
+                },
+                NoRepository if args.init() && dry_run() => {
+                    print_log!(
+                        Level::INFO,
+                        "Dry run: skip initialization of repository {repo_name}."
+                    );
+                    results.add_repository(repo_name, "NOT FOUND", None);
+                    failed.push(Status::Unavailable);
                 }
                 NoRepository if args.init() => {
                     print_log!(
@@ -255,17 +2675,214 @@ fn verify(config: &Config, args: &VerifyArgs) -> Result<()> {
                         "Repository {repo_name} not found. Initialize ..."
                     );
                     api.init(&repo)?;
-                    print_log!(Level::INFO, "Repository {repo_name}: INITIALIZED")
+                    results.add_repository(repo_name, "INITIALIZED", None);
+                }
+                NoRepository => {
+                    results.add_repository(repo_name, "NOT FOUND", None);
+                    failed.push(Status::Unavailable);
+                }
+                Locked => match handle_locked_repository(
+                    &api,
+                    repo_name,
+                    &repo,
+                    config,
+                    args.unlock_stale(),
+                ) {
+                    Result::Ok(()) => results.add_repository(repo_name, "UNLOCKED", None),
+                    Err(locks) => {
+                        results.add_repository(repo_name, "LOCKED", Some(locks));
+                        failed.push(Status::Overlap);
+                    }
+                },
+                InvalidKey => {
+                    results.add_repository(repo_name, "INVALID KEY", None);
+                    failed.push(Status::InvalidKey);
                 }
-                NoRepository => print_log!(Level::ERROR, "Repository {repo_name}: NOT FOUND"),
-                Locked => print_log!(Level::ERROR, "Repository {repo_name}: LOCKED"),
-                InvalidKey => print_log!(Level::ERROR, "Repository {repo_name}: INVALID KEY."),
             }
         }
         // No else required here, because we resolve the repository from the
         // definied repository configurations.
     }
 
+    let mut location_names: Vec<_> = config.locations().keys().collect();
+    location_names.sort();
+    for location_name in location_names {
+        let location = &config.locations()[location_name];
+        let problems = verify::location_problems(location_name, location, config);
+        results.add_location(location_name, problems);
+    }
+
+    let (failed_repos, failed_locations) = results.failed();
+    if args.json() {
+        results.print_json();
+    }
+    if failed_repos > 0 || failed_locations > 0 {
+        let mut parts = Vec::new();
+        if failed_repos > 0 {
+            parts.push(format!("{failed_repos} repository(ies)"));
+        }
+        if failed_locations > 0 {
+            parts.push(format!("{failed_locations} location(s)"));
+            failed.push(Status::Config);
+        }
+        let message = format!("Verification of {} failed.", parts.join(" and "));
+        if args.warn_only() {
+            print_log!(Level::WARN, "{message}");
+            return Ok(());
+        }
+        return Err(exit::Error::new(Status::combine(failed.iter().copied()), message).into());
+    }
+
+    Ok(())
+}
+
+/// Describes the locks of a locked repository and removes them if requested and
+/// all of them are older than the stale lock age. Returns the description if
+/// the repository is still locked.
+fn handle_locked_repository(
+    api: &restic_api::Api,
+    repo_name: &Name,
+    repo: &Repository,
+    config: &Config,
+    unlock_stale: bool,
+) -> Result<(), String> {
+    let locks = api
+        .locks(repo)
+        .map_err(|e| format!("Failed to list the locks: {e}"))?;
+    if locks.is_empty() {
+        return Err("The locks have been removed in the meantime.".to_string());
+    }
+    let now = chrono::Utc::now();
+    let max_age: std::time::Duration = config.stale_lock_age().into();
+    let mut stale = true;
+    let descriptions: Vec<_> = locks
+        .iter()
+        .map(|lock| {
+            let age = (now - lock.time.to_utc()).to_std().unwrap_or_default();
+            stale &= age >= max_age;
+            format!(
+                "{} lock {} of {}@{} (PID {}), refreshed {} ago",
+                if lock.exclusive {
+                    "Exclusive"
+                } else {
+                    "Shared"
+                },
+                lock.id.get(..8).unwrap_or(&lock.id),
+                lock.username,
+                lock.hostname,
+                lock.pid,
+                config::Duration::from(age),
+            )
+        })
+        .collect();
+    let description = descriptions.join(", ");
+    if !stale {
+        return Err(description);
+    }
+    if !unlock_stale {
+        return Err(format!("{description} (stale, remove with --unlock-stale)"));
+    }
+    if dry_run() {
+        print_log!(
+            Level::INFO,
+            "Dry run: skip removal of the stale locks of repository {repo_name}."
+        );
+        return Err(format!("{description} (stale, not removed in dry run)"));
+    }
+    print_log!(
+        Level::WARN,
+        "Remove stale locks of repository {repo_name}: {description}"
+    );
+    api.unlock_all(repo)
+        .map_err(|e| format!("{description}: Failed to remove the stale locks: {e}"))
+}
+
+/// Checks the integrity of the repository and reads its data as requested by
+/// the --read-data and --read-data-subset options of the verify command.
+fn verify_data(
+    api: &restic_api::Api,
+    repo_name: &Name,
+    repo: &Repository,
+    config: &Config,
+    args: &VerifyArgs,
+) -> Result<(), restic_api::Error> {
+    let check_args: Vec<String> = if args.read_data() {
+        vec!["--read-data".to_string()]
+    } else if let Some(subset) = args.read_data_subset() {
+        let subset = subset
+            .or(config.repos()[repo_name].check().read_data_subset())
+            .or(config.maintenance().read_data_subset());
+        subset
+            .map(|subset| format!("--read-data-subset={subset}"))
+            .into_iter()
+            .collect()
+    } else {
+        return Ok(());
+    };
+    print_log!(Level::INFO, "Check repository {repo_name} ...");
+    let check_opts = config.options().check().cloned().unwrap_or_default();
+    api.check(repo, &check_opts, &check_args)
+}
+
+/// Checks that the rclone remote of an rclone repository is configured, so
+/// that a typo in its name is reported as such.
+fn verify_rclone_remote(repo: &config::Repo) -> Result<()> {
+    let path = repo.path();
+    let Some(remote) = path.strip_prefix("rclone:") else {
+        return Ok(());
+    };
+    // Remotes that are defined inline, e.g. ":sftp,host=example.org:", are
+    // not listed.
+    let name = remote.split(':').next().unwrap_or_default();
+    if name.is_empty() {
+        return Ok(());
+    }
+    let program = repo.rclone().program();
+    let mut cmd = std::process::Command::new(program);
+    cmd.arg("listremotes");
+    let output = run::run_output(&mut cmd, true)
+        .map_err(|e| anyhow::anyhow!("Failed to run {program}: {e}"))?;
+    if !output.status.success() {
+        bail!("{program} listremotes failed ({}).", output.status);
+    }
+    let remotes = String::from_utf8_lossy(&output.stdout);
+    if !remotes.lines().any(|l| l.trim() == format!("{name}:")) {
+        bail!("rclone remote {name} is not configured.");
+    }
+    Ok(())
+}
+
+/// Loads the current metric values if the textfile exporter is enabled.
+fn load_metrics(config: &Config) -> Result<metrics::Registry> {
+    Ok(match config.metrics().textfile_dir() {
+        Some(dir) => metrics::Registry::load_textfile(dir.join(metrics::TEXTFILE_NAME))?,
+        None => Default::default(),
+    })
+}
+
+fn save_metrics(config: &Config, metrics: &metrics::Registry) -> Result<()> {
+    if let Some(dir) = config.metrics().textfile_dir() {
+        // Operations may run concurrently in daemon mode. Merge the values of
+        // this run into the current textfile, so that no values of another
+        // operation are lost.
+        static LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+        let _guard = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let path = dir.join(metrics::TEXTFILE_NAME);
+        let mut current = metrics::Registry::load_textfile(&path)?;
+        current.merge(metrics);
+        current.write_textfile(path)?;
+    }
+    // An unavailable Pushgateway must not fail the run, like a state file
+    // that cannot be written.
+    if let Some(gateway) = config.metrics().pushgateway() {
+        if let Err(err) = metrics.push(gateway) {
+            print_log!(
+                Level::WARN,
+                "Failed to push metrics to {}: {err}",
+                gateway.url()
+            );
+        }
+    }
     Ok(())
 }
 
@@ -309,25 +2926,98 @@ where
     }
 }
 
-fn setup_logger() {
-    tracing_subscriber::registry()
-        .with(
-            tracing_subscriber::fmt::layer()
-                .with_ansi(std::io::stdout().is_terminal())
-                .with_filter(
-                    EnvFilter::builder()
-                        .with_default_directive(LevelFilter::OFF.into())
-                        .with_env_var("ARESTICRAT_LOG")
-                        .from_env_lossy(),
-                ),
+fn setup_logger(#[cfg_attr(not(windows), allow(unused_variables))] args: &Args) {
+    let registry = tracing_subscriber::registry().with(
+        tracing_subscriber::fmt::layer()
+            .with_ansi(style::enabled_stderr())
+            .with_writer(|| redact::Writer::new(std::io::stderr()))
+            .with_filter(
+                EnvFilter::builder()
+                    .with_default_directive(LevelFilter::OFF.into())
+                    .with_env_var("ARESTICRAT_LOG")
+                    .from_env_lossy(),
+            ),
+    );
+
+    #[cfg(windows)]
+    let registry = {
+        let layer = args
+            .event_log()
+            .then(eventlog::EventLogLayer::new)
+            .and_then(|result| {
+                result
+                    .inspect_err(|err| {
+                        print_log!(Level::WARN, "Failed to open the Windows Event Log: {err}")
+                    })
+                    .ok()
+            });
+        registry.with(
+            layer.with_filter(
+                EnvFilter::builder()
+                    .with_default_directive(LevelFilter::INFO.into())
+                    .with_env_var("ARESTICRAT_EVENTLOG")
+                    .from_env_lossy(),
+            ),
         )
-        .init();
+    };
+
+    registry.init();
 }
 
 fn get_tag(location_name: &Name) -> String {
     format!("_aresticrat_{location_name}")
 }
 
+/// Whether a forget from the repository should prune it according to the
+/// prune cadence. Without cadence, every forget prunes.
+fn prune_due(repo_name: &Name, forget_opts: &ForgetOptions) -> bool {
+    if forget_opts.prune_interval().is_none() && forget_opts.prune_every().is_none() {
+        return true;
+    }
+    let repo_state = match state::load() {
+        Ok(state) => state.repo(repo_name),
+        Err(err) => {
+            print_log!(Level::WARN, "Failed to read state file: {err}");
+            return true;
+        }
+    };
+    let Some(last_prune) = repo_state.last_prune() else {
+        return true;
+    };
+    let interval_elapsed = forget_opts.prune_interval().is_some_and(|interval| {
+        let elapsed = (chrono::Local::now() - last_prune)
+            .to_std()
+            .unwrap_or_default();
+        elapsed >= interval.into()
+    });
+    let every_reached = forget_opts
+        .prune_every()
+        .is_some_and(|n| repo_state.forgets_since_prune() + 1 >= n);
+    if !interval_elapsed && !every_reached {
+        print_log!(
+            Level::INFO,
+            "Prune of repository {repo_name} is not due yet."
+        );
+    }
+    interval_elapsed || every_reached
+}
+
+/// The extra arguments of a subcommand of the location, followed by the
+/// restic arguments of the command line.
+fn location_args(
+    location_name: &Name,
+    config: &Config,
+    extra_args: fn(&ExtraArgs) -> &[String],
+    args: &[String],
+) -> Vec<String> {
+    let extra = config
+        .locations()
+        .get(location_name)
+        .map(|l| extra_args(l.extra_args()))
+        .unwrap_or_default();
+    [extra, args].concat()
+}
+
 fn get_backup_options(location_name: &Name, config: &Config) -> BackupOptions {
     config
         .locations()
@@ -348,6 +3038,51 @@ fn get_forget_options(location_name: &Name, config: &Config) -> ForgetOptions {
         .unwrap_or_default()
 }
 
+/// The forget options of the location's snapshots in the repository. Those
+/// of the repository replace those of the location.
+fn get_repo_forget_options(
+    location_name: &Name,
+    repo_name: &Name,
+    config: &Config,
+) -> ForgetOptions {
+    config
+        .locations()
+        .get(location_name)
+        .and_then(|l| l.repo_forget(repo_name))
+        .cloned()
+        .unwrap_or_else(|| get_forget_options(location_name, config))
+}
+
+fn get_check_options(location_name: &Name, config: &Config) -> CheckOptions {
+    config
+        .locations()
+        .get(location_name)
+        .and_then(|l| l.options().check())
+        .or_else(|| config.options().check())
+        .cloned()
+        .unwrap_or_default()
+}
+
+fn get_copy_options(location_name: &Name, config: &Config) -> CopyOptions {
+    config
+        .locations()
+        .get(location_name)
+        .and_then(|l| l.options().copy())
+        .or_else(|| config.options().copy())
+        .cloned()
+        .unwrap_or_default()
+}
+
+fn get_status_options(location_name: &Name, config: &Config) -> StatusOptions {
+    config
+        .locations()
+        .get(location_name)
+        .and_then(|l| l.options().status())
+        .or_else(|| config.options().status())
+        .cloned()
+        .unwrap_or_default()
+}
+
 fn get_repo_env_vars(repo_name: &Name, config: &Config) -> HashMap<String, String> {
     let mut vars = HashMap::new();
     append_env(config.environment(), &mut vars);
@@ -433,19 +3168,86 @@ fn resolve_selection(
     Ok(m)
 }
 
+/// The selected locations in the order in which their operations run: higher
+/// priority first and every location after the selected locations it depends
+/// on. Locations of equal priority run in alphabetical order.
+fn ordered_locations<'a>(
+    m: &'a HashMap<Name, HashSet<Name>>,
+    config: &Config,
+) -> Vec<(&'a Name, &'a HashSet<Name>)> {
+    fn visit<'a>(
+        location_name: &'a Name,
+        m: &'a HashMap<Name, HashSet<Name>>,
+        config: &Config,
+        visited: &mut HashSet<&'a Name>,
+        ordered: &mut Vec<(&'a Name, &'a HashSet<Name>)>,
+    ) {
+        if !visited.insert(location_name) {
+            return;
+        }
+        // Cycles are rejected when the configuration is loaded.
+        if let Some(location) = config.locations().get(location_name) {
+            for dependency in location.depends_on() {
+                if let Some((dependency, _)) = m.get_key_value(dependency) {
+                    visit(dependency, m, config, visited, ordered);
+                }
+            }
+        }
+        ordered.push((location_name, &m[location_name]));
+    }
+
+    let mut location_names: Vec<_> = m.keys().collect();
+    location_names.sort_by_key(|location_name| {
+        let priority = config
+            .locations()
+            .get(*location_name)
+            .map_or(0, Location::priority);
+        (std::cmp::Reverse(priority), *location_name)
+    });
+    let mut visited = HashSet::new();
+    let mut ordered = Vec::new();
+    for location_name in location_names {
+        visit(location_name, m, config, &mut visited, &mut ordered);
+    }
+    ordered
+}
+
 /// Turns the repository configuration into the format that ist expected by the
 /// API.
 fn resolve_repository(repo_name: &Name, config: &Config) -> Option<Repository> {
     if let Some(repo_config) = config.repos().get(repo_name) {
         let env_vars = get_repo_env_vars(repo_name, config);
+        redact::add_env(&env_vars);
         Some(Repository {
             name: repo_name.clone(),
-            path: repo_config.path().to_string(),
+            path: repo_config.path(),
             password: repo_config.password().to_string(),
             password_file: repo_config.password_file().map(Path::to_path_buf),
             password_command: repo_config.password_command().to_string(),
-            retry_lock: repo_config.retry_lock().to_string(),
-            options: repo_config.options().clone(),
+            cache_password_command: repo_config.cache_password_command(),
+            password_keyring: repo_config.password_keyring().cloned(),
+            password_credential: repo_config.password_credential().map(str::to_string),
+            retry_lock: match repo_config.retry_lock() {
+                "" => config.retry_lock().to_string(),
+                retry_lock => retry_lock.to_string(),
+            },
+            no_lock: repo_config.no_lock().unwrap_or(config.no_lock()),
+            extra_args: config.extra_args().chain(repo_config.extra_args()),
+            timeouts: repo_config.timeout().clone(),
+            options: repo_config.backend_options(),
+            pack_size: repo_config.pack_size(),
+            limit_upload: repo_config.limit_upload(),
+            limit_download: repo_config.limit_download(),
+            compression: repo_config.compression(),
+            cache_dir: repo_config
+                .cache_dir()
+                .or(config.cache_dir())
+                .map(Path::to_path_buf),
+            no_cache: repo_config.no_cache().unwrap_or(config.no_cache()),
+            cacert: repo_config.cacert().to_vec(),
+            tls_client_cert: repo_config.tls_client_cert().map(Path::to_path_buf),
+            insecure_tls: repo_config.insecure_tls(),
+            max_cores: repo_config.max_cores(),
             environment: env_vars,
         })
     } else {
@@ -453,18 +3255,39 @@ fn resolve_repository(repo_name: &Name, config: &Config) -> Option<Repository> {
     }
 }
 
+/// The repository as used by the operations of the location, whose settings
+/// take precedence over those of the repository.
+fn resolve_location_repository(
+    repo_name: &Name,
+    location_name: &Name,
+    config: &Config,
+) -> Option<Repository> {
+    let mut repo = resolve_repository(repo_name, config)?;
+    if let Some(location) = config.locations().get(location_name) {
+        repo.max_cores = location.max_cores().or(repo.max_cores);
+    }
+    Some(repo)
+}
+
 macro_rules! print_log {
     ($lvl:expr, $($arg:tt)*) => {
         {
+            let message = format!($($arg)*);
+            let message = crate::redact::redact(&message);
             match ($lvl, crate::verbosity()) {
-                (tracing::Level::TRACE, v) if v > 4 => println!($($arg)*),
-                (tracing::Level::DEBUG, v) if v > 3 => println!($($arg)*),
-                (tracing::Level::INFO, v) if v > 2 => println!($($arg)*),
-                (tracing::Level::WARN, v) if v > 1 => eprintln!($($arg)*),
-                (tracing::Level::ERROR, v) if v > 0 => eprintln!($($arg)*),
+                (tracing::Level::TRACE, v) if v > 4 => println!("{message}"),
+                (tracing::Level::DEBUG, v) if v > 3 => println!("{message}"),
+                (tracing::Level::INFO, v) if v > 2 => println!("{message}"),
+                (tracing::Level::WARN, v) if v > 1 => {
+                    eprintln!("{}", crate::style::paint_stderr(&message, crate::style::Color::Yellow))
+                }
+                (tracing::Level::ERROR, v) if v > 0 => {
+                    eprintln!("{}", crate::style::paint_stderr(&message, crate::style::Color::Red))
+                }
                 _ => {},
             };
-            tracing::event!($lvl, $($arg)*)
+            crate::output::log($lvl, &message);
+            tracing::event!($lvl, "{message}")
         }
     };
 }
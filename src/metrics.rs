@@ -0,0 +1,215 @@
+use crate::config::Name;
+use crate::config::Pushgateway;
+use crate::event::Operation;
+use crate::http;
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::fmt::Write as _;
+use std::io::ErrorKind;
+use std::path::Path;
+use std::time::Duration;
+
+pub const TEXTFILE_NAME: &str = "aresticrat.prom";
+
+/// Names and help texts of all exported metrics.
+const DEFINITIONS: [(&str, &str); 7] = [
+    (
+        "aresticrat_backup_last_success_timestamp",
+        "Unix timestamp of the last successful backup.",
+    ),
+    (
+        "aresticrat_backup_duration_seconds",
+        "Duration of the last backup in seconds.",
+    ),
+    (
+        "aresticrat_backup_bytes_added",
+        "Bytes added to the repository by the last backup.",
+    ),
+    (
+        "aresticrat_backup_exit_status",
+        "Exit status of restic for the last backup (-1 if restic could not be run).",
+    ),
+    (
+        "aresticrat_forget_last_success_timestamp",
+        "Unix timestamp of the last successful forget.",
+    ),
+    (
+        "aresticrat_forget_duration_seconds",
+        "Duration of the last forget in seconds.",
+    ),
+    (
+        "aresticrat_forget_exit_status",
+        "Exit status of restic for the last forget (-1 if restic could not be run).",
+    ),
+];
+
+/// The outcome of a single restic operation for a location/repository pair.
+#[derive(Clone, Debug)]
+pub struct Sample {
+    pub exit_code: Option<i32>,
+    pub success: bool,
+    pub duration: Duration,
+    pub bytes_added: Option<u64>,
+}
+
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+struct Key {
+    name: String,
+    location: String,
+    repo: String,
+}
+
+/// A set of metric values labeled by location and repository.
+#[derive(Debug, Default)]
+pub struct Registry {
+    values: BTreeMap<Key, f64>,
+    /// Locations for which values have been recorded in this run.
+    recorded: BTreeSet<String>,
+    /// Values that have been recorded in this run.
+    changed: BTreeSet<Key>,
+}
+
+impl Registry {
+    /// Loads the values of a previously written textfile, so that metrics of
+    /// locations that are not part of the current run are preserved.
+    pub fn load_textfile<P: AsRef<Path>>(path: P) -> Result<Self, std::io::Error> {
+        let content = match std::fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == ErrorKind::NotFound => String::new(),
+            Err(err) => return Err(err),
+        };
+
+        let mut registry = Self::default();
+        for line in content.lines().filter(|l| !l.starts_with('#')) {
+            if let Some((key, value)) = parse_line(line) {
+                registry.values.insert(key, value);
+            }
+        }
+        Ok(registry)
+    }
+
+    pub fn record(&mut self, op: Operation, location: &Name, repo: &Name, sample: &Sample) {
+        self.recorded.insert(location.to_string());
+        let mut set = |suffix: &str, value: f64| {
+            let key = Key {
+                name: format!("aresticrat_{op}_{suffix}"),
+                location: location.to_string(),
+                repo: repo.to_string(),
+            };
+            self.values.insert(key.clone(), value);
+            self.changed.insert(key);
+        };
+
+        set("duration_seconds", sample.duration.as_secs_f64());
+        set("exit_status", sample.exit_code.unwrap_or(-1) as f64);
+        if let Some(bytes) = sample.bytes_added {
+            set("bytes_added", bytes as f64);
+        }
+        if sample.success {
+            set(
+                "last_success_timestamp",
+                chrono::Utc::now().timestamp() as f64,
+            );
+        }
+    }
+
+    /// Takes over the values recorded in the other registry.
+    pub fn merge(&mut self, other: &Registry) {
+        for key in &other.changed {
+            self.values.insert(key.clone(), other.values[key]);
+            self.changed.insert(key.clone());
+        }
+        self.recorded.extend(other.recorded.iter().cloned());
+    }
+
+    /// Renders all values in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        self.render_filtered(None)
+    }
+
+    /// Renders the values of all or a single location. The location label is
+    /// omitted if a location is given.
+    fn render_filtered(&self, location: Option<&str>) -> String {
+        let mut out = String::new();
+        for (name, help) in DEFINITIONS {
+            let mut values = self
+                .values
+                .iter()
+                .filter(|(k, _)| k.name == name)
+                .filter(|(k, _)| location.is_none_or(|l| k.location == l))
+                .peekable();
+            if values.peek().is_none() {
+                continue;
+            }
+            let _ = writeln!(out, "# HELP {name} {help}");
+            let _ = writeln!(out, "# TYPE {name} gauge");
+            for (key, value) in values {
+                let _ = match location {
+                    Some(_) => writeln!(out, "{name}{{repo=\"{}\"}} {value}", key.repo),
+                    None => writeln!(
+                        out,
+                        "{name}{{location=\"{}\",repo=\"{}\"}} {value}",
+                        key.location, key.repo
+                    ),
+                };
+            }
+        }
+        out
+    }
+
+    /// Pushes the values recorded in this run to a Pushgateway.
+    ///
+    /// Values are grouped by location, and only metrics with the same name are
+    /// replaced, so that results of earlier runs (e.g. the timestamp of the
+    /// last successful backup) are kept.
+    pub fn push(&self, gateway: &Pushgateway) -> Result<(), ureq::Error> {
+        let agent = http::agent();
+        for location in &self.recorded {
+            let url = format!(
+                "{}/metrics/job/{}/location/{location}",
+                gateway.url().trim_end_matches('/'),
+                gateway.job()
+            );
+            let mut request = agent.post(&url).content_type("text/plain; version=0.0.4");
+            if let Some(username) = gateway.username() {
+                let password = gateway.password().unwrap_or_default();
+                request = request.header("Authorization", http::basic_auth(username, password));
+            }
+            request.send(self.render_filtered(Some(location)))?;
+        }
+        Ok(())
+    }
+
+    /// Writes the values to the given file. The file is replaced atomically,
+    /// so that the node exporter never reads a partially written file.
+    pub fn write_textfile<P: AsRef<Path>>(&self, path: P) -> Result<(), std::io::Error> {
+        let path = path.as_ref();
+        let tmp_path = path.with_extension("prom.tmp");
+        std::fs::write(&tmp_path, self.render())?;
+        std::fs::rename(&tmp_path, path)
+    }
+}
+
+fn parse_line(line: &str) -> Option<(Key, f64)> {
+    let (name, rest) = line.split_once('{')?;
+    let (labels, value) = rest.split_once('}')?;
+
+    let mut location = None;
+    let mut repo = None;
+    for label in labels.split(',') {
+        let (k, v) = label.split_once('=')?;
+        let v = v.trim_matches('"').to_string();
+        match k {
+            "location" => location = Some(v),
+            "repo" => repo = Some(v),
+            _ => {}
+        }
+    }
+
+    let key = Key {
+        name: name.to_string(),
+        location: location?,
+        repo: repo?,
+    };
+    Some((key, value.trim().parse().ok()?))
+}
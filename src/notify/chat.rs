@@ -0,0 +1,96 @@
+use super::{Error, Message, Sink};
+use crate::config::{ChatWebhook, Config};
+use crate::event::{Event, EventKind};
+use crate::{http, template};
+
+/// Discord rejects messages with more characters.
+const DISCORD_MAX_LENGTH: usize = 2000;
+
+#[derive(Clone, Copy)]
+enum Service {
+    Slack,
+    Discord,
+}
+
+/// Posts the results of locations to a Slack or Discord incoming webhook.
+pub struct Chat {
+    service: Service,
+    config: ChatWebhook,
+    agent: ureq::Agent,
+}
+
+impl Chat {
+    pub fn slack_from_config(config: &Config) -> Option<Self> {
+        Self::new(Service::Slack, config.notify().slack()?)
+    }
+
+    pub fn discord_from_config(config: &Config) -> Option<Self> {
+        Self::new(Service::Discord, config.notify().discord()?)
+    }
+
+    fn new(service: Service, config: &ChatWebhook) -> Option<Self> {
+        Some(Self {
+            service,
+            config: config.clone(),
+            agent: http::agent(),
+        })
+    }
+
+    fn text(&self, event: &Event, message: &Message) -> String {
+        if let Some(t) = self.config.template() {
+            return template::render(t, |name| event.var(name), str::to_string);
+        }
+
+        let bold = match self.service {
+            Service::Slack => "*",
+            Service::Discord => "**",
+        };
+        let icon = match event.kind {
+            EventKind::Failed => ":x:",
+            _ => ":white_check_mark:",
+        };
+        let mut text = format!("{icon} {bold}{}{bold}", message.title);
+        if message.templated {
+            text.push_str(&format!("\n{}", message.body));
+            return text;
+        }
+        if let Some(duration) = event.var("duration") {
+            text.push_str(&format!(" ({duration}s)"));
+        }
+        if let Some(error) = &event.error {
+            text.push_str(&format!("\n```\n{error}\n```"));
+        }
+        text
+    }
+}
+
+impl Sink for Chat {
+    fn name(&self) -> &'static str {
+        match self.service {
+            Service::Slack => "slack",
+            Service::Discord => "discord",
+        }
+    }
+
+    fn send(&self, event: &Event, message: &Message) -> Result<(), Error> {
+        if !super::is_location_result(event) {
+            return Ok(());
+        }
+
+        let text = self.text(event, message);
+        let payload = match self.service {
+            Service::Slack => serde_json::json!({
+                "text": text,
+                "channel": self.config.channel(),
+            }),
+            Service::Discord => serde_json::json!({
+                "content": text.chars().take(DISCORD_MAX_LENGTH).collect::<String>(),
+            }),
+        };
+        self.agent
+            .post(self.config.url())
+            .content_type("application/json")
+            .send(payload.to_string())?;
+        Ok(())
+    }
+}
@@ -0,0 +1,133 @@
+use super::{Error, Sink};
+use crate::config::{self, Config, EmailTls};
+use crate::event::{Event, EventKind};
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{SmtpTransport, Transport};
+use std::cell::{Cell, RefCell};
+use std::fmt::Write as _;
+
+/// Sends a report via SMTP after a run.
+///
+/// Results of single repositories are collected during the run and sent as one
+/// message when the run is finished.
+pub struct Email {
+    config: config::Email,
+    results: RefCell<Vec<String>>,
+    failures: Cell<usize>,
+}
+
+impl Email {
+    pub fn from_config(config: &Config) -> Option<Self> {
+        let config = config.notify().email()?.clone();
+        Some(Self {
+            config,
+            results: Default::default(),
+            failures: Default::default(),
+        })
+    }
+
+    fn record(&self, event: &Event) {
+        let (Some(location), Some(repo)) = (&event.location, &event.repo) else {
+            return;
+        };
+        let status = match event.kind {
+            EventKind::Started => return,
+            EventKind::Succeeded => "OK",
+            EventKind::Failed => {
+                self.failures.set(self.failures.get() + 1);
+                "FAILED"
+            }
+        };
+
+        let mut line = format!("{status:<7} {} {location}@{repo}", event.operation);
+        if let Some(duration) = event.var("duration") {
+            let _ = write!(line, " ({duration}s)");
+        }
+        if let Some(error) = &event.error {
+            for l in error.lines() {
+                let _ = write!(line, "\n        {l}");
+            }
+        }
+        self.results.borrow_mut().push(line);
+    }
+
+    fn report(&self, event: &Event) -> Result<(), Error> {
+        let failed = event.kind == EventKind::Failed;
+        if !failed && !self.config.always() {
+            return Ok(());
+        }
+
+        let host = hostname::get()
+            .map(|h| h.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let verdict = if failed { "failed" } else { "succeeded" };
+        let subject = format!("[aresticrat] {} {verdict} on {host}", event.operation);
+
+        let mut body = format!("The {} run on {host} {verdict}.\n\n", event.operation);
+        let results = self.results.borrow();
+        if !results.is_empty() {
+            body.push_str("Results:\n");
+            for line in results.iter() {
+                let _ = writeln!(body, "{line}");
+            }
+        }
+        if let (Some(error), 0) = (&event.error, self.failures.get()) {
+            let _ = write!(body, "\nError:\n{error}\n");
+        }
+
+        self.send_mail(subject, body)
+    }
+
+    fn send_mail(&self, subject: String, body: String) -> Result<(), Error> {
+        let parse = |s: &str| {
+            s.parse::<Mailbox>()
+                .map_err(|e| Error::Config(format!("Invalid address {s}: {e}")))
+        };
+
+        let mut message = lettre::Message::builder()
+            .from(parse(self.config.from())?)
+            .subject(subject);
+        for to in self.config.to() {
+            message = message.to(parse(to)?);
+        }
+        let message = message
+            .body(body)
+            .map_err(|e| Error::Config(e.to_string()))?;
+
+        let server = self.config.server();
+        let mut transport = match self.config.tls() {
+            EmailTls::Starttls => SmtpTransport::starttls_relay(server)?,
+            EmailTls::Tls => SmtpTransport::relay(server)?,
+            EmailTls::None => SmtpTransport::builder_dangerous(server),
+        };
+        if let Some(port) = self.config.port() {
+            transport = transport.port(port);
+        }
+        if let Some(username) = self.config.username() {
+            let password = self.config.password().unwrap_or_default();
+            transport =
+                transport.credentials(Credentials::new(username.to_string(), password.to_string()));
+        }
+        transport.build().send(&message)?;
+        Ok(())
+    }
+}
+
+impl Sink for Email {
+    fn name(&self) -> &'static str {
+        "email"
+    }
+
+    fn send(&self, event: &Event, _message: &super::Message) -> Result<(), Error> {
+        if event.location.is_none() && event.kind != EventKind::Started {
+            let result = self.report(event);
+            self.results.borrow_mut().clear();
+            self.failures.set(0);
+            result
+        } else {
+            self.record(event);
+            Ok(())
+        }
+    }
+}
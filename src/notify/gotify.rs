@@ -0,0 +1,56 @@
+use super::{Error, Message, Sink};
+use crate::config::{self, Config};
+use crate::event::{Event, EventKind};
+use crate::http;
+
+const DEFAULT_PRIORITY_SUCCEEDED: i32 = 2;
+const DEFAULT_PRIORITY_FAILED: i32 = 8;
+
+/// Sends the results of locations to a Gotify server.
+pub struct Gotify {
+    config: config::PushService,
+    agent: ureq::Agent,
+}
+
+impl Gotify {
+    pub fn from_config(config: &Config) -> Option<Self> {
+        let config = config.notify().gotify()?.clone();
+        Some(Self {
+            config,
+            agent: http::agent(),
+        })
+    }
+}
+
+impl Sink for Gotify {
+    fn name(&self) -> &'static str {
+        "gotify"
+    }
+
+    fn send(&self, event: &Event, message: &Message) -> Result<(), Error> {
+        if !super::is_location_result(event) {
+            return Ok(());
+        }
+        let priority = match event.kind {
+            EventKind::Failed => self.config.priority_failed(DEFAULT_PRIORITY_FAILED),
+            _ => self.config.priority_succeeded(DEFAULT_PRIORITY_SUCCEEDED),
+        };
+
+        let url = self
+            .config
+            .url()
+            .ok_or_else(|| Error::Config("Gotify requires a server URL.".to_string()))?;
+        let url = format!("{}/message", url.trim_end_matches('/'));
+        let payload = serde_json::json!({
+            "title": message.title.as_str(),
+            "message": message.body.as_str(),
+            "priority": priority,
+        });
+        self.agent
+            .post(url)
+            .header("X-Gotify-Key", self.config.token())
+            .content_type("application/json")
+            .send(payload.to_string())?;
+        Ok(())
+    }
+}
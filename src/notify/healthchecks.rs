@@ -0,0 +1,71 @@
+use super::{Error, Message, Sink};
+use crate::config::{Config, Name};
+use crate::event::{Event, EventKind, Operation};
+use crate::http;
+use std::collections::HashMap;
+
+/// Pings healthchecks.io (or a compatible service) on backup runs.
+///
+/// The global ping URL monitors whole runs, location-specific ping URLs
+/// monitor the backup of single locations.
+pub struct Healthchecks {
+    global_url: Option<String>,
+    location_urls: HashMap<Name, String>,
+    agent: ureq::Agent,
+}
+
+impl Healthchecks {
+    pub fn from_config(config: &Config) -> Option<Self> {
+        let global_url = config
+            .notify()
+            .healthchecks()
+            .map(|h| h.ping_url().to_string());
+        let location_urls: HashMap<_, _> = config
+            .locations()
+            .iter()
+            .filter_map(|(name, l)| {
+                let url = l.notify().healthchecks()?.ping_url().to_string();
+                Some((name.clone(), url))
+            })
+            .collect();
+
+        if global_url.is_none() && location_urls.is_empty() {
+            return None;
+        }
+        Some(Self {
+            global_url,
+            location_urls,
+            agent: http::agent(),
+        })
+    }
+}
+
+impl Sink for Healthchecks {
+    fn name(&self) -> &'static str {
+        "healthchecks"
+    }
+
+    fn send(&self, event: &Event, _message: &Message) -> Result<(), Error> {
+        if event.operation != Operation::Backup || event.repo.is_some() {
+            return Ok(());
+        }
+        let url = match &event.location {
+            Some(location) => self.location_urls.get(location),
+            None => self.global_url.as_ref(),
+        };
+        let Some(url) = url else {
+            return Ok(());
+        };
+
+        let url = url.trim_end_matches('/');
+        match event.kind {
+            EventKind::Started => self.agent.post(format!("{url}/start")).send_empty()?,
+            EventKind::Succeeded => self.agent.post(url).send_empty()?,
+            EventKind::Failed => self
+                .agent
+                .post(format!("{url}/fail"))
+                .send(event.error.as_deref().unwrap_or_default())?,
+        };
+        Ok(())
+    }
+}
@@ -0,0 +1,227 @@
+use crate::config::{self, Config, Name};
+use crate::event::{Event, EventKind};
+use crate::template;
+use crate::{output, print_log};
+use std::collections::HashMap;
+use thiserror::Error;
+use tracing::Level;
+
+mod chat;
+mod email;
+mod gotify;
+mod healthchecks;
+mod ntfy;
+mod plugin;
+mod pushover;
+mod webhook;
+
+trait Sink {
+    fn name(&self) -> &str;
+    fn send(&self, event: &Event, message: &Message) -> Result<(), Error>;
+}
+
+/// The human-readable form of an event used by push and chat services.
+pub struct Message {
+    pub title: String,
+    pub body: String,
+    /// Whether the body has been rendered from a configured template instead
+    /// of the default format.
+    pub templated: bool,
+}
+
+/// Delivers events to all configured notification services.
+#[derive(Default)]
+pub struct Notifier {
+    sinks: Vec<Box<dyn Sink>>,
+    rules: Rules,
+    location_rules: HashMap<Name, Rules>,
+    retries: u32,
+    retry_delay: std::time::Duration,
+}
+
+/// Routing and templating settings of the global or a location-specific
+/// notify section.
+#[derive(Default)]
+struct Rules {
+    routes: HashMap<String, Vec<String>>,
+    templates: config::NotifyTemplates,
+}
+
+impl Rules {
+    fn from_config(notify: &config::Notify) -> Self {
+        Self {
+            routes: notify.routes().clone(),
+            templates: notify.templates().clone(),
+        }
+    }
+}
+
+impl Notifier {
+    pub fn new(config: &Config) -> Self {
+        let mut sinks: Vec<Box<dyn Sink>> = Vec::new();
+        if let Some(sink) = healthchecks::Healthchecks::from_config(config) {
+            sinks.push(Box::new(sink));
+        }
+        if let Some(sink) = webhook::Webhook::from_config(config) {
+            sinks.push(Box::new(sink));
+        }
+        if let Some(sink) = email::Email::from_config(config) {
+            sinks.push(Box::new(sink));
+        }
+        if let Some(sink) = ntfy::Ntfy::from_config(config) {
+            sinks.push(Box::new(sink));
+        }
+        if let Some(sink) = chat::Chat::slack_from_config(config) {
+            sinks.push(Box::new(sink));
+        }
+        if let Some(sink) = chat::Chat::discord_from_config(config) {
+            sinks.push(Box::new(sink));
+        }
+        if let Some(sink) = gotify::Gotify::from_config(config) {
+            sinks.push(Box::new(sink));
+        }
+        if let Some(sink) = pushover::Pushover::from_config(config) {
+            sinks.push(Box::new(sink));
+        }
+        for sink in plugin::Plugin::all_from_config(config) {
+            sinks.push(Box::new(sink));
+        }
+        Self {
+            sinks,
+            rules: Rules::from_config(config.notify()),
+            location_rules: config
+                .locations()
+                .iter()
+                .map(|(name, l)| (name.clone(), Rules::from_config(l.notify())))
+                .collect(),
+            retries: config.notify().retries(),
+            retry_delay: config.notify().retry_delay().into(),
+        }
+    }
+
+    /// Sends the event to all sinks it is routed to and writes it to the JSON
+    /// output if enabled. Failed deliveries are retried according to the
+    /// configured policy. Remaining failures are logged, but never interrupt
+    /// the run.
+    pub fn notify(&self, event: &Event) {
+        output::event(event);
+        let message = self.message(event);
+        for sink in self.sinks.iter().filter(|s| self.routed(s.name(), event)) {
+            let mut attempt = 0;
+            while let Err(err) = sink.send(event, &message) {
+                // A misconfigured sink fails again on every attempt.
+                if attempt >= self.retries || matches!(err, Error::Config(_)) {
+                    print_log!(
+                        Level::WARN,
+                        "Failed to send {} notification: {err}",
+                        sink.name()
+                    );
+                    break;
+                }
+                attempt += 1;
+                print_log!(
+                    Level::DEBUG,
+                    "Failed to send {} notification, retry in {}: {err}",
+                    sink.name(),
+                    config::Duration::from(self.retry_delay)
+                );
+                std::thread::sleep(self.retry_delay);
+            }
+        }
+    }
+
+    fn location_rules(&self, event: &Event) -> Option<&Rules> {
+        event
+            .location
+            .as_ref()
+            .and_then(|l| self.location_rules.get(l))
+    }
+
+    /// Whether the event should be delivered to the sink. Location-specific
+    /// routes take precedence over global routes. Sinks without routes receive
+    /// all events.
+    fn routed(&self, sink: &str, event: &Event) -> bool {
+        let patterns = self
+            .location_rules(event)
+            .and_then(|r| r.routes.get(sink))
+            .or_else(|| self.rules.routes.get(sink));
+        match patterns {
+            Some(patterns) => patterns.iter().any(|p| matches_route(p, event)),
+            None => true,
+        }
+    }
+
+    fn message(&self, event: &Event) -> Message {
+        let location_templates = self.location_rules(event).map(|r| &r.templates);
+        let render = |t: &str| template::render(t, |name| event.var(name), str::to_string);
+
+        let title = location_templates
+            .and_then(|t| t.title())
+            .or_else(|| self.rules.templates.title())
+            .map(render)
+            .unwrap_or_else(|| title(event));
+        let template = location_templates
+            .and_then(|t| t.message())
+            .or_else(|| self.rules.templates.message());
+        let body = template.map(render).unwrap_or_else(|| message(event));
+        Message {
+            title,
+            body,
+            templated: template.is_some(),
+        }
+    }
+}
+
+/// Matches an event against a route pattern. Patterns are `*`, a status (e.g.
+/// `failed`), an operation (e.g. `backup`) or both (e.g. `backup.failed`).
+fn matches_route(pattern: &str, event: &Event) -> bool {
+    pattern == "*"
+        || pattern == event.kind.as_str()
+        || pattern == event.operation.as_str()
+        || pattern == event.name()
+}
+
+/// Whether the event concludes the operation of a location. Push services only
+/// report these events to keep the number of messages low.
+fn is_location_result(event: &Event) -> bool {
+    event.location.is_some() && event.repo.is_none() && event.kind != EventKind::Started
+}
+
+/// A short human-readable summary of the event, e.g. "Backup of home failed".
+fn title(event: &Event) -> String {
+    let operation = event.operation.label();
+    match &event.location {
+        Some(location) => format!("{operation} of {location} {}", event.kind.as_str()),
+        None => format!("{operation} {}", event.kind.as_str()),
+    }
+}
+
+/// The details of the event, i.e. its duration, added data and error.
+fn message(event: &Event) -> String {
+    let mut lines = Vec::new();
+    if let Some(duration) = event.var("duration") {
+        lines.push(format!("Duration: {duration}s"));
+    }
+    if let Some(bytes) = event.var("bytes_added") {
+        lines.push(format!("Added: {bytes} bytes"));
+    }
+    if let Some(error) = &event.error {
+        lines.push(String::new());
+        lines.push(error.clone());
+    }
+    lines.join("\n")
+}
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("{0}")]
+    Http(#[from] ureq::Error),
+    #[error("{0}")]
+    Smtp(#[from] lettre::transport::smtp::Error),
+    #[error("{0}")]
+    Config(String),
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+    #[error("{0}")]
+    Plugin(String),
+}
@@ -0,0 +1,48 @@
+use super::{Error, Message, Sink};
+use crate::config::{self, Config};
+use crate::event::{Event, EventKind};
+use crate::http;
+
+/// Publishes the results of locations to an ntfy topic.
+pub struct Ntfy {
+    config: config::Ntfy,
+    agent: ureq::Agent,
+}
+
+impl Ntfy {
+    pub fn from_config(config: &Config) -> Option<Self> {
+        let config = config.notify().ntfy()?.clone();
+        Some(Self {
+            config,
+            agent: http::agent(),
+        })
+    }
+}
+
+impl Sink for Ntfy {
+    fn name(&self) -> &'static str {
+        "ntfy"
+    }
+
+    fn send(&self, event: &Event, message: &Message) -> Result<(), Error> {
+        if !super::is_location_result(event) {
+            return Ok(());
+        }
+        let (priority, tag) = match event.kind {
+            EventKind::Failed => (self.config.priority().failed(), "warning"),
+            _ => (self.config.priority().succeeded(), "white_check_mark"),
+        };
+
+        let mut request = self
+            .agent
+            .post(self.config.url())
+            .header("Title", message.title.as_str())
+            .header("Priority", priority)
+            .header("Tags", tag);
+        if let Some(token) = self.config.token() {
+            request = request.header("Authorization", format!("Bearer {token}"));
+        }
+        request.send(message.body.as_str())?;
+        Ok(())
+    }
+}
@@ -0,0 +1,129 @@
+use super::{Error, Message, Sink};
+use crate::config::{CommandSeq, Config};
+use crate::event::Event;
+use crate::{print_log, redact};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use tracing::Level;
+
+/// An external program that receives every event as JSON object on its
+/// standard input. The program is started once per event and reports a failed
+/// delivery with a non-zero exit status.
+pub struct Plugin {
+    name: String,
+    program: Program,
+}
+
+enum Program {
+    Configured(CommandSeq),
+    Discovered(PathBuf),
+}
+
+impl Plugin {
+    /// The plugins of the configuration and the executables of the plugins
+    /// directory, named by their file stem. Configured plugins take
+    /// precedence over discovered ones of the same name.
+    pub fn all_from_config(config: &Config) -> Vec<Self> {
+        let notify = config.notify();
+        let mut plugins: Vec<Self> = notify
+            .plugins()
+            .iter()
+            .map(|(name, command)| Self {
+                name: name.clone(),
+                program: Program::Configured(command.clone()),
+            })
+            .collect();
+        if let Some(dir) = notify.plugins_dir() {
+            match discover(dir) {
+                Ok(found) => {
+                    for (name, path) in found {
+                        if plugins.iter().all(|p| p.name != name) {
+                            plugins.push(Self {
+                                name,
+                                program: Program::Discovered(path),
+                            });
+                        }
+                    }
+                }
+                Err(err) => print_log!(
+                    Level::WARN,
+                    "Failed to read plugins directory {}: {err}",
+                    dir.display()
+                ),
+            }
+        }
+        plugins.sort_by(|a, b| a.name.cmp(&b.name));
+        plugins
+    }
+
+    fn command(&self) -> Command {
+        match &self.program {
+            Program::Configured(command) => command.to_command(),
+            Program::Discovered(path) => crate::priority::command(path),
+        }
+    }
+}
+
+/// The executable files of the directory by name.
+fn discover(dir: &Path) -> std::io::Result<Vec<(String, PathBuf)>> {
+    let mut found = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if !is_executable(&path) {
+            continue;
+        }
+        if let Some(name) = path.file_stem().and_then(|s| s.to_str()) {
+            found.push((name.to_string(), path.clone()));
+        }
+    }
+    Ok(found)
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata()
+        .is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+impl Sink for Plugin {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn send(&self, event: &Event, message: &Message) -> Result<(), Error> {
+        let mut json = event.to_json();
+        json["title"] = redact::redact(&message.title).into();
+        json["message"] = redact::redact(&message.body).into();
+
+        let mut cmd = self.command();
+        cmd.stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped());
+        let mut child = cmd.spawn()?;
+        if let Some(mut stdin) = child.stdin.take() {
+            // A plugin may exit without reading its input.
+            let _ = writeln!(stdin, "{json}");
+        }
+        let output = child.wait_with_output()?;
+        if output.status.success() {
+            return Ok(());
+        }
+        let mut error = format!(
+            "{} exited with {}",
+            cmd.get_program().to_string_lossy(),
+            output.status
+        );
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if !stderr.trim().is_empty() {
+            error = format!("{error}: {}", redact::redact(stderr.trim()));
+        }
+        Err(Error::Plugin(error))
+    }
+}
@@ -0,0 +1,54 @@
+use super::{Error, Message, Sink};
+use crate::config::{self, Config};
+use crate::event::{Event, EventKind};
+use crate::http;
+
+const API_URL: &str = "https://api.pushover.net/1/messages.json";
+const DEFAULT_PRIORITY_SUCCEEDED: i32 = -1;
+const DEFAULT_PRIORITY_FAILED: i32 = 1;
+
+/// Sends the results of locations to Pushover.
+pub struct Pushover {
+    config: config::PushService,
+    agent: ureq::Agent,
+}
+
+impl Pushover {
+    pub fn from_config(config: &Config) -> Option<Self> {
+        let config = config.notify().pushover()?.clone();
+        Some(Self {
+            config,
+            agent: http::agent(),
+        })
+    }
+}
+
+impl Sink for Pushover {
+    fn name(&self) -> &'static str {
+        "pushover"
+    }
+
+    fn send(&self, event: &Event, message: &Message) -> Result<(), Error> {
+        if !super::is_location_result(event) {
+            return Ok(());
+        }
+        let priority = match event.kind {
+            EventKind::Failed => self.config.priority_failed(DEFAULT_PRIORITY_FAILED),
+            _ => self.config.priority_succeeded(DEFAULT_PRIORITY_SUCCEEDED),
+        };
+        let user = self
+            .config
+            .user()
+            .ok_or_else(|| Error::Config("Pushover requires a user key.".to_string()))?;
+
+        let url = self.config.url().unwrap_or(API_URL);
+        self.agent.post(url).send_form([
+            ("token", self.config.token()),
+            ("user", user),
+            ("title", &message.title),
+            ("message", &message.body),
+            ("priority", &priority.to_string()),
+        ])?;
+        Ok(())
+    }
+}
@@ -0,0 +1,60 @@
+use super::{Error, Message, Sink};
+use crate::config::{self, Config};
+use crate::event::Event;
+use crate::{http, template};
+
+/// Sends events to an arbitrary HTTP endpoint.
+pub struct Webhook {
+    config: config::Webhook,
+    agent: ureq::Agent,
+}
+
+impl Webhook {
+    pub fn from_config(config: &Config) -> Option<Self> {
+        let config = config.notify().webhook()?.clone();
+        Some(Self {
+            config,
+            agent: http::agent(),
+        })
+    }
+
+    fn body(&self, event: &Event) -> String {
+        match self.config.body() {
+            Some(body) => template::render(body, |name| event.var(name), escape_json),
+            None => event.to_json().to_string(),
+        }
+    }
+}
+
+impl Sink for Webhook {
+    fn name(&self) -> &'static str {
+        "webhook"
+    }
+
+    fn send(&self, event: &Event, _message: &Message) -> Result<(), Error> {
+        let method: ureq::http::Method = self
+            .config
+            .method()
+            .to_uppercase()
+            .parse()
+            .map_err(|_| Error::Config(format!("Invalid method {}.", self.config.method())))?;
+        let mut request = ureq::http::Request::builder()
+            .method(method)
+            .uri(self.config.url())
+            .header("Content-Type", "application/json");
+        for (k, v) in self.config.headers() {
+            request = request.header(k, v);
+        }
+        let request = request
+            .body(self.body(event))
+            .map_err(|e| Error::Config(e.to_string()))?;
+        self.agent.run(request)?;
+        Ok(())
+    }
+}
+
+/// Escapes a value for use inside a JSON string literal.
+fn escape_json(value: &str) -> String {
+    let quoted = serde_json::Value::from(value).to_string();
+    quoted[1..quoted.len() - 1].to_string()
+}
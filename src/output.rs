@@ -0,0 +1,94 @@
+use crate::cli::OutputFormat;
+use crate::event::{Event, Operation};
+use crate::redact;
+use chrono::{DateTime, Local};
+use std::collections::VecDeque;
+use std::io::Write;
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+use tracing::Level;
+
+/// The number of recent messages that are kept, see [`keep_recent`].
+const RECENT_SIZE: usize = 100;
+
+static FORMAT: OnceLock<OutputFormat> = OnceLock::new();
+static RECENT: OnceLock<Mutex<VecDeque<Message>>> = OnceLock::new();
+
+/// A kept message with its time and level.
+pub type Message = (DateTime<Local>, Level, String);
+
+pub fn init(format: OutputFormat) {
+    FORMAT.get_or_init(|| format);
+}
+
+/// Whether structured records are written instead of human-readable text.
+pub fn is_json() -> bool {
+    FORMAT.get() == Some(&OutputFormat::Json)
+}
+
+/// Writes an event record.
+pub fn event(event: &Event) {
+    if is_json() {
+        emit("event", event.to_json());
+    }
+}
+
+/// Keeps the recent messages of level info and above from now on, e.g. to
+/// show them on the dashboard.
+pub fn keep_recent() {
+    RECENT.get_or_init(|| Mutex::new(VecDeque::new()));
+}
+
+/// The kept recent messages, the newest first.
+pub fn recent() -> Vec<Message> {
+    RECENT.get().map_or_else(Vec::new, |recent| {
+        let recent = recent.lock().unwrap_or_else(|e| e.into_inner());
+        recent.iter().cloned().collect()
+    })
+}
+
+/// Writes a record for a warning or error message. Messages of other levels
+/// are only part of the human-readable output.
+pub fn log(level: Level, message: &str) {
+    if let Some(recent) = RECENT.get() {
+        if level <= Level::INFO && !message.is_empty() {
+            let mut recent = recent.lock().unwrap_or_else(|e| e.into_inner());
+            recent.push_front((Local::now(), level, message.to_string()));
+            recent.truncate(RECENT_SIZE);
+        }
+    }
+    if is_json() && level <= Level::WARN {
+        let level = level.as_str().to_ascii_lowercase();
+        emit(
+            "log",
+            serde_json::json!({ "level": level, "message": message }),
+        );
+    }
+}
+
+/// Writes the record that concludes a run.
+pub fn summary(operation: Operation, duration: Duration, errors: &[String]) {
+    if is_json() {
+        emit(
+            "summary",
+            serde_json::json!({
+                "operation": operation.as_str(),
+                "success": errors.is_empty(),
+                "duration": duration.as_secs_f64(),
+                "errors": errors,
+            }),
+        );
+    }
+}
+
+/// Writes a single line to stdout. The record type and a timestamp are added
+/// to the given JSON object.
+fn emit(kind: &str, mut record: serde_json::Value) {
+    if let Some(object) = record.as_object_mut() {
+        object.insert("type".to_string(), kind.into());
+        object.insert("time".to_string(), chrono::Utc::now().to_rfc3339().into());
+    }
+    let mut stdout = std::io::stdout().lock();
+    let _ = writeln!(stdout, "{}", redact::redact(&record.to_string()));
+    let _ = stdout.flush();
+}
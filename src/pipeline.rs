@@ -0,0 +1,221 @@
+use crate::cli::{BackupArgs, CheckArgs, CopyArgs, ForgetArgs, RunArgs};
+use crate::config::{Config, LocationRepo, Name};
+use crate::event::Operation;
+use crate::exit::{self, Status};
+use crate::print_log;
+use crate::report::Report;
+use crate::signal;
+use crate::state;
+use anyhow::{Result, anyhow, bail};
+use chrono::Local;
+use std::collections::HashSet;
+use tracing::Level;
+
+/// Runs the steps of a configured pipeline, or the default maintenance cycle
+/// if no pipeline is given. Later steps still run after a step has failed, so
+/// that e.g. a failed copy does not prevent the check of the repositories.
+pub fn run(config: &Config, args: &RunArgs, report: &mut Report) -> Result<()> {
+    match args.pipeline() {
+        Some(name) => run_pipeline(config, name, args, report),
+        None => run_cycle(config, args, report),
+    }
+}
+
+fn run_pipeline(config: &Config, name: &Name, args: &RunArgs, report: &mut Report) -> Result<()> {
+    let Some(steps) = config.pipelines().get(name) else {
+        bail!("Pipeline {name} is not defined.");
+    };
+    let selection = args.selected_locations();
+
+    let mut failed = Vec::new();
+    for step in steps {
+        if signal::interrupted() {
+            break;
+        }
+        print_log!(Level::INFO, "Pipeline step {step} ...");
+        let restic_args = step.args().to_vec();
+        let result = match (step.operation(), step.target()) {
+            (Operation::Backup, _) => {
+                let backup_args = BackupArgs::new(selection.clone(), args.dry_run())
+                    .with_restic_args(restic_args);
+                let result = crate::backup(config, &backup_args, report);
+                if !signal::interrupted() {
+                    replicate(config, selection, args.dry_run(), report, &mut failed);
+                }
+                result
+            }
+            (Operation::Forget, _) => {
+                let args = ForgetArgs::new(selection.clone(), args.dry_run())
+                    .with_restic_args(restic_args);
+                crate::forget(config, &args, report)
+            }
+            (Operation::Check, _) => {
+                if args.dry_run() {
+                    print_log!(Level::INFO, "Skipped check in dry run.");
+                    continue;
+                }
+                let args = CheckArgs::new(selection.clone()).with_restic_args(restic_args);
+                crate::check(config, &args, report)
+            }
+            (Operation::Copy, Some(target)) => {
+                if args.dry_run() {
+                    print_log!(Level::INFO, "Skipped copy in dry run.");
+                    continue;
+                }
+                let args =
+                    CopyArgs::new(selection.clone(), target.clone()).with_restic_args(restic_args);
+                crate::copy(config, &args, report)
+            }
+            (Operation::Copy, None) => unreachable!("copy steps have a target"),
+            (Operation::Maintenance | Operation::Status, _) => {
+                unreachable!("{} is no pipeline step", step.operation())
+            }
+        };
+        if let Err(err) = result {
+            print_log!(Level::ERROR, "Pipeline step {step} failed: {err}");
+            failed.push((step.to_string(), crate::error_status(&err)));
+        }
+    }
+
+    conclude(&format!("Pipeline {name}"), &failed)
+}
+
+/// Runs the default maintenance cycle: a backup of all selected locations,
+/// followed by a forget of the locations with a keep policy and a check of the
+/// repositories whose check interval has elapsed.
+fn run_cycle(config: &Config, args: &RunArgs, report: &mut Report) -> Result<()> {
+    let selection = args.selected_locations();
+    let mut failed = Vec::new();
+
+    print_log!(Level::INFO, "Maintenance cycle step backup ...");
+    let backup_args = BackupArgs::new(selection.clone(), args.dry_run());
+    if let Err(err) = crate::backup(config, &backup_args, report) {
+        print_log!(Level::ERROR, "Maintenance cycle step backup failed: {err}");
+        failed.push(("backup".to_string(), crate::error_status(&err)));
+    }
+    if !signal::interrupted() {
+        replicate(config, selection, args.dry_run(), report, &mut failed);
+    }
+
+    // Locations that forget after every backup have already been forgotten.
+    let forget_selection = filter_selection(config, selection, |location_name| {
+        let has_policy = |repo_name| {
+            crate::get_repo_forget_options(location_name, repo_name, config).has_policy()
+        };
+        config
+            .locations()
+            .get(location_name)
+            .is_some_and(|location| location.repos().iter().any(has_policy))
+            && !crate::get_backup_options(location_name, config).forget()
+    });
+    if !forget_selection.is_empty() && !signal::interrupted() {
+        print_log!(Level::INFO, "Maintenance cycle step forget ...");
+        let forget_args = ForgetArgs::new(forget_selection, args.dry_run());
+        if let Err(err) = crate::forget(config, &forget_args, report) {
+            print_log!(Level::ERROR, "Maintenance cycle step forget failed: {err}");
+            failed.push(("forget".to_string(), crate::error_status(&err)));
+        }
+    }
+
+    let check_selection = due_checks(config, selection)?;
+    if !check_selection.is_empty() && !signal::interrupted() {
+        print_log!(Level::INFO, "Maintenance cycle step check ...");
+        if args.dry_run() {
+            print_log!(Level::INFO, "Skipped check in dry run.");
+        } else if let Err(err) = crate::check(config, &CheckArgs::new(check_selection), report) {
+            print_log!(Level::ERROR, "Maintenance cycle step check failed: {err}");
+            failed.push(("check".to_string(), crate::error_status(&err)));
+        }
+    }
+
+    conclude("Maintenance cycle", &failed)
+}
+
+/// Copies the new snapshots of the backups to the replicas of their
+/// repositories. Snapshots of failed backups have not been recorded, so the
+/// replication also runs after a failed backup step.
+fn replicate(
+    config: &Config,
+    selection: &[LocationRepo],
+    dry_run: bool,
+    report: &mut Report,
+    failed: &mut Vec<(String, Status)>,
+) {
+    if let Err(err) = crate::replicate(config, selection, dry_run, report) {
+        print_log!(Level::ERROR, "Replication failed: {err}");
+        failed.push(("replicate".to_string(), crate::error_status(&err)));
+    }
+}
+
+/// The repositories of the selection whose check is due. Repositories without
+/// check interval are not checked by the maintenance cycle.
+fn due_checks(config: &Config, selection: &[LocationRepo]) -> Result<Vec<LocationRepo>> {
+    let state = state::load().map_err(|e| anyhow!("Failed to read state file: {e}"))?;
+    let now = Local::now();
+
+    let mut due = Vec::new();
+    let mut seen = HashSet::new();
+    for (location_name, repo_names) in crate::resolve_selection(selection, config)? {
+        let check_opts = crate::get_check_options(&location_name, config);
+        for repo_name in repo_names {
+            let Some(repo) = config.repos().get(&repo_name) else {
+                continue;
+            };
+            if !seen.insert(repo_name.clone()) {
+                continue;
+            }
+            let Some(interval) = repo.check().interval().or(check_opts.interval()) else {
+                continue;
+            };
+            let next_check = state
+                .repo(&repo_name)
+                .last_check()
+                .map(|t| t + chrono::Duration::from_std(interval.into()).unwrap_or_default());
+            match next_check {
+                Some(next_check) if next_check > now => print_log!(
+                    Level::INFO,
+                    "Check of repository {repo_name} is due at {}.",
+                    next_check.format("%Y-%m-%d %H:%M")
+                ),
+                _ => due.push(LocationRepo::new(location_name.clone(), Some(repo_name))),
+            }
+        }
+    }
+    Ok(due)
+}
+
+/// The part of a selection (or of all locations, if nothing is selected) whose
+/// locations satisfy the predicate. The result is empty if no location does.
+fn filter_selection(
+    config: &Config,
+    selection: &[LocationRepo],
+    predicate: impl Fn(&Name) -> bool,
+) -> Vec<LocationRepo> {
+    if selection.is_empty() {
+        config
+            .locations()
+            .keys()
+            .filter(|l| predicate(l))
+            .map(|l| l.clone().into())
+            .collect()
+    } else {
+        selection
+            .iter()
+            .filter(|s| predicate(s.location()))
+            .cloned()
+            .collect()
+    }
+}
+
+/// Fails with the combined exit status of the failed steps, if any.
+pub fn conclude(label: &str, failed: &[(String, Status)]) -> Result<()> {
+    if failed.is_empty() {
+        return Ok(());
+    }
+    let steps: Vec<_> = failed.iter().map(|(s, _)| format!("\"{s}\"")).collect();
+    Err(exit::Error::new(
+        Status::combine(failed.iter().map(|(_, s)| *s)),
+        format!("{label} failed in step(s) {}.", steps.join(", ")),
+    )
+    .into())
+}
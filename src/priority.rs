@@ -0,0 +1,84 @@
+use crate::config::{Priority, PriorityLevel};
+use std::ffi::OsStr;
+use std::process::Command;
+use std::sync::OnceLock;
+
+static PRIORITY: OnceLock<Priority> = OnceLock::new();
+
+/// Runs subsequent restic and hook commands at the given priority.
+pub fn set(priority: Priority) {
+    let _ = PRIORITY.set(priority);
+}
+
+/// A command that runs the program at the configured priority.
+///
+/// On Unix, the program is wrapped in `nice` and, on Linux, `ionice`. Both
+/// replace themselves with the program, so that signals still reach it. On
+/// Windows, the process is created with a lower priority class. The I/O
+/// priority is not supported there.
+pub fn command<S: AsRef<OsStr>>(program: S) -> Command {
+    let priority = PRIORITY.get().cloned().unwrap_or_default();
+    #[cfg(unix)]
+    {
+        match wrapper(&priority).split_first() {
+            Some((wrapper, args)) => {
+                let mut cmd = Command::new(wrapper);
+                cmd.args(args);
+                cmd.arg(program);
+                cmd
+            }
+            None => Command::new(program),
+        }
+    }
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        use windows_sys::Win32::System::Threading::{
+            BELOW_NORMAL_PRIORITY_CLASS, IDLE_PRIORITY_CLASS,
+        };
+
+        let mut cmd = Command::new(program);
+        match priority.cpu() {
+            PriorityLevel::Normal => {}
+            PriorityLevel::Low => {
+                cmd.creation_flags(BELOW_NORMAL_PRIORITY_CLASS);
+            }
+            PriorityLevel::Idle => {
+                cmd.creation_flags(IDLE_PRIORITY_CLASS);
+            }
+        }
+        cmd
+    }
+}
+
+/// The program that a command of [`command`] runs, i.e. without the wrappers.
+pub fn program(cmd: &Command) -> &OsStr {
+    #[cfg(unix)]
+    {
+        let priority = PRIORITY.get().cloned().unwrap_or_default();
+        let len = wrapper(&priority).len();
+        if len > 0 {
+            return cmd.get_args().nth(len - 1).unwrap_or(cmd.get_program());
+        }
+    }
+    cmd.get_program()
+}
+
+/// The commands, with their arguments, that lower the priority of the program
+/// that follows them.
+#[cfg(unix)]
+fn wrapper(priority: &Priority) -> Vec<&'static str> {
+    let mut wrapper = Vec::new();
+    match priority.cpu() {
+        PriorityLevel::Normal => {}
+        PriorityLevel::Low => wrapper.extend(["nice", "-n", "10"]),
+        PriorityLevel::Idle => wrapper.extend(["nice", "-n", "19"]),
+    }
+    #[cfg(target_os = "linux")]
+    match priority.io() {
+        PriorityLevel::Normal => {}
+        PriorityLevel::Low => wrapper.extend(["ionice", "-c", "2", "-n", "7"]),
+        PriorityLevel::Idle => wrapper.extend(["ionice", "-c", "3"]),
+    }
+    wrapper
+}
@@ -0,0 +1,139 @@
+use crate::config::{self, OnUnreachable, Probe};
+use crate::print_log;
+use crate::signal;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant};
+use thiserror::Error;
+use tracing::Level;
+
+const RETRY_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Probes whether the server of a repository can be reached and handles an
+/// unreachable one as configured: waits until it can be reached, skips the
+/// repository or fails.
+pub fn check(name: &str, path: &str, probe: &Probe) -> Result<(), Error> {
+    let wait: Duration = probe.wait().into();
+    let started = Instant::now();
+    let mut waiting = false;
+    loop {
+        let reason = match reach(path, probe) {
+            Ok(()) => return Ok(()),
+            Err(reason) => reason,
+        };
+        match probe.on_unreachable() {
+            OnUnreachable::Skip => return Err(Error::Skipped(name.to_string(), reason)),
+            OnUnreachable::Fail => return Err(Error::Unreachable(name.to_string(), reason)),
+            OnUnreachable::Wait if started.elapsed() >= wait => {
+                return Err(Error::Timeout(name.to_string(), probe.wait()));
+            }
+            OnUnreachable::Wait => {
+                if !waiting {
+                    print_log!(
+                        Level::INFO,
+                        "Repository {name} is unreachable ({reason}). Waiting ..."
+                    );
+                    waiting = true;
+                }
+                if signal::sleep(RETRY_INTERVAL.min(wait.saturating_sub(started.elapsed()))) {
+                    return Err(Error::Interrupted(name.to_string()));
+                }
+            }
+        }
+    }
+}
+
+fn reach(path: &str, probe: &Probe) -> Result<(), String> {
+    let timeout: Duration = probe.timeout().into();
+    if let Some(url) = probe.url() {
+        return head(url, timeout);
+    }
+    let address = probe
+        .address()
+        .map(str::to_string)
+        .or_else(|| address(path))
+        .ok_or_else(|| "unknown address".to_string())?;
+    connect(&address, timeout)
+}
+
+/// Opens a TCP connection to the address, e.g. `nas:8000`.
+fn connect(address: &str, timeout: Duration) -> Result<(), String> {
+    let addrs = address
+        .to_socket_addrs()
+        .map_err(|e| format!("{address}: {e}"))?;
+    let mut error = format!("{address}: no addresses");
+    for addr in addrs {
+        match TcpStream::connect_timeout(&addr, timeout) {
+            Ok(_) => return Ok(()),
+            Err(e) => error = format!("{address}: {e}"),
+        }
+    }
+    Err(error)
+}
+
+/// Sends a HEAD request to the URL. Every response counts, even an error
+/// status, because it proves that the server is up.
+fn head(url: &str, timeout: Duration) -> Result<(), String> {
+    let agent: ureq::Agent = ureq::Agent::config_builder()
+        .timeout_global(Some(timeout))
+        .http_status_as_error(false)
+        .build()
+        .into();
+    agent
+        .head(url)
+        .call()
+        .map(|_| ())
+        .map_err(|e| format!("{url}: {e}"))
+}
+
+/// The TCP address of the server of a repository path, if it can be derived,
+/// e.g. `nas:8000` for `rest:http://nas:8000/`. Supported are the REST, S3 and
+/// SFTP backends.
+pub fn address(path: &str) -> Option<String> {
+    let (scheme, rest) = path.split_once(':')?;
+    match scheme {
+        "rest" | "s3" => {
+            if let Some(rest) = rest.strip_prefix("https://") {
+                host_port(rest, 443)
+            } else if let Some(rest) = rest.strip_prefix("http://") {
+                host_port(rest, 80)
+            } else {
+                host_port(rest, 443)
+            }
+        }
+        // sftp://user@host:port//path or sftp:user@host:/path
+        "sftp" => match rest.strip_prefix("//") {
+            Some(rest) => host_port(rest, 22),
+            None => host_port(rest.split_once(':')?.0, 22),
+        },
+        _ => None,
+    }
+}
+
+fn host_port(rest: &str, default_port: u16) -> Option<String> {
+    let authority = rest.split('/').next()?;
+    let authority = authority
+        .rsplit_once('@')
+        .map_or(authority, |(_, host)| host);
+    if authority.is_empty() {
+        return None;
+    }
+    // The port follows the closing bracket of IPv6 addresses, e.g. [::1]:8000.
+    let host_end = authority.rfind(']').unwrap_or(0);
+    if authority[host_end..].contains(':') {
+        Some(authority.to_string())
+    } else {
+        Some(format!("{authority}:{default_port}"))
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Repository {0} is unreachable ({1}).")]
+    Unreachable(String, String),
+    #[error("Repository {0} is unreachable ({1}). Skip.")]
+    Skipped(String, String),
+    #[error("Repository {0} has not become reachable within {1}.")]
+    Timeout(String, config::Duration),
+    #[error("Interrupted while waiting for repository {0}.")]
+    Interrupted(String),
+}
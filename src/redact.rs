@@ -0,0 +1,129 @@
+use std::borrow::Cow;
+use std::io::Write;
+use std::sync::{OnceLock, RwLock};
+
+/// Names of environment variables whose values are always masked.
+const DEFAULT_PATTERNS: [&str; 4] = ["*PASSWORD*", "*SECRET*", "*TOKEN*", "*_KEY"];
+const MASK: &str = "***";
+
+/// Patterns configured in addition to the default patterns.
+static PATTERNS: OnceLock<Vec<String>> = OnceLock::new();
+static SECRETS: RwLock<Vec<String>> = RwLock::new(Vec::new());
+
+/// Sets additional patterns of environment variable names whose values are
+/// masked and registers the matching variables of the current process.
+pub fn init(patterns: &[String]) {
+    PATTERNS.get_or_init(|| patterns.to_vec());
+    add_env(std::env::vars());
+}
+
+/// Registers a value that must not appear in any output.
+pub fn add_secret(secret: &str) {
+    if secret.is_empty() {
+        return;
+    }
+    let mut secrets = SECRETS.write().unwrap_or_else(|e| e.into_inner());
+    if !secrets.iter().any(|s| s == secret) {
+        secrets.push(secret.to_string());
+        // Longer secrets first, so that secrets containing other secrets are
+        // masked completely.
+        secrets.sort_by_key(|s| std::cmp::Reverse(s.len()));
+    }
+}
+
+/// Registers the values of all variables whose names match a pattern.
+pub fn add_env<K: AsRef<str>, V: AsRef<str>>(vars: impl IntoIterator<Item = (K, V)>) {
+    let extra = PATTERNS.get().map(Vec::as_slice).unwrap_or_default();
+    let patterns = DEFAULT_PATTERNS
+        .iter()
+        .copied()
+        .chain(extra.iter().map(String::as_str));
+    for (name, value) in vars {
+        let name = name.as_ref().to_ascii_uppercase();
+        if patterns
+            .clone()
+            .any(|p| matches(&p.to_ascii_uppercase(), &name))
+        {
+            add_secret(value.as_ref());
+        }
+    }
+}
+
+/// Replaces all registered secrets in the text.
+pub fn redact(text: &str) -> Cow<'_, str> {
+    let secrets = SECRETS.read().unwrap_or_else(|e| e.into_inner());
+    let mut text = Cow::Borrowed(text);
+    for secret in secrets.iter() {
+        if text.contains(secret.as_str()) {
+            text = Cow::Owned(text.replace(secret.as_str(), MASK));
+        }
+    }
+    text
+}
+
+/// A writer that masks secrets in everything written to the inner writer.
+///
+/// Output is passed on line by line (lines end with a newline or a carriage
+/// return), so that secrets and characters split across writes are still
+/// recognized. An incomplete last line is passed on when the writer is flushed
+/// or dropped.
+pub struct Writer<W: Write> {
+    inner: W,
+    pending: Vec<u8>,
+}
+
+impl<W: Write> Writer<W> {
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            pending: Vec::new(),
+        }
+    }
+
+    fn write_redacted(&mut self, bytes: &[u8]) -> std::io::Result<()> {
+        let text = String::from_utf8_lossy(bytes);
+        self.inner.write_all(redact(&text).as_bytes())
+    }
+}
+
+impl<W: Write> Write for Writer<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.pending.extend_from_slice(buf);
+        if let Some(end) = self.pending.iter().rposition(|&b| b == b'\n' || b == b'\r') {
+            let rest = self.pending.split_off(end + 1);
+            let lines = std::mem::replace(&mut self.pending, rest);
+            self.write_redacted(&lines)?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        let pending = std::mem::take(&mut self.pending);
+        if !pending.is_empty() {
+            self.write_redacted(&pending)?;
+        }
+        self.inner.flush()
+    }
+}
+
+impl<W: Write> Drop for Writer<W> {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+/// Matches a name against a pattern in which `*` matches any sequence of
+/// characters.
+fn matches(pattern: &str, name: &str) -> bool {
+    match pattern.split_once('*') {
+        None => pattern == name,
+        Some((prefix, rest)) => {
+            let Some(name) = name.strip_prefix(prefix) else {
+                return false;
+            };
+            (0..=name.len())
+                .filter(|&i| name.is_char_boundary(i))
+                .any(|i| matches(rest, &name[i..]))
+        }
+    }
+}
@@ -0,0 +1,52 @@
+use crate::HookEnv;
+use crate::config::{Hook, Name, RepoHooks};
+use std::cell::RefCell;
+
+/// A repository that has been used in the current run.
+struct Used {
+    name: Name,
+    after: Vec<Hook>,
+    /// The context of the first use.
+    env: HookEnv,
+    /// The failure of the BEFORE hooks, if any.
+    error: Option<String>,
+}
+
+thread_local! {
+    // Every run of the daemon mode has its own thread, so that runs do not
+    // see the repositories of each other.
+    static USED: RefCell<Vec<Used>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Runs the BEFORE hooks of a repository on its first use in the current run.
+/// Later uses fail with the same error without running the hooks again.
+pub fn enter(name: &Name, hooks: &RepoHooks, env: HookEnv) -> Result<(), String> {
+    if let Some(error) = USED.with_borrow(|used| {
+        used.iter()
+            .find(|u| &u.name == name)
+            .map(|u| u.error.clone())
+    }) {
+        return error.map_or(Ok(()), Err);
+    }
+
+    let result = crate::run_required_hooks("BEFORE", hooks.before(), &env);
+    USED.with_borrow_mut(|used| {
+        used.push(Used {
+            name: name.clone(),
+            after: hooks.after().to_vec(),
+            env,
+            error: result.clone().err(),
+        })
+    });
+    result
+}
+
+/// Runs the AFTER hooks of all repositories used in the current run, in
+/// reverse order of their first use. Their environment tells whether the run
+/// succeeded.
+pub fn leave_all(success: bool) {
+    let used = USED.take();
+    for repo in used.iter().rev() {
+        crate::run_final_hooks("AFTER", &repo.after, &repo.env.concluded(success));
+    }
+}
@@ -0,0 +1,373 @@
+use crate::config::{self, Name};
+use crate::event::{Event, Operation};
+use crate::print_log;
+use crate::redact;
+use crate::style::{self, Color};
+use chrono::{DateTime, Local};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+use tracing::Level;
+
+static FILE: OnceLock<PathBuf> = OnceLock::new();
+
+/// Writes the report of every subsequent run to the given file.
+pub fn set_file(path: PathBuf) {
+    let _ = FILE.set(path);
+}
+
+/// The result of an operation for a repository of a location.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Outcome {
+    Ok,
+    /// The operation succeeded with warnings, e.g. some files could not be
+    /// read by a backup.
+    Warn,
+    Fail,
+    Skipped,
+}
+
+impl Outcome {
+    const ALL: [Outcome; 4] = [Outcome::Ok, Outcome::Warn, Outcome::Fail, Outcome::Skipped];
+
+    /// The outcome of an operation without warnings.
+    pub fn of<T, E>(result: &Result<T, E>) -> Self {
+        match result {
+            Ok(_) => Outcome::Ok,
+            Err(_) => Outcome::Fail,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Outcome::Ok => "OK",
+            Outcome::Warn => "WARN",
+            Outcome::Fail => "FAIL",
+            Outcome::Skipped => "SKIPPED",
+        }
+    }
+
+    fn color(self) -> Option<Color> {
+        match self {
+            Outcome::Ok => Some(Color::Green),
+            Outcome::Warn => Some(Color::Yellow),
+            Outcome::Fail => Some(Color::Red),
+            Outcome::Skipped => None,
+        }
+    }
+}
+
+/// The result of an operation for a repository of a location.
+#[derive(Clone, Debug)]
+pub struct Item {
+    pub operation: Operation,
+    /// The location, unless the operation concerns the whole repository.
+    pub location: Option<Name>,
+    pub repo: Name,
+    pub outcome: Outcome,
+    pub duration: Option<Duration>,
+    pub bytes_added: Option<u64>,
+    pub error: Option<String>,
+}
+
+impl Item {
+    fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "location": self.location.as_ref().map(Name::as_str),
+            "repo": self.repo.as_str(),
+            "operation": self.operation.as_str(),
+            "status": self.outcome.label().to_ascii_lowercase(),
+            "duration": self.duration.map(|d| d.as_secs_f64()),
+            "bytes_added": self.bytes_added,
+            "error": self.error,
+        })
+    }
+}
+
+/// Collects the results of all operations of a run.
+#[derive(Debug)]
+pub struct Report {
+    /// The name of the run for use in messages, e.g. "Backup".
+    label: String,
+    started: DateTime<Local>,
+    started_instant: Instant,
+    items: Vec<Item>,
+    errors: Vec<String>,
+}
+
+impl Report {
+    pub fn new(label: impl Into<String>) -> Self {
+        Self {
+            label: label.into(),
+            started: Local::now(),
+            started_instant: Instant::now(),
+            items: Vec::new(),
+            errors: Vec::new(),
+        }
+    }
+
+    /// Adds the result described by a finished event of a repository.
+    pub fn add(&mut self, event: &Event, outcome: Outcome) {
+        let Some(repo) = &event.repo else {
+            return;
+        };
+        self.items.push(Item {
+            operation: event.operation,
+            location: event.location.clone(),
+            repo: repo.clone(),
+            outcome,
+            duration: event.duration,
+            bytes_added: event.bytes_added,
+            error: event.error.clone(),
+        });
+    }
+
+    /// Adds an operation that has not been run or has failed before restic
+    /// could be started.
+    pub fn add_not_run(
+        &mut self,
+        operation: Operation,
+        location: Option<&Name>,
+        repo: &Name,
+        outcome: Outcome,
+    ) {
+        self.items.push(Item {
+            operation,
+            location: location.cloned(),
+            repo: repo.clone(),
+            outcome,
+            duration: None,
+            bytes_added: None,
+            error: None,
+        });
+    }
+
+    /// Adds the messages of failures that concluded an operation.
+    pub fn add_errors(&mut self, errors: &[String]) {
+        self.errors.extend_from_slice(errors);
+    }
+
+    /// The number of results with the given outcome.
+    pub fn count(&self, outcome: Outcome) -> usize {
+        self.items.iter().filter(|i| i.outcome == outcome).count()
+    }
+
+    /// Prints the summary of the run and writes the report file, if any.
+    pub fn finish(&self) {
+        let duration = self.started_instant.elapsed();
+        self.print(duration);
+        if let Err(err) = self.save(duration) {
+            print_log!(Level::WARN, "Failed to write report file: {err}");
+        }
+    }
+
+    /// A single line that concludes the run, e.g. "Backup failed: 3 OK,
+    /// 1 FAIL (took 5m)."
+    fn verdict(&self, duration: Duration) -> String {
+        let result = if self.count(Outcome::Fail) > 0 {
+            "failed"
+        } else if self.count(Outcome::Warn) > 0 {
+            "finished with warnings"
+        } else {
+            "succeeded"
+        };
+        let counts: Vec<_> = Outcome::ALL
+            .iter()
+            .map(|o| (o.label(), self.count(*o)))
+            .filter(|(_, n)| *n > 0)
+            .map(|(label, n)| format!("{n} {label}"))
+            .collect();
+        format!(
+            "{} {result}: {} (took {}).",
+            self.label,
+            counts.join(", "),
+            config::Duration::from(duration)
+        )
+    }
+
+    /// Prints a table of all results and the verdict of the run. Runs of a
+    /// single repository are not summarized.
+    fn print(&self, duration: Duration) {
+        if self.items.len() < 2 {
+            return;
+        }
+
+        let header = [
+            "LOCATION",
+            "REPOSITORY",
+            "OPERATION",
+            "STATUS",
+            "DURATION",
+            "ADDED",
+        ];
+        let rows: Vec<[String; 6]> = self
+            .items
+            .iter()
+            .map(|item| {
+                [
+                    item.location
+                        .as_ref()
+                        .map(Name::to_string)
+                        .unwrap_or_default(),
+                    item.repo.to_string(),
+                    item.operation.to_string(),
+                    item.outcome.label().to_string(),
+                    item.duration
+                        .map(|d| config::Duration::from(d).to_string())
+                        .unwrap_or_default(),
+                    item.bytes_added.map(format_size).unwrap_or_default(),
+                ]
+            })
+            .collect();
+        let mut widths = header.map(str::len);
+        for row in &rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.len());
+            }
+        }
+
+        // Cells are padded before they are painted, so that escape codes do
+        // not affect the alignment.
+        let line = |cells: [String; 6], outcome: Option<Outcome>| {
+            let cells: Vec<_> = cells
+                .iter()
+                .zip(widths)
+                .enumerate()
+                .map(|(i, (cell, width))| {
+                    let cell = format!("{cell:width$}");
+                    match outcome.and_then(Outcome::color) {
+                        Some(color) if i == 3 => style::paint(cell, color),
+                        _ => cell,
+                    }
+                })
+                .collect();
+            cells.join("  ").trim_end().to_string()
+        };
+
+        print_log!(Level::INFO, "");
+        print_log!(Level::INFO, "{}", line(header.map(String::from), None));
+        for (row, item) in rows.into_iter().zip(&self.items) {
+            print_log!(Level::INFO, "{}", line(row, Some(item.outcome)));
+        }
+        print_log!(Level::INFO, "{}", self.verdict(duration));
+    }
+
+    /// Writes the report to the configured file, if any. Files ending in
+    /// `.html` are rendered as HTML page, all others are written as JSON.
+    fn save(&self, duration: Duration) -> Result<(), std::io::Error> {
+        let Some(path) = FILE.get() else {
+            return Ok(());
+        };
+        let content = if is_html(path) {
+            self.to_html(duration)
+        } else {
+            let json = self.to_json(duration);
+            serde_json::to_string_pretty(&json).map_err(std::io::Error::other)?
+        };
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, redact::redact(&content).as_bytes())
+    }
+
+    fn to_json(&self, duration: Duration) -> serde_json::Value {
+        let counts: serde_json::Map<_, _> = Outcome::ALL
+            .iter()
+            .map(|o| (o.label().to_ascii_lowercase(), self.count(*o).into()))
+            .collect();
+        serde_json::json!({
+            "run": self.label,
+            "started": self.started.to_rfc3339(),
+            "duration": duration.as_secs_f64(),
+            "success": self.errors.is_empty(),
+            "verdict": self.verdict(duration),
+            "counts": counts,
+            "items": self.items.iter().map(Item::to_json).collect::<Vec<_>>(),
+            "errors": self.errors,
+        })
+    }
+
+    fn to_html(&self, duration: Duration) -> String {
+        let title = format!(
+            "{} report {}",
+            self.label,
+            self.started.format("%Y-%m-%d %H:%M:%S")
+        );
+        let mut html = String::new();
+        html.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+        html.push_str(&format!("<title>{}</title>\n", escape_html(&title)));
+        html.push_str(HTML_STYLE);
+        html.push_str("</head>\n<body>\n");
+        html.push_str(&format!("<h1>{}</h1>\n", escape_html(&title)));
+        html.push_str(&format!(
+            "<p>{}</p>\n",
+            escape_html(&self.verdict(duration))
+        ));
+        html.push_str("<table>\n<tr><th>Location</th><th>Repository</th><th>Operation</th><th>Status</th><th>Duration</th><th>Added</th></tr>\n");
+        for item in &self.items {
+            html.push_str(&format!(
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td class=\"{}\">{}</td><td>{}</td><td>{}</td></tr>\n",
+                escape_html(item.location.as_ref().map_or("", Name::as_str)),
+                escape_html(item.repo.as_str()),
+                item.operation,
+                item.outcome.label().to_ascii_lowercase(),
+                item.outcome.label(),
+                item.duration
+                    .map(|d| config::Duration::from(d).to_string())
+                    .unwrap_or_default(),
+                item.bytes_added.map(format_size).unwrap_or_default(),
+            ));
+        }
+        html.push_str("</table>\n");
+        if !self.errors.is_empty() {
+            html.push_str("<h2>Errors</h2>\n");
+            for error in &self.errors {
+                html.push_str(&format!("<pre>{}</pre>\n", escape_html(error)));
+            }
+        }
+        html.push_str("</body>\n</html>\n");
+        html
+    }
+}
+
+/// Formats a number of bytes with binary units as restic does, e.g. `1.2 MiB`.
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = UNITS[0];
+    for next in &UNITS[1..] {
+        if value < 1024.0 {
+            break;
+        }
+        value /= 1024.0;
+        unit = next;
+    }
+    format!("{value:.1} {unit}")
+}
+
+pub const HTML_STYLE: &str = "<style>
+body { font-family: sans-serif; }
+table { border-collapse: collapse; }
+th, td { padding: 0.2em 0.8em; text-align: left; border-bottom: 1px solid #ddd; }
+.ok { color: #2e7d32; }
+.warn { color: #ef6c00; }
+.fail { color: #c62828; font-weight: bold; }
+pre { background: #f5f5f5; padding: 0.5em; }
+</style>
+";
+
+fn is_html(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("html") || e.eq_ignore_ascii_case("htm"))
+}
+
+pub fn escape_html(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -1,47 +1,164 @@
 use crate::ENV_PREFIX;
+use crate::config;
 use crate::config::BackupOptions;
+use crate::config::CheckOptions;
 use crate::config::ForgetOptions;
+use crate::config::KeyringEntry;
 use crate::config::Name;
+use crate::config::Retry;
+use crate::config::Source;
+use crate::config::Timeouts;
+use crate::exit;
+use crate::print_log;
+use crate::redact;
 use crate::run;
+use crate::signal;
+use chrono::DateTime;
+use chrono::FixedOffset;
+use serde::Deserialize;
+use std::cell::RefCell;
 use std::collections::HashMap;
-use std::ffi::OsStr;
 use std::ffi::OsString;
 use std::path::Path;
 use std::path::PathBuf;
 use std::process::Command;
 use std::process::ExitStatus;
+use std::process::Output;
+use std::process::Stdio;
 use thiserror::Error;
+use tracing::Level;
 
 const BACKUP_READ_ERROR_CODE: i32 = 3;
+const REPOSITORY_NOT_FOUND_CODE: i32 = 10;
+const REPOSITORY_LOCKED_CODE: i32 = 11;
+const INVALID_KEY_CODE: i32 = 12;
+const STDERR_TAIL_LINES: usize = 20;
+/// The variable systemd sets to the directory of credentials loaded by
+/// `LoadCredential=`.
+const CREDENTIALS_DIRECTORY_VAR: &str = "CREDENTIALS_DIRECTORY";
 
 pub struct Api {
     exe: String,
     verbosity: usize,
+    /// The number of progress updates per second, if any.
+    progress_fps: Option<f64>,
+    retry: Retry,
+    /// Outputs of password commands by repository.
+    passwords: RefCell<HashMap<Name, String>>,
+    /// Reads a password from the keyring of the operating system.
+    keyring: fn(&KeyringEntry) -> keyring::Result<String>,
 }
 
 impl Api {
-    pub fn new(exe: String, verbosity: usize) -> Self {
-        Api { exe, verbosity }
+    pub fn new(exe: String, verbosity: usize, progress_fps: Option<f64>, retry: Retry) -> Self {
+        Api {
+            exe,
+            verbosity,
+            progress_fps,
+            retry,
+            passwords: Default::default(),
+            keyring: |entry| keyring::Entry::new(entry.service(), entry.account())?.get_password(),
+        }
     }
 
-    pub fn backup<I, P, S>(
+    /// Backs up every source into a snapshot of its own. Stops at the first
+    /// failed source.
+    pub fn backup<S>(
         &self,
         repo: &Repository,
-        paths: I,
+        sources: &[Source],
         tag: S,
         options: &BackupOptions,
+        args: &[String],
         dry_run: bool,
-    ) -> Result<()>
+    ) -> Result<BackupSummary>
     where
-        I: IntoIterator<Item = P>,
-        P: AsRef<Path>,
         S: AsRef<str>,
     {
-        let mut cmd = self.command(repo);
-        cmd.arg("backup");
-        if dry_run {
-            cmd.arg("--dry-run");
+        let mut summary = BackupSummary::default();
+        for source in sources {
+            let source_summary =
+                self.backup_source(repo, source, tag.as_ref(), options, args, dry_run)?;
+            summary.merge(source_summary);
+        }
+        Ok(summary)
+    }
+
+    fn backup_source(
+        &self,
+        repo: &Repository,
+        source: &Source,
+        tag: &str,
+        options: &BackupOptions,
+        args: &[String],
+        dry_run: bool,
+    ) -> Result<BackupSummary> {
+        let flags: &[&str] = if dry_run { &["--dry-run"] } else { &[] };
+        let mut cmd = self.backup_command(repo, source, tag, options, args, flags)?;
+        let timeout = repo.timeouts.backup().or(options.timeout());
+        self.retry(|| {
+            let output = run::run_output_timeout(&mut cmd, false, timeout.map(Into::into))?;
+            if !output.status.success() && !is_backup_read_error(output.status) {
+                return Err(Error::failure(&cmd, &output));
+            }
+            Ok(BackupSummary::parse(
+                output.status,
+                &String::from_utf8_lossy(&output.stdout),
+            ))
+        })
+    }
+
+    /// Estimates what a backup of the sources would add to the repository
+    /// without writing any data (`restic backup --dry-run`).
+    pub fn estimate<S>(
+        &self,
+        repo: &Repository,
+        sources: &[Source],
+        tag: S,
+        options: &BackupOptions,
+        args: &[String],
+    ) -> Result<Estimate>
+    where
+        S: AsRef<str>,
+    {
+        let mut estimate = Estimate::default();
+        let timeout = repo.timeouts.backup().or(options.timeout());
+        for source in sources {
+            let mut cmd = self.backup_command(
+                repo,
+                source,
+                tag.as_ref(),
+                options,
+                args,
+                &["--dry-run", "--json"],
+            )?;
+            let source_estimate = self.retry(|| {
+                let output = run::run_output_timeout(&mut cmd, true, timeout.map(Into::into))?;
+                if !output.status.success() && !is_backup_read_error(output.status) {
+                    return Err(Error::failure(&cmd, &output));
+                }
+                Estimate::parse(&String::from_utf8_lossy(&output.stdout))
+            })?;
+            estimate.merge(source_estimate);
         }
+        Ok(estimate)
+    }
+
+    /// The backup command of a source with additional flags, e.g.
+    /// `--dry-run`.
+    fn backup_command(
+        &self,
+        repo: &Repository,
+        source: &Source,
+        tag: &str,
+        options: &BackupOptions,
+        args: &[String],
+        flags: &[&str],
+    ) -> Result<Command> {
+        let mut cmd = self.command(repo)?;
+        cmd.arg("backup");
+        cmd.args(repo.extra_args.backup());
+        cmd.args(flags);
         for pattern in options.exclude() {
             cmd.arg("--exclude");
             cmd.arg(pattern);
@@ -58,6 +175,18 @@ impl Api {
             cmd.arg("--iexclude-file");
             cmd.arg(file);
         }
+        for file in options.files_from() {
+            cmd.arg("--files-from");
+            cmd.arg(file);
+        }
+        for file in options.files_from_verbatim() {
+            cmd.arg("--files-from-verbatim");
+            cmd.arg(file);
+        }
+        for file in options.files_from_raw() {
+            cmd.arg("--files-from-raw");
+            cmd.arg(file);
+        }
         for file in options.exclude_if_present() {
             cmd.arg("--exclude-if-present");
             cmd.arg(file);
@@ -75,6 +204,13 @@ impl Api {
         if options.ignore_inode() {
             cmd.arg("--ignore-inode");
         }
+        if options.force_rescan() {
+            cmd.arg("--force");
+        }
+        if let Some(parent) = options.parent() {
+            cmd.arg("--parent");
+            cmd.arg(parent);
+        }
         if options.no_scan() {
             cmd.arg("--no-scan");
         }
@@ -90,15 +226,37 @@ impl Api {
         if options.with_atime() {
             cmd.arg("--with-atime");
         }
-        cmd.arg("--tag");
-        cmd.arg(tag.as_ref());
-        for path in paths.into_iter().collect::<Vec<_>>() {
-            cmd.arg(OsStr::new(path.as_ref()));
+        if let Some(n) = options.read_concurrency() {
+            cmd.arg("--read-concurrency");
+            cmd.arg(format!("{n}"));
         }
-        match run(&mut cmd) {
-            Err(Error::CmdFailure { status, .. }) if is_backup_read_error(status) => Ok(()),
-            result => result,
+        cmd.arg("--tag");
+        cmd.arg(tag);
+        cmd.args(args);
+        match source {
+            Source::Paths(paths) => {
+                cmd.args(paths);
+            }
+            Source::Command {
+                command,
+                filename,
+                env,
+            } => {
+                // restic runs the command itself, so that its failure fails
+                // the backup instead of creating a truncated snapshot. It
+                // passes its environment on to the command.
+                cmd.envs(env.iter().map(|(k, v)| (k, v)));
+                if let Some(filename) = filename {
+                    cmd.arg("--stdin-filename");
+                    cmd.arg(filename);
+                }
+                cmd.arg("--stdin-from-command");
+                cmd.arg("--");
+                cmd.arg(command.program());
+                cmd.args(command.args());
+            }
         }
+        Ok(cmd)
     }
 
     pub fn forget<S>(
@@ -106,17 +264,20 @@ impl Api {
         repo: &Repository,
         tag: S,
         options: &ForgetOptions,
+        prune: bool,
+        args: &[String],
         dry_run: bool,
     ) -> Result<()>
     where
         S: AsRef<str>,
     {
-        let mut cmd = self.command(repo);
+        let mut cmd = self.command(repo)?;
         cmd.arg("forget");
+        cmd.args(repo.extra_args.forget());
         if dry_run {
             cmd.arg("--dry-run");
         }
-        if options.prune() {
+        if prune {
             cmd.arg("--prune");
         }
         if let Some(n) = options.keep_last() {
@@ -173,46 +334,349 @@ impl Api {
         }
         cmd.arg("--tag");
         cmd.arg(tag.as_ref());
-        run(&mut cmd)
+        cmd.args(args);
+        let timeout = repo.timeouts.forget().or(options.timeout());
+        self.retry(|| run(&mut cmd, timeout))
+    }
+
+    pub fn check(&self, repo: &Repository, options: &CheckOptions, args: &[String]) -> Result<()> {
+        let mut cmd = self.command(repo)?;
+        cmd.arg("check");
+        cmd.args(repo.extra_args.check());
+        cmd.args(args);
+        let timeout = repo.timeouts.check().or(options.timeout());
+        self.retry(|| run(&mut cmd, timeout))
+    }
+
+    /// Removes stale locks, i.e. locks of processes that no longer run.
+    pub fn unlock(&self, repo: &Repository) -> Result<()> {
+        let mut cmd = self.command(repo)?;
+        cmd.arg("unlock");
+        cmd.args(repo.extra_args.unlock());
+        self.retry(|| run(&mut cmd, None))
+    }
+
+    /// Adds the tag to (or removes it from) the snapshot with the given tag,
+    /// e.g. "latest" for the most recent one.
+    pub fn tag_snapshot<S>(
+        &self,
+        repo: &Repository,
+        tag: S,
+        snapshot: &str,
+        pin_tag: &str,
+        remove: bool,
+    ) -> Result<()>
+    where
+        S: AsRef<str>,
+    {
+        let mut cmd = self.command(repo)?;
+        cmd.arg("tag");
+        cmd.arg(if remove { "--remove" } else { "--add" });
+        cmd.arg(pin_tag);
+        cmd.arg("--tag");
+        cmd.arg(tag.as_ref());
+        cmd.arg(snapshot);
+        self.retry(|| run(&mut cmd, None))
+    }
+
+    /// Removes all locks, including those restic does not consider stale.
+    pub fn unlock_all(&self, repo: &Repository) -> Result<()> {
+        let mut cmd = self.command(repo)?;
+        cmd.arg("unlock");
+        cmd.args(repo.extra_args.unlock());
+        cmd.arg("--remove-all");
+        self.retry(|| run(&mut cmd, None))
+    }
+
+    /// Returns the locks of the repository. They are read without a lock,
+    /// because the repository may be locked exclusively.
+    pub fn locks(&self, repo: &Repository) -> Result<Vec<Lock>> {
+        let mut cmd = self.command_with(repo, true)?;
+        cmd.arg("list");
+        cmd.arg("locks");
+        let ids = self.retry(|| {
+            let output = run::run_output(&mut cmd, true)?;
+            if !output.status.success() {
+                return Err(Error::failure(&cmd, &output));
+            }
+            Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+        })?;
+
+        let mut locks = Vec::new();
+        for id in ids.lines().map(str::trim).filter(|id| !id.is_empty()) {
+            let mut cmd = self.command_with(repo, true)?;
+            cmd.arg("cat");
+            cmd.arg("lock");
+            cmd.arg(id);
+            let output = self.retry(|| {
+                let output = run::run_output(&mut cmd, true)?;
+                if !output.status.success() {
+                    return Err(Error::failure(&cmd, &output));
+                }
+                Ok(output)
+            });
+            match output {
+                Ok(output) => {
+                    let mut lock: Lock = serde_json::from_slice(&output.stdout)?;
+                    lock.id = id.to_string();
+                    locks.push(lock);
+                }
+                // The lock may have been removed in the meantime.
+                Err(err) => print_log!(Level::DEBUG, "Failed to read lock {id}: {err}"),
+            }
+        }
+        Ok(locks)
+    }
+
+    /// Removes unreferenced data from the repository.
+    pub fn prune(&self, repo: &Repository, timeout: Option<config::Duration>) -> Result<()> {
+        let mut cmd = self.command(repo)?;
+        cmd.arg("prune");
+        cmd.args(repo.extra_args.prune());
+        self.retry(|| run(&mut cmd, timeout))
+    }
+
+    /// Removes old cache directories of repositories.
+    pub fn cache_cleanup(&self, repo: &Repository) -> Result<()> {
+        let mut cmd = self.command(repo)?;
+        cmd.arg("cache");
+        cmd.arg("--cleanup");
+        self.retry(|| run(&mut cmd, None))
+    }
+
+    /// Copies the snapshots with the given tag from the source repository to
+    /// the target repository.
+    pub fn copy<S>(
+        &self,
+        source: &Repository,
+        target: &Repository,
+        tag: S,
+        args: &[String],
+    ) -> Result<()>
+    where
+        S: AsRef<str>,
+    {
+        let mut cmd = self.copy_command(source, target)?;
+        cmd.arg("--tag");
+        cmd.arg(tag.as_ref());
+        cmd.args(args);
+        self.retry(|| run(&mut cmd, None))
+    }
+
+    /// Copies the given snapshots from the source repository to the target
+    /// repository.
+    pub fn copy_snapshots(
+        &self,
+        source: &Repository,
+        target: &Repository,
+        snapshots: &[String],
+    ) -> Result<()> {
+        let mut cmd = self.copy_command(source, target)?;
+        cmd.args(snapshots);
+        self.retry(|| run(&mut cmd, None))
+    }
+
+    /// The copy command of the target repository. The source repository is
+    /// passed by the variables restic reads for the --from-repo options, with
+    /// its password resolved like for its own commands. restic uses the same
+    /// backend variables and options for both repositories, so those of the
+    /// source are added and must not contradict those of the target.
+    fn copy_command(&self, source: &Repository, target: &Repository) -> Result<Command> {
+        let mut cmd = self.command(target)?;
+        let source_cmd = self.command(source)?;
+        let conflict = |setting: &str| {
+            Error::CopyConflict(format!(
+                "Repositories {} and {} define different values of {setting}, but restic copy uses the same value for both.",
+                source.name, target.name
+            ))
+        };
+        let target_envs: HashMap<_, _> = cmd
+            .get_envs()
+            .map(|(key, value)| (key.to_os_string(), value.map(|v| v.to_os_string())))
+            .collect();
+        for (key, value) in source_cmd.get_envs() {
+            let name = key.to_string_lossy();
+            if let Some(name) = name.strip_prefix("RESTIC_") {
+                if matches!(
+                    name,
+                    "REPOSITORY" | "PASSWORD" | "PASSWORD_FILE" | "PASSWORD_COMMAND" | "KEY_HINT"
+                ) {
+                    match value {
+                        Some(value) => cmd.env(format!("RESTIC_FROM_{name}"), value),
+                        None => cmd.env_remove(format!("RESTIC_FROM_{name}")),
+                    };
+                }
+                continue;
+            }
+            // The number of cores is a setting of the command, not of the
+            // backend.
+            if name == "GOMAXPROCS" {
+                continue;
+            }
+            match (target_envs.get(key), value) {
+                (Some(Some(target_value)), Some(value)) if target_value != value => {
+                    return Err(conflict(&name));
+                }
+                (_, Some(value)) => {
+                    cmd.env(key, value);
+                }
+                (_, None) => {}
+            }
+        }
+        for option in &source.options {
+            let key = option
+                .split_once('=')
+                .map_or(option.as_str(), |(key, _)| key);
+            let target_option = target
+                .options
+                .iter()
+                .find(|o| o.split_once('=').map_or(o.as_str(), |(k, _)| k) == key);
+            match target_option {
+                Some(target_option) if target_option != option => {
+                    return Err(conflict(&format!("option {key}")));
+                }
+                Some(_) => {}
+                None => {
+                    cmd.arg("--option");
+                    cmd.arg(option);
+                }
+            }
+        }
+        cmd.arg("copy");
+        cmd.args(target.extra_args.copy());
+        Ok(cmd)
     }
 
     pub fn status(&self, repo: &Repository) -> Result<RepoStatus> {
-        let mut cmd = self.command(repo);
+        let mut cmd = self.read_only_command(repo)?;
         cmd.arg("cat");
         cmd.arg("config");
 
-        let status = run::run(&mut cmd, true)?;
-        match status.code() {
-            Some(0) => Ok(RepoStatus::Ok),
-            Some(10) => Ok(RepoStatus::NoRepository),
-            Some(11) => Ok(RepoStatus::Locked),
-            Some(12) => Ok(RepoStatus::InvalidKey),
-            _ => Err(Error::CmdFailure {
-                program: cmd.get_program().to_owned(),
-                status,
-            }),
+        self.retry(|| {
+            let output = run::run_output(&mut cmd, true)?;
+            match output.status.code() {
+                Some(0) => Ok(RepoStatus::Ok),
+                Some(REPOSITORY_NOT_FOUND_CODE) => Ok(RepoStatus::NoRepository),
+                Some(REPOSITORY_LOCKED_CODE) => Ok(RepoStatus::Locked),
+                Some(INVALID_KEY_CODE) => Ok(RepoStatus::InvalidKey),
+                _ => Err(Error::failure(&cmd, &output)),
+            }
+        })
+    }
+
+    /// Returns the most recent snapshot with the given tag, if any.
+    pub fn latest_snapshot<S>(&self, repo: &Repository, tag: S) -> Result<Option<Snapshot>>
+    where
+        S: AsRef<str>,
+    {
+        let mut cmd = self.read_only_command(repo)?;
+        cmd.arg("snapshots");
+        cmd.args(repo.extra_args.snapshots());
+        cmd.arg("--json");
+        cmd.arg("--latest");
+        cmd.arg("1");
+        cmd.arg("--tag");
+        cmd.arg(tag.as_ref());
+
+        self.retry(|| {
+            let output = run::run_output(&mut cmd, true)?;
+            if !output.status.success() {
+                return Err(Error::failure(&cmd, &output));
+            }
+            let snapshots: Vec<Snapshot> = serde_json::from_slice(&output.stdout)?;
+            Ok(snapshots.into_iter().max_by_key(|s| s.time))
+        })
+    }
+
+    /// Restores the snapshot into the target directory. With verify, restic
+    /// reads the restored files again and compares them with the snapshot.
+    pub fn restore(
+        &self,
+        repo: &Repository,
+        snapshot: &str,
+        target: &Path,
+        verify: bool,
+        args: &[String],
+        dry_run: bool,
+    ) -> Result<()> {
+        let mut cmd = self.read_only_command(repo)?;
+        cmd.arg("restore");
+        if dry_run {
+            cmd.arg("--dry-run");
         }
+        if verify {
+            cmd.arg("--verify");
+        }
+        cmd.arg("--target");
+        cmd.arg(target);
+        cmd.args(args);
+        cmd.arg(snapshot);
+        self.retry(|| run(&mut cmd, None))
+    }
+
+    /// Returns all snapshots of the repository.
+    pub fn snapshots(&self, repo: &Repository) -> Result<Vec<Snapshot>> {
+        let mut cmd = self.read_only_command(repo)?;
+        cmd.arg("snapshots");
+        cmd.args(repo.extra_args.snapshots());
+        cmd.arg("--json");
+
+        self.retry(|| {
+            let output = run::run_output(&mut cmd, true)?;
+            if !output.status.success() {
+                return Err(Error::failure(&cmd, &output));
+            }
+            Ok(serde_json::from_slice(&output.stdout)?)
+        })
     }
 
     pub fn init(&self, repo: &Repository) -> Result<()> {
-        let mut cmd = self.command(repo);
+        let mut cmd = self.command(repo)?;
         cmd.arg("init");
-        run(&mut cmd)
+        cmd.args(repo.extra_args.init());
+        // Only repositories of version 2 support compression.
+        if repo.compression.is_some() {
+            cmd.arg("--repository-version");
+            cmd.arg("2");
+        }
+        run(&mut cmd, None)
     }
 
-    pub fn exec<I, S>(&self, repo: &Repository, args: I) -> Result<()>
+    /// Runs a restic command with the given arguments and handling of the
+    /// output. Returns the output.
+    pub fn exec<I, S>(
+        &self,
+        repo: &Repository,
+        args: I,
+        options: &run::OutputOptions,
+    ) -> Result<Output>
     where
         I: IntoIterator<Item = S>,
         S: AsRef<str>,
     {
-        let mut cmd = self.command(repo);
+        let mut cmd = self.command(repo)?;
         args.into_iter().for_each(|arg| {
             cmd.arg(arg.as_ref());
         });
-        run(&mut cmd)
+        let output = run::run_output_with(&mut cmd, options, None)?;
+        if output.status.success() {
+            Ok(output)
+        } else {
+            Err(Error::failure(&cmd, &output))
+        }
     }
 
-    fn command(&self, repo: &Repository) -> Command {
+    /// A command that does not modify the repository. It runs without a lock
+    /// if configured, so that it does not wait for running backups.
+    fn read_only_command(&self, repo: &Repository) -> Result<Command> {
+        self.command_with(repo, repo.no_lock)
+    }
+
+    fn command(&self, repo: &Repository) -> Result<Command> {
+        self.command_with(repo, false)
+    }
+
+    fn command_with(&self, repo: &Repository, no_lock: bool) -> Result<Command> {
         let env_prefix = format!("{ENV_PREFIX}_R_");
         let repo_env_prefix = format!("{}{}_", env_prefix, repo.name.as_str().to_uppercase());
 
@@ -223,10 +687,13 @@ impl Api {
             })
             .filter(|(k, _)| !k.starts_with(&env_prefix));
 
-        let mut cmd = std::process::Command::new(&self.exe);
+        let mut cmd = crate::priority::command(&self.exe);
         cmd.env_clear();
         cmd.envs(vars);
         cmd.envs(&repo.environment);
+        if let Some(cores) = repo.max_cores {
+            cmd.env("GOMAXPROCS", cores.to_string());
+        }
         if !repo.path.is_empty() {
             cmd.env("RESTIC_REPOSITORY", &repo.path);
         }
@@ -237,13 +704,33 @@ impl Api {
             cmd.env("RESTIC_PASSWORD_FILE", path);
         }
         if !repo.password_command.is_empty() {
-            cmd.env("RESTIC_PASSWORD_COMMAND", &repo.password_command);
+            if repo.cache_password_command {
+                cmd.env("RESTIC_PASSWORD", self.cached_password(repo)?);
+            } else {
+                cmd.env("RESTIC_PASSWORD_COMMAND", &repo.password_command);
+            }
+        }
+        if let Some(entry) = &repo.password_keyring {
+            let password = (self.keyring)(entry).map_err(|e| Error::Keyring(entry.clone(), e))?;
+            redact::add_secret(&password);
+            cmd.env("RESTIC_PASSWORD", password);
+        }
+        if let Some(name) = &repo.password_credential {
+            let dir = std::env::var_os(CREDENTIALS_DIRECTORY_VAR)
+                .ok_or_else(|| Error::MissingCredentials(name.clone()))?;
+            cmd.env("RESTIC_PASSWORD_FILE", Path::new(&dir).join(name));
         }
-        cmd.env("RESTIC_PROGRESS_FPS", "0.016666");
+        match self.progress_fps {
+            Some(fps) => cmd.env("RESTIC_PROGRESS_FPS", fps.to_string()),
+            None => cmd.env_remove("RESTIC_PROGRESS_FPS"),
+        };
         if self.verbosity > 0 {
             cmd.arg(format!("--verbose={}", self.verbosity));
         }
-        if !repo.retry_lock.is_empty() {
+        // restic rejects --retry-lock together with --no-lock.
+        if no_lock {
+            cmd.arg("--no-lock");
+        } else if !repo.retry_lock.is_empty() {
             cmd.arg("--retry-lock");
             cmd.arg(&repo.retry_lock);
         }
@@ -251,19 +738,155 @@ impl Api {
             cmd.arg("--option");
             cmd.arg(option);
         }
-        cmd
+        if let Some(size) = repo.pack_size {
+            cmd.arg("--pack-size");
+            cmd.arg(format!("{size}"));
+        }
+        if let Some(limit) = repo.limit_upload {
+            cmd.arg("--limit-upload");
+            cmd.arg(format!("{limit}"));
+        }
+        if let Some(limit) = repo.limit_download {
+            cmd.arg("--limit-download");
+            cmd.arg(format!("{limit}"));
+        }
+        if let Some(compression) = repo.compression {
+            cmd.arg("--compression");
+            cmd.arg(compression.as_str());
+        }
+        if repo.no_cache {
+            cmd.arg("--no-cache");
+        } else if let Some(dir) = &repo.cache_dir {
+            cmd.arg("--cache-dir");
+            cmd.arg(dir);
+        }
+        for file in &repo.cacert {
+            cmd.arg("--cacert");
+            cmd.arg(file);
+        }
+        if let Some(file) = &repo.tls_client_cert {
+            cmd.arg("--tls-client-cert");
+            cmd.arg(file);
+        }
+        if repo.insecure_tls {
+            cmd.arg("--insecure-tls");
+        }
+        Ok(cmd)
     }
 }
 
-fn run(cmd: &mut Command) -> Result<()> {
-    let status = run::run(cmd, false)?;
-    if status.success() {
+impl Api {
+    /// Runs the operation and repeats it while it fails with a transient error,
+    /// at most as often as configured. The delay between attempts doubles with
+    /// every retry.
+    fn retry<T>(&self, mut operation: impl FnMut() -> Result<T>) -> Result<T> {
+        let mut attempt = 0;
+        loop {
+            match operation() {
+                Err(err) if attempt < self.retry.retries() && self.is_transient(&err) => {
+                    let backoff: std::time::Duration = self.retry.backoff().into();
+                    let delay = backoff.saturating_mul(2u32.saturating_pow(attempt));
+                    attempt += 1;
+                    print_log!(
+                        Level::WARN,
+                        "{err} Retry {attempt} of {} in {} ...",
+                        self.retry.retries(),
+                        config::Duration::from(delay)
+                    );
+                    if signal::sleep(delay) {
+                        return Err(err);
+                    }
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Whether restic failed with one of the configured exit codes or an error
+    /// output that matches one of the configured patterns.
+    fn is_transient(&self, err: &Error) -> bool {
+        err.exit_code()
+            .is_some_and(|code| self.retry.exit_codes().contains(&code))
+            || self.is_network_error(err)
+    }
+
+    /// Whether the error output of restic matches one of the configured retry
+    /// patterns.
+    fn is_network_error(&self, err: &Error) -> bool {
+        let Some(stderr) = err.stderr() else {
+            return false;
+        };
+        let stderr = stderr.to_lowercase();
+        self.retry
+            .patterns()
+            .iter()
+            .any(|pattern| stderr.contains(&pattern.to_lowercase()))
+    }
+
+    /// The exit status of aresticrat that corresponds to a failure.
+    pub fn exit_status(&self, err: &Error) -> exit::Status {
+        match err.exit_code() {
+            Some(REPOSITORY_NOT_FOUND_CODE) => exit::Status::Unavailable,
+            Some(INVALID_KEY_CODE) => exit::Status::InvalidKey,
+            _ if matches!(err, Error::CopyConflict(_)) => exit::Status::Config,
+            _ if self.is_network_error(err) => exit::Status::Unavailable,
+            _ => exit::Status::Failure,
+        }
+    }
+
+    /// The failure of an operation that failed with the given error. It
+    /// includes the details of the failed command, if any.
+    pub fn failure(&self, err: &Error, message: String) -> exit::Failure {
+        let failure = exit::Failure::new(self.exit_status(err), message);
+        match err.command() {
+            Some(command) => {
+                failure.with_command(command, err.exit_code(), err.stderr().map(str::to_string))
+            }
+            None => failure,
+        }
+    }
+
+    /// Runs the password command of the repository once and returns its
+    /// output. Subsequent calls for the same repository return the cached
+    /// output.
+    fn cached_password(&self, repo: &Repository) -> Result<String> {
+        if let Some(password) = self.passwords.borrow().get(&repo.name) {
+            return Ok(password.clone());
+        }
+
+        let failure = |msg: String| Error::PasswordCommand(repo.password_command.clone(), msg);
+        let args =
+            shell_words::split(&repo.password_command).map_err(|e| failure(e.to_string()))?;
+        let Some((program, args)) = args.split_first() else {
+            return Err(failure("empty command".to_string()));
+        };
+        let output = Command::new(program)
+            .args(args)
+            .envs(&repo.environment)
+            .stdin(Stdio::inherit())
+            .stderr(Stdio::inherit())
+            .output()?;
+        if !output.status.success() {
+            return Err(failure(output.status.to_string()));
+        }
+
+        let password = String::from_utf8_lossy(&output.stdout)
+            .trim_end_matches(['\r', '\n'])
+            .to_string();
+        redact::add_secret(&password);
+        self.passwords
+            .borrow_mut()
+            .insert(repo.name.clone(), password.clone());
+        Ok(password)
+    }
+}
+
+fn run(cmd: &mut Command, timeout: Option<config::Duration>) -> Result<()> {
+    let output = run::run_output_timeout(cmd, false, timeout.map(Into::into))?;
+    if output.status.success() {
         Ok(())
     } else {
-        Err(Error::CmdFailure {
-            program: cmd.get_program().to_os_string(),
-            status,
-        })
+        Err(Error::failure(cmd, &output))
     }
 }
 
@@ -271,6 +894,11 @@ fn is_backup_read_error(status: ExitStatus) -> bool {
     status.code() == Some(BACKUP_READ_ERROR_CODE)
 }
 
+/// Whether a backup succeeded, but some source files could not be read.
+pub fn is_incomplete(summary: &BackupSummary) -> bool {
+    summary.exit_code == Some(BACKUP_READ_ERROR_CODE)
+}
+
 fn remove_prefix(str: &mut String, prefix: &str) -> bool {
     if str.starts_with(prefix) {
         str.replace_range(..prefix.len(), "");
@@ -279,6 +907,100 @@ fn remove_prefix(str: &mut String, prefix: &str) -> bool {
     false
 }
 
+/// Information extracted from the output of a backup.
+#[derive(Debug, Default)]
+pub struct BackupSummary {
+    pub exit_code: Option<i32>,
+    pub bytes_added: Option<u64>,
+    pub snapshot_ids: Vec<String>,
+}
+
+impl BackupSummary {
+    fn parse(status: ExitStatus, output: &str) -> Self {
+        let mut summary = BackupSummary {
+            exit_code: status.code(),
+            bytes_added: None,
+            snapshot_ids: Vec::new(),
+        };
+        for line in output.lines().map(str::trim) {
+            if let Some(rest) = line
+                .strip_prefix("Added to the repository:")
+                .or_else(|| line.strip_prefix("Would add to the repository:"))
+            {
+                summary.bytes_added = parse_size(rest.split('(').next().unwrap_or_default());
+            } else if let Some(rest) = line.strip_prefix("snapshot ") {
+                if let Some(id) = rest.strip_suffix(" saved") {
+                    summary.snapshot_ids.push(id.to_string());
+                }
+            }
+        }
+        summary
+    }
+
+    /// Adds the summary of another backup of the same run. The merged
+    /// summary is incomplete if any of them is.
+    fn merge(&mut self, other: BackupSummary) {
+        if !is_incomplete(self) {
+            self.exit_code = other.exit_code;
+        }
+        self.bytes_added = match (self.bytes_added, other.bytes_added) {
+            (Some(a), Some(b)) => Some(a + b),
+            (a, b) => a.or(b),
+        };
+        self.snapshot_ids.extend(other.snapshot_ids);
+    }
+}
+
+/// What a backup would add to a repository, taken from the summary of
+/// `restic backup --dry-run --json`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Estimate {
+    pub files_new: u64,
+    pub files_changed: u64,
+    pub files_unmodified: u64,
+    #[serde(rename = "total_bytes_processed")]
+    pub bytes_processed: u64,
+    #[serde(rename = "data_added")]
+    pub bytes_added: u64,
+}
+
+impl Estimate {
+    fn parse(output: &str) -> Result<Self> {
+        for line in output.lines().map(str::trim).filter(|l| l.starts_with('{')) {
+            let message: serde_json::Value = serde_json::from_str(line)?;
+            if message["message_type"] == "summary" {
+                return Ok(serde_json::from_value(message)?);
+            }
+        }
+        Err(Error::MissingSummary)
+    }
+
+    /// Adds the estimate of another source of the same location.
+    pub fn merge(&mut self, other: Estimate) {
+        self.files_new += other.files_new;
+        self.files_changed += other.files_changed;
+        self.files_unmodified += other.files_unmodified;
+        self.bytes_processed += other.bytes_processed;
+        self.bytes_added += other.bytes_added;
+    }
+}
+
+/// Parses sizes as printed by restic, e.g. `1.234 MiB`.
+fn parse_size(s: &str) -> Option<u64> {
+    let (value, unit) = s.trim().split_once(' ')?;
+    let value: f64 = value.parse().ok()?;
+    let factor: u64 = match unit {
+        "B" => 1,
+        "KiB" => 1 << 10,
+        "MiB" => 1 << 20,
+        "GiB" => 1 << 30,
+        "TiB" => 1 << 40,
+        _ => return None,
+    };
+    Some((value * factor as f64) as u64)
+}
+
 pub enum RepoStatus {
     Ok,
     NoRepository,
@@ -289,14 +1011,96 @@ pub enum RepoStatus {
 type Result<T> = std::result::Result<T, Error>;
 
 #[derive(Debug, Error)]
+#[allow(clippy::enum_variant_names)]
 pub enum Error {
     #[error("Execution of {program:?} failed ({status}).")]
     CmdFailure {
         program: OsString,
+        /// The program and its arguments.
+        command: String,
         status: ExitStatus,
+        /// The last lines of the error output.
+        stderr: String,
     },
     #[error("{0}")]
     IoError(#[from] std::io::Error),
+    #[error("Unexpected restic output: {0}")]
+    InvalidOutput(#[from] serde_json::Error),
+    #[error("restic did not report a summary.")]
+    MissingSummary,
+    #[error("Failed to read the password from keyring entry {0}: {1}")]
+    Keyring(KeyringEntry, keyring::Error),
+    #[error("Password command {0:?} failed: {1}")]
+    PasswordCommand(String, String),
+    #[error("Credential {0:?} is unavailable, because $CREDENTIALS_DIRECTORY is not set.")]
+    MissingCredentials(String),
+    #[error("{0}")]
+    CopyConflict(String),
+}
+
+impl Error {
+    fn failure(cmd: &Command, output: &Output) -> Self {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let lines: Vec<_> = stderr.lines().collect();
+        Error::CmdFailure {
+            program: crate::priority::program(cmd).to_os_string(),
+            command: run::describe(cmd),
+            status: output.status,
+            stderr: lines[lines.len().saturating_sub(STDERR_TAIL_LINES)..].join("\n"),
+        }
+    }
+
+    /// The last lines of the error output of restic, if any.
+    pub fn stderr(&self) -> Option<&str> {
+        match self {
+            Error::CmdFailure { stderr, .. } if !stderr.is_empty() => Some(stderr),
+            _ => None,
+        }
+    }
+
+    /// The failed restic command including its arguments, if it has been run.
+    pub fn command(&self) -> Option<&str> {
+        match self {
+            Error::CmdFailure { command, .. } => Some(command),
+            _ => None,
+        }
+    }
+
+    /// Whether restic failed, because the repository is locked.
+    pub fn is_locked(&self) -> bool {
+        self.exit_code() == Some(REPOSITORY_LOCKED_CODE)
+    }
+
+    /// The exit code of restic, if it has been run and terminated regularly.
+    pub fn exit_code(&self) -> Option<i32> {
+        match self {
+            Error::CmdFailure { status, .. } => status.code(),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Snapshot {
+    pub short_id: String,
+    pub time: DateTime<FixedOffset>,
+}
+
+/// A lock of a repository as printed by `restic cat lock`. Running operations
+/// refresh their locks, so the time is that of the last refresh.
+#[derive(Debug, Deserialize)]
+pub struct Lock {
+    #[serde(skip)]
+    pub id: String,
+    pub time: DateTime<FixedOffset>,
+    #[serde(default)]
+    pub exclusive: bool,
+    #[serde(default)]
+    pub hostname: String,
+    #[serde(default)]
+    pub username: String,
+    #[serde(default)]
+    pub pid: u32,
 }
 
 pub struct Repository {
@@ -305,7 +1109,121 @@ pub struct Repository {
     pub password: String,
     pub password_file: Option<PathBuf>,
     pub password_command: String,
+    pub cache_password_command: bool,
+    pub password_keyring: Option<KeyringEntry>,
+    pub password_credential: Option<String>,
     pub retry_lock: String,
+    /// Whether read-only commands run without a lock.
+    pub no_lock: bool,
+    /// Additional arguments of restic subcommands.
+    pub extra_args: config::ExtraArgs,
+    pub timeouts: Timeouts,
     pub options: Vec<String>,
+    pub pack_size: Option<u32>,
+    pub limit_upload: Option<u32>,
+    pub limit_download: Option<u32>,
+    pub compression: Option<config::Compression>,
+    pub cache_dir: Option<PathBuf>,
+    pub no_cache: bool,
+    pub cacert: Vec<PathBuf>,
+    pub tls_client_cert: Option<PathBuf>,
+    pub insecure_tls: bool,
+    /// The number of CPU cores restic may use at once.
+    pub max_cores: Option<u32>,
     pub environment: HashMap<String, String>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn repo(name: &str, path: &str) -> Repository {
+        Repository {
+            name: Name::parse(name).unwrap(),
+            path: path.to_string(),
+            password: String::new(),
+            password_file: None,
+            password_command: String::new(),
+            cache_password_command: false,
+            password_keyring: None,
+            password_credential: None,
+            retry_lock: String::new(),
+            no_lock: false,
+            extra_args: Default::default(),
+            timeouts: Default::default(),
+            options: Vec::new(),
+            pack_size: None,
+            limit_upload: None,
+            limit_download: None,
+            compression: None,
+            cache_dir: None,
+            no_cache: false,
+            cacert: Vec::new(),
+            tls_client_cert: None,
+            insecure_tls: false,
+            max_cores: None,
+            environment: HashMap::new(),
+        }
+    }
+
+    fn api() -> Api {
+        Api {
+            keyring: |entry| Ok(format!("keyring:{}/{}", entry.service(), entry.account())),
+            ..Api::new("restic".to_string(), 0, None, Default::default())
+        }
+    }
+
+    fn env<'a>(cmd: &'a Command, name: &str) -> Option<&'a std::ffi::OsStr> {
+        cmd.get_envs()
+            .find(|(key, _)| *key == name)
+            .and_then(|(_, value)| value)
+    }
+
+    #[test]
+    fn copy_command_passes_source_password_and_backend() {
+        let mut source = repo("nas", "s3:https://s3.example.com/nas");
+        source.password_keyring = Some(KeyringEntry::parse("aresticrat/nas").unwrap());
+        source.environment =
+            HashMap::from([("AWS_ACCESS_KEY_ID".to_string(), "nas-key".to_string())]);
+        source.options = vec!["s3.region=eu-west-1".to_string()];
+        let mut target = repo("offsite", "/srv/offsite");
+        target.password = "target-secret".to_string();
+
+        let cmd = api().copy_command(&source, &target).unwrap();
+        assert_eq!(
+            env(&cmd, "RESTIC_FROM_REPOSITORY"),
+            Some("s3:https://s3.example.com/nas".as_ref())
+        );
+        assert_eq!(
+            env(&cmd, "RESTIC_FROM_PASSWORD"),
+            Some("keyring:aresticrat/nas".as_ref())
+        );
+        assert_eq!(
+            env(&cmd, "RESTIC_REPOSITORY"),
+            Some("/srv/offsite".as_ref())
+        );
+        assert_eq!(env(&cmd, "RESTIC_PASSWORD"), Some("target-secret".as_ref()));
+        assert_eq!(env(&cmd, "AWS_ACCESS_KEY_ID"), Some("nas-key".as_ref()));
+        let args: Vec<_> = cmd.get_args().collect();
+        assert!(
+            args.windows(2)
+                .any(|a| a[0] == "--option" && a[1] == "s3.region=eu-west-1")
+        );
+        assert_eq!(args.last().map(|a| a.to_os_string()), Some("copy".into()));
+    }
+
+    #[test]
+    fn copy_command_rejects_contradicting_backend_settings() {
+        let mut source = repo("nas", "s3:https://s3.example.com/nas");
+        source.environment =
+            HashMap::from([("AWS_ACCESS_KEY_ID".to_string(), "nas-key".to_string())]);
+        let mut target = repo("offsite", "s3:https://s3.example.com/offsite");
+        target.environment =
+            HashMap::from([("AWS_ACCESS_KEY_ID".to_string(), "offsite-key".to_string())]);
+
+        assert!(matches!(
+            api().copy_command(&source, &target),
+            Err(Error::CopyConflict(_))
+        ));
+    }
+}
@@ -1,56 +1,231 @@
-use crate::DEFAULT_VERBOSITY;
+use crate::config;
+use crate::print_command_output;
 use crate::print_log;
-use crate::verbosity;
-use std::borrow::BorrowMut;
+use crate::redact;
+use std::fs::File;
+use std::io::ErrorKind;
 use std::io::Read;
 use std::io::Write;
+use std::path::Path;
+use std::process::Child;
 use std::process::Command;
+use std::process::ExitStatus;
+use std::process::Output;
 use std::process::Stdio;
+use std::sync::{Mutex, OnceLock};
 use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
 use tracing::Level;
 use tracing::debug;
 use tracing::error;
 use tracing::info;
 use tracing_subscriber::fmt::writer::EitherWriter;
 
-pub fn run_sequential<C, I>(
-    cmds: I,
-    quiet: bool,
-) -> Result<std::process::ExitStatus, std::io::Error>
-where
-    I: IntoIterator<Item = C>,
-    C: BorrowMut<Command>,
-{
-    for mut cmd in cmds {
-        let status = run(cmd.borrow_mut(), quiet)?;
-        if !status.success() {
-            return Ok(status);
-        }
+/// Time a command has to exit after it has been asked to terminate.
+#[cfg(unix)]
+const KILL_GRACE_PERIOD: Duration = Duration::from_secs(30);
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+static LOG_FILE: OnceLock<Mutex<File>> = OnceLock::new();
+/// Process IDs of the running commands and whether they lead their own
+/// process group.
+static CHILDREN: Mutex<Vec<(u32, bool)>> = Mutex::new(Vec::new());
+
+/// Additionally writes the output of all subsequent commands to the given file.
+pub fn set_log_file(path: &Path) -> Result<(), std::io::Error> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
     }
-    Ok(Default::default())
+    let file = File::options().create(true).append(true).open(path)?;
+    let _ = LOG_FILE.set(Mutex::new(file));
+    Ok(())
+}
+
+/// Runs the command and returns its status and its captured stdout and stderr.
+pub fn run_output(cmd: &mut Command, quiet: bool) -> Result<Output, std::io::Error> {
+    run_output_timeout(cmd, quiet, None)
+}
+
+/// Like [`run_output`], but terminates the command if it is still running
+/// after the timeout. It is asked to terminate first (SIGTERM on Unix) and
+/// killed if it has not exited after a grace period. On Unix, the command runs
+/// in its own process group, so that the processes it has started are
+/// terminated as well and cannot keep its output open.
+pub fn run_output_timeout(
+    cmd: &mut Command,
+    quiet: bool,
+    timeout: Option<Duration>,
+) -> Result<Output, std::io::Error> {
+    let options = OutputOptions {
+        print: !quiet && print_command_output(),
+        capture_stdout: false,
+        log: true,
+        label: None,
+    };
+    run_output_with(cmd, &options, timeout)
+}
+
+/// Where the output of a command goes.
+#[derive(Clone, Debug)]
+pub struct OutputOptions<'a> {
+    /// Whether the output is printed to the console while the command runs.
+    pub print: bool,
+    /// Whether the standard output is only captured instead of printed, e.g.
+    /// to process it after the command has finished.
+    pub capture_stdout: bool,
+    /// Whether the output is written to the log file.
+    pub log: bool,
+    /// A label that prefixes every printed line, e.g. `[db-dump] `.
+    pub label: Option<&'a str>,
 }
 
-pub fn run(cmd: &mut Command, quiet: bool) -> Result<std::process::ExitStatus, std::io::Error> {
-    let print = !quiet && verbosity() >= DEFAULT_VERBOSITY;
+/// Like [`run_output_timeout`], but with the given handling of the output.
+pub fn run_output_with(
+    cmd: &mut Command,
+    options: &OutputOptions,
+    timeout: Option<Duration>,
+) -> Result<Output, std::io::Error> {
+    let prefix = options.label.map(|l| format!("[{l}] ")).unwrap_or_default();
 
     cmd.stdout(Stdio::piped());
     cmd.stderr(Stdio::piped());
 
-    print_log!(Level::DEBUG, "Run command: {cmd:?} ...");
+    let group = cfg!(unix) && timeout.is_some();
+    #[cfg(unix)]
+    if group {
+        use std::os::unix::process::CommandExt;
+        cmd.process_group(0);
+    }
+
+    print_log!(Level::DEBUG, "Run command: {} ...", describe(cmd));
     let mut child = cmd.spawn()?;
+    let pid = child.id();
+    lock_children().push((pid, group));
     let child_stdout = child.stdout.take().unwrap();
     let child_stderr = child.stderr.take().unwrap();
 
-    let out_task = spawn_tee(child_stdout, filter_writer(print, std::io::stdout()));
-    let err_task = spawn_tee(child_stderr, filter_writer(print, std::io::stderr()));
+    let out_task = spawn_tee(
+        child_stdout,
+        filter_writer(
+            options.print && !options.capture_stdout,
+            Prefixed::new(&prefix, redact::Writer::new(std::io::stdout())),
+        ),
+    );
+    let err_task = spawn_tee(
+        child_stderr,
+        filter_writer(
+            options.print,
+            Prefixed::new(&prefix, redact::Writer::new(std::io::stderr())),
+        ),
+    );
 
-    let status = child.wait()?;
+    let result = wait(&mut child, timeout);
+    lock_children().retain(|(p, _)| *p != pid);
+    let (status, timed_out) = result?;
     let out = out_task.join().unwrap()?;
     let err = err_task.join().unwrap()?;
 
-    log_cmd_result(cmd, &status, &out, &err, quiet);
+    log_cmd_result(cmd, &status, &out, &err, !options.print);
+    if options.log {
+        if let Err(err) = write_log_file(cmd, &status, &out, &err) {
+            print_log!(Level::WARN, "Failed to write log file: {err}");
+        }
+    }
+
+    if let (true, Some(timeout)) = (timed_out, timeout) {
+        return Err(std::io::Error::new(
+            ErrorKind::TimedOut,
+            format!(
+                "{} did not finish within {} and has been terminated.",
+                cmd.get_program().to_string_lossy(),
+                config::Duration::from(timeout)
+            ),
+        ));
+    }
+
+    Ok(Output {
+        status,
+        stdout: out,
+        stderr: err,
+    })
+}
+
+/// Waits for the child to exit. Returns its status and whether it has been
+/// terminated, because it exceeded the timeout.
+fn wait(
+    child: &mut Child,
+    timeout: Option<Duration>,
+) -> Result<(ExitStatus, bool), std::io::Error> {
+    let Some(timeout) = timeout else {
+        return Ok((child.wait()?, false));
+    };
+    if let Some(status) = wait_until(child, Instant::now() + timeout)? {
+        return Ok((status, false));
+    }
+
+    print_log!(
+        Level::WARN,
+        "Command did not finish within {}. Terminate ...",
+        config::Duration::from(timeout)
+    );
+    #[cfg(unix)]
+    {
+        // The child leads its own process group, see `run_output_with`.
+        let group = -(child.id() as libc::pid_t);
+        unsafe { libc::kill(group, libc::SIGTERM) };
+        let status = match wait_until(child, Instant::now() + KILL_GRACE_PERIOD)? {
+            Some(status) => status,
+            None => {
+                child.kill()?;
+                child.wait()?
+            }
+        };
+        // Processes that ignored the request would keep the output open.
+        unsafe { libc::kill(group, libc::SIGKILL) };
+        Ok((status, true))
+    }
+    #[cfg(not(unix))]
+    {
+        child.kill()?;
+        Ok((child.wait()?, true))
+    }
+}
 
-    Ok(status)
+/// Waits for the child to exit until the deadline. Returns `None` if it is
+/// still running.
+fn wait_until(child: &mut Child, deadline: Instant) -> Result<Option<ExitStatus>, std::io::Error> {
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(Some(status));
+        }
+        if Instant::now() >= deadline {
+            return Ok(None);
+        }
+        std::thread::sleep(POLL_INTERVAL.min(deadline - Instant::now()));
+    }
+}
+
+/// Asks all running commands to terminate.
+///
+/// On Windows, commands share the console of aresticrat and receive Ctrl-C
+/// themselves.
+pub fn interrupt_children() {
+    #[cfg(unix)]
+    for &(pid, group) in lock_children().iter() {
+        // Commands in their own process group do not receive the Ctrl-C of
+        // the terminal, so the whole group is interrupted.
+        let pid = if group {
+            -(pid as libc::pid_t)
+        } else {
+            pid as libc::pid_t
+        };
+        // SIGINT lets restic remove its lock before it exits.
+        unsafe { libc::kill(pid, libc::SIGINT) };
+    }
+}
+
+fn lock_children() -> std::sync::MutexGuard<'static, Vec<(u32, bool)>> {
+    CHILDREN.lock().unwrap_or_else(|e| e.into_inner())
 }
 
 fn log_cmd_result(
@@ -64,7 +239,10 @@ fn log_cmd_result(
     let err = String::from_utf8_lossy(stderr);
 
     let mut str = String::new();
-    str.push_str(&format!("Finished command {cmd:?}\nStatus: {status}"));
+    str.push_str(&format!(
+        "Finished command {}\nStatus: {status}",
+        describe(cmd)
+    ));
 
     if !out.is_empty() {
         if quiet {
@@ -84,6 +262,38 @@ fn log_cmd_result(
     debug!("Command completed. (Status: {status})");
 }
 
+fn write_log_file(
+    cmd: &Command,
+    status: &std::process::ExitStatus,
+    stdout: &[u8],
+    stderr: &[u8],
+) -> Result<(), std::io::Error> {
+    let Some(file) = LOG_FILE.get() else {
+        return Ok(());
+    };
+    let mut file = file.lock().unwrap_or_else(|e| e.into_inner());
+
+    writeln!(
+        file,
+        "=== {} {}",
+        chrono::Local::now().to_rfc3339(),
+        describe(cmd)
+    )?;
+    file.write_all(redact::redact(&String::from_utf8_lossy(stdout)).as_bytes())?;
+    file.write_all(redact::redact(&String::from_utf8_lossy(stderr)).as_bytes())?;
+    writeln!(file, "=== {status}")?;
+    file.flush()
+}
+
+/// Formats the program and arguments of the command. Unlike the debug
+/// representation, environment variables are omitted.
+pub fn describe(cmd: &Command) -> String {
+    let args = std::iter::once(cmd.get_program())
+        .chain(cmd.get_args())
+        .map(|a| a.to_string_lossy());
+    redact::redact(&shell_words::join(args)).into_owned()
+}
+
 pub struct Tee<R: Read, W: Write>(R, W);
 
 impl<R: Read, W: Write> Tee<R, W> {
@@ -122,6 +332,45 @@ fn tee_all<R: Read, W: Write>(r: R, w: W) -> Result<Vec<u8>, std::io::Error> {
     Ok(v)
 }
 
+/// Prefixes every line written to the inner writer, e.g. with a label.
+struct Prefixed<W: Write> {
+    prefix: String,
+    inner: W,
+    line_start: bool,
+}
+
+impl<W: Write> Prefixed<W> {
+    fn new(prefix: &str, inner: W) -> Self {
+        Self {
+            prefix: prefix.to_string(),
+            inner,
+            line_start: true,
+        }
+    }
+}
+
+impl<W: Write> Write for Prefixed<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if self.prefix.is_empty() {
+            return self.inner.write(buf);
+        }
+        let mut out = Vec::with_capacity(buf.len() + self.prefix.len());
+        for &b in buf {
+            if self.line_start {
+                out.extend_from_slice(self.prefix.as_bytes());
+            }
+            out.push(b);
+            self.line_start = b == b'\n';
+        }
+        self.inner.write_all(&out)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
 fn filter_writer<W: Write>(condition: bool, w: W) -> impl Write {
     if condition {
         EitherWriter::A(w)
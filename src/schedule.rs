@@ -0,0 +1,278 @@
+use crate::cli::{Args, ScheduleInstallArgs, ScheduleShowArgs};
+use crate::config::{Config, CronExpr, Name};
+use crate::event::Operation;
+use crate::print_log;
+use crate::run;
+use anyhow::{Result, anyhow, bail};
+use std::path::PathBuf;
+use std::process::Command;
+use tracing::Level;
+
+/// The folder of the Windows Task Scheduler that holds all tasks.
+const TASK_FOLDER: &str = "aresticrat";
+
+/// A scheduled operation of a location.
+struct Entry<'a> {
+    location: &'a Name,
+    operation: Operation,
+    cron: &'a CronExpr,
+}
+
+/// All scheduled operations of the configured locations, sorted by location.
+fn entries(config: &Config) -> Vec<Entry<'_>> {
+    let mut entries: Vec<_> = config
+        .locations()
+        .iter()
+        .flat_map(|(location, l)| {
+            l.schedule().entries().map(move |(operation, cron)| Entry {
+                location,
+                operation,
+                cron,
+            })
+        })
+        .collect();
+    entries.sort_by_key(|e| e.location.as_str());
+    entries
+}
+
+/// The arguments that run aresticrat with the current executable,
+/// configuration files and overrides, working directory and environment
+/// files, followed by the given operation of a location.
+fn invocation(args: &Args, entry: &Entry) -> Result<Vec<String>> {
+    // The working directory has already been changed according to --wd, so
+    // relative paths are resolved against it.
+    let wd = std::env::current_dir()?;
+    let absolute = |path: &std::path::Path| -> PathBuf { wd.join(path) };
+
+    let mut invocation = vec![std::env::current_exe()?.display().to_string()];
+    for config_file in args.config_files() {
+        invocation.push("--config".to_string());
+        invocation.push(absolute(config_file).display().to_string());
+    }
+    for (key, value) in args.settings() {
+        invocation.push("--set".to_string());
+        invocation.push(format!("{key}={value}"));
+    }
+    invocation.push("--wd".to_string());
+    invocation.push(wd.display().to_string());
+    for env_file in args.env_files() {
+        invocation.push("--env".to_string());
+        invocation.push(absolute(env_file).display().to_string());
+    }
+    invocation.push(entry.operation.as_str().to_string());
+    invocation.push("-l".to_string());
+    invocation.push(entry.location.to_string());
+    Ok(invocation)
+}
+
+pub fn install(config: &Config, args: &Args, install_args: &ScheduleInstallArgs) -> Result<()> {
+    let entries = entries(config);
+    if entries.is_empty() {
+        bail!("No location defines a schedule.");
+    }
+
+    if install_args.windows() {
+        install_windows(&entries, args)?;
+    }
+
+    Ok(())
+}
+
+fn install_windows(entries: &[Entry], args: &Args) -> Result<()> {
+    // Translate all schedules first, so that no tasks are created if a single
+    // schedule is not supported.
+    let triggers = entries
+        .iter()
+        .map(|entry| {
+            schtasks_trigger(entry.cron).ok_or_else(|| {
+                anyhow!(
+                    "The {} schedule \"{}\" of location {} cannot be expressed as Windows scheduled task. Use \"aresticrat daemon\" instead.",
+                    entry.operation.as_str(),
+                    entry.cron,
+                    entry.location
+                )
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    for (entry, trigger) in entries.iter().zip(triggers) {
+        let name = format!(
+            "{TASK_FOLDER}\\{}-{}",
+            entry.location,
+            entry.operation.as_str()
+        );
+        let command_line = invocation(args, entry)?
+            .iter()
+            .map(|arg| windows_quote(arg))
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        let mut cmd = Command::new("schtasks");
+        cmd.args(["/Create", "/F", "/TN", &name, "/TR", &command_line])
+            .args(trigger);
+        let output =
+            run::run_output(&mut cmd, true).map_err(|e| anyhow!("Failed to run schtasks: {e}"))?;
+        if !output.status.success() {
+            bail!(
+                "Failed to create scheduled task {name}: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        print_log!(
+            Level::INFO,
+            "Scheduled task {name} created ({}).",
+            entry.cron
+        );
+    }
+
+    Ok(())
+}
+
+pub fn show(config: &Config, args: &Args, show_args: &ScheduleShowArgs) -> Result<()> {
+    let entries = entries(config);
+    if entries.is_empty() {
+        bail!("No location defines a schedule.");
+    }
+
+    if show_args.cron() {
+        show_cron(&entries, args)?;
+    }
+
+    Ok(())
+}
+
+fn show_cron(entries: &[Entry], args: &Args) -> Result<()> {
+    let mut lines = Vec::new();
+    for entry in entries {
+        let schedule = crontab_schedule(entry.cron).ok_or_else(|| {
+            anyhow!(
+                "The {} schedule \"{}\" of location {} cannot be expressed in a crontab, because cron does not support seconds.",
+                entry.operation.as_str(),
+                entry.cron,
+                entry.location
+            )
+        })?;
+        // A percent sign starts the standard input of the command in a
+        // crontab and has to be escaped.
+        let command = shell_words::join(invocation(args, entry)?).replace('%', "\\%");
+        lines.push(format!(
+            "# {} of location {}\n{schedule} {command}",
+            entry.operation.label(),
+            entry.location
+        ));
+    }
+    println!("{}", lines.join("\n"));
+    Ok(())
+}
+
+/// The five fields (minute to weekday) of a cron expression as used in a
+/// crontab. Returns `None` if the expression has a seconds field other than 0.
+fn crontab_schedule(cron: &CronExpr) -> Option<String> {
+    let expr = cron.to_string();
+    let mut fields: Vec<_> = expr.split_whitespace().collect();
+    if fields.len() == 6 && fields.remove(0) != "0" {
+        return None;
+    }
+    Some(fields.join(" "))
+}
+
+/// Translates a cron expression to the trigger arguments of schtasks. Returns
+/// `None` if the expression has no equivalent trigger.
+fn schtasks_trigger(cron: &CronExpr) -> Option<Vec<String>> {
+    // Tasks always start at the full minute.
+    let expr = crontab_schedule(cron)?;
+    let fields: Vec<_> = expr.split_whitespace().collect();
+    let [minute, hour, day, month, weekday] = fields[..] else {
+        return None;
+    };
+    if month != "*" {
+        return None;
+    }
+
+    let args = |schedule: &str, rest: &[String]| {
+        let mut args = vec!["/SC".to_string(), schedule.to_string()];
+        args.extend_from_slice(rest);
+        Some(args)
+    };
+    let start_time =
+        |hour: u32, minute: u32| vec!["/ST".to_string(), format!("{hour:02}:{minute:02}")];
+    let number = |field: &str, max: u32| field.parse::<u32>().ok().filter(|n| *n <= max);
+    let interval = |field: &str| {
+        match field {
+            "*" => Some(1),
+            _ => field.strip_prefix("*/")?.parse::<u32>().ok(),
+        }
+        .filter(|n| *n > 0)
+    };
+
+    if hour == "*" && day == "*" && weekday == "*" {
+        if let Some(n) = interval(minute) {
+            return args("MINUTE", &["/MO".to_string(), n.to_string()]);
+        }
+    }
+    let minute = number(minute, 59)?;
+    if day == "*" && weekday == "*" {
+        if let Some(hour) = number(hour, 23) {
+            return args("DAILY", &start_time(hour, minute));
+        }
+        let n = interval(hour)?;
+        let mut rest = vec!["/MO".to_string(), n.to_string()];
+        rest.extend(start_time(0, minute));
+        return args("HOURLY", &rest);
+    }
+    let hour = number(hour, 23)?;
+    if day == "*" {
+        let mut rest = vec!["/D".to_string(), schtasks_weekdays(weekday)?];
+        rest.extend(start_time(hour, minute));
+        return args("WEEKLY", &rest);
+    }
+    if weekday == "*" {
+        let days = day
+            .split(',')
+            .map(|d| number(d, 31).filter(|d| *d > 0).map(|d| d.to_string()))
+            .collect::<Option<Vec<_>>>()?;
+        let mut rest = vec!["/D".to_string(), days.join(",")];
+        rest.extend(start_time(hour, minute));
+        return args("MONTHLY", &rest);
+    }
+    None
+}
+
+/// Translates the weekday field of a cron expression (e.g. "1-5" or "MON,SAT")
+/// to the day list of schtasks.
+fn schtasks_weekdays(field: &str) -> Option<String> {
+    const DAYS: [&str; 7] = ["SUN", "MON", "TUE", "WED", "THU", "FRI", "SAT"];
+    let day = |s: &str| -> Option<usize> {
+        let s = s.to_ascii_uppercase();
+        match s.parse::<usize>() {
+            Ok(n) if n <= 7 => Some(n),
+            Ok(_) => None,
+            Err(_) => DAYS.iter().position(|d| *d == s),
+        }
+    };
+
+    let mut days = Vec::new();
+    for part in field.split(',') {
+        match part.split_once('-') {
+            Some((from, to)) => {
+                let (from, to) = (day(from)?, day(to)?);
+                if from > to {
+                    return None;
+                }
+                days.extend((from..=to).map(|d| d % 7));
+            }
+            None => days.push(day(part)? % 7),
+        }
+    }
+    days.sort();
+    days.dedup();
+    Some(days.iter().map(|d| DAYS[*d]).collect::<Vec<_>>().join(","))
+}
+
+/// Quotes an argument of a Windows command line if necessary.
+fn windows_quote(arg: &str) -> String {
+    if !arg.is_empty() && !arg.contains([' ', '\t', '"']) {
+        return arg.to_string();
+    }
+    format!("\"{}\"", arg.replace('"', "\\\""))
+}
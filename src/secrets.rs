@@ -0,0 +1,200 @@
+use crate::redact;
+use config::{Value, ValueKind};
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use thiserror::Error;
+
+/// The key of tables that hold an encrypted value, e.g.
+/// `password = { encrypted = "-----BEGIN AGE ENCRYPTED FILE-----..." }`.
+const ENCRYPTED_KEY: &str = "encrypted";
+
+/// A function that looks up a secret in an external secret manager.
+type Lookup = fn(&str) -> Result<String, Error>;
+
+/// Prefixes of values that refer to secrets in external secret managers and
+/// the functions that resolve them.
+const RESOLVERS: [(&str, Lookup); 2] = [("vault:", vault_lookup), ("aws-sm:", aws_lookup)];
+
+/// Replaces all encrypted values and secret references in the configuration by
+/// their plaintext.
+///
+/// Encrypted values are decrypted with age using the given identity file.
+/// References like `vault:kv/backups#password` are looked up in the
+/// corresponding secret manager. The plaintext is registered as secret, so
+/// that it never appears in any output.
+pub fn resolve_values(value: &mut Value, identity: Option<&Path>) -> Result<(), Error> {
+    resolve_value(value, identity, "")
+}
+
+fn resolve_value(value: &mut Value, identity: Option<&Path>, key: &str) -> Result<(), Error> {
+    let plaintext = match &mut value.kind {
+        ValueKind::Table(table) if table.len() == 1 && table.contains_key(ENCRYPTED_KEY) => {
+            let ciphertext = table[ENCRYPTED_KEY].clone().into_string().map_err(|_| {
+                Error::Value(key.to_string(), "Encrypted value must be a string.".into())
+            })?;
+            let identity = identity.ok_or_else(|| {
+                Error::Value(
+                    key.to_string(),
+                    "No age identity configured (see age.identity).".into(),
+                )
+            })?;
+            age_decrypt(&ciphertext, identity)
+        }
+        ValueKind::Table(table) => {
+            for (k, v) in table.iter_mut() {
+                resolve_value(v, identity, &join_key(key, k))?;
+            }
+            return Ok(());
+        }
+        ValueKind::Array(items) => {
+            for (i, v) in items.iter_mut().enumerate() {
+                resolve_value(v, identity, &format!("{key}[{i}]"))?;
+            }
+            return Ok(());
+        }
+        ValueKind::String(s) => {
+            let Some((lookup, reference)) = RESOLVERS
+                .iter()
+                .find_map(|(prefix, lookup)| Some((lookup, s.strip_prefix(prefix)?)))
+            else {
+                return Ok(());
+            };
+            lookup(reference)
+        }
+        _ => return Ok(()),
+    };
+
+    let plaintext = plaintext.map_err(|e| Error::Value(key.to_string(), e.to_string()))?;
+    redact::add_secret(&plaintext);
+    let origin = value.origin().map(str::to_string);
+    *value = Value::new(origin.as_ref(), plaintext);
+    Ok(())
+}
+
+fn age_decrypt(ciphertext: &str, identity: &Path) -> Result<String, Error> {
+    let mut cmd = Command::new("age");
+    cmd.arg("--decrypt").arg("--identity").arg(identity);
+    let output = run_with_input(&mut cmd, ciphertext.trim().as_bytes())?;
+    Ok(output.trim_end_matches(['\r', '\n']).to_string())
+}
+
+/// Reads a field of a secret from HashiCorp Vault, e.g. `kv/backups#password`.
+/// The Vault CLI must be in PATH and is configured by its usual environment
+/// variables (VAULT_ADDR, VAULT_TOKEN, ...).
+fn vault_lookup(reference: &str) -> Result<String, Error> {
+    let (path, field) = reference.rsplit_once('#').ok_or_else(|| {
+        Error::Reference(reference.to_string(), "Expected <path>#<field>.".into())
+    })?;
+    let mut cmd = Command::new("vault");
+    cmd.arg("kv")
+        .arg("get")
+        .arg(format!("-field={field}"))
+        .arg(path);
+    let output = run_with_input(&mut cmd, &[])?;
+    Ok(output.trim_end_matches(['\r', '\n']).to_string())
+}
+
+/// Reads a secret from AWS Secrets Manager, e.g. `arn:aws:...:secret:backups`.
+/// If the reference ends with `#<key>`, the secret is parsed as JSON object and
+/// the value of the key is returned. The AWS CLI must be in PATH.
+fn aws_lookup(reference: &str) -> Result<String, Error> {
+    let (id, key) = match reference.rsplit_once('#') {
+        Some((id, key)) => (id, Some(key)),
+        None => (reference, None),
+    };
+    let mut cmd = Command::new("aws");
+    cmd.arg("secretsmanager")
+        .arg("get-secret-value")
+        .arg("--secret-id")
+        .arg(id)
+        .arg("--query")
+        .arg("SecretString")
+        .arg("--output")
+        .arg("text");
+    let output = run_with_input(&mut cmd, &[])?;
+    let secret = output.trim_end_matches(['\r', '\n']);
+
+    let Some(key) = key else {
+        return Ok(secret.to_string());
+    };
+    let json: serde_json::Value = serde_json::from_str(secret)
+        .map_err(|e| Error::Reference(reference.to_string(), e.to_string()))?;
+    match json.get(key) {
+        Some(serde_json::Value::String(value)) => Ok(value.clone()),
+        Some(value) => Ok(value.to_string()),
+        None => Err(Error::Reference(
+            reference.to_string(),
+            format!("The secret has no key {key:?}."),
+        )),
+    }
+}
+
+/// Decrypts a SOPS file and returns its content as JSON. All string values of
+/// the file are registered as secrets.
+pub fn sops_decrypt(path: &Path) -> Result<String, Error> {
+    let mut cmd = Command::new("sops");
+    cmd.arg("--decrypt")
+        .arg("--output-type")
+        .arg("json")
+        .arg(path);
+    let output = run_with_input(&mut cmd, &[])?;
+
+    let json: serde_json::Value = serde_json::from_str(&output)
+        .map_err(|e| Error::Value(path.display().to_string(), e.to_string()))?;
+    register_strings(&json);
+    Ok(output)
+}
+
+fn register_strings(value: &serde_json::Value) {
+    match value {
+        serde_json::Value::String(s) => redact::add_secret(s),
+        serde_json::Value::Array(items) => items.iter().for_each(register_strings),
+        serde_json::Value::Object(map) => map.values().for_each(register_strings),
+        _ => {}
+    }
+}
+
+/// Runs the command with the given standard input and returns its output.
+fn run_with_input(cmd: &mut Command, input: &[u8]) -> Result<String, Error> {
+    let program = cmd.get_program().to_string_lossy().into_owned();
+    let mut child = cmd
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| Error::Io(program.clone(), e))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(input)
+            .map_err(|e| Error::Io(program.clone(), e))?;
+    }
+    let output = child
+        .wait_with_output()
+        .map_err(|e| Error::Io(program.clone(), e))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        return Err(Error::Failure(program, output.status, stderr));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+fn join_key(parent: &str, key: &str) -> String {
+    if parent.is_empty() {
+        key.to_string()
+    } else {
+        format!("{parent}.{key}")
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Failed to run {0}: {1}")]
+    Io(String, std::io::Error),
+    #[error("{0} failed ({1}): {2}")]
+    Failure(String, std::process::ExitStatus, String),
+    #[error("Failed to resolve {0}: {1}")]
+    Value(String, String),
+    #[error("Invalid secret reference {0:?}: {1}")]
+    Reference(String, String),
+}
@@ -0,0 +1,62 @@
+use crate::exit::Status;
+use crate::print_log;
+use crate::run;
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, Instant};
+use tracing::Level;
+
+/// Signals received within this interval after the first one are ignored.
+const REPEAT_INTERVAL: Duration = Duration::from_secs(1);
+
+static INTERRUPTED: Mutex<bool> = Mutex::new(false);
+static WAKEUP: Condvar = Condvar::new();
+
+/// Installs a handler for SIGINT, SIGTERM and SIGHUP (Ctrl-C and closing the
+/// console on Windows).
+///
+/// The first signal is forwarded to the running commands, so that restic can
+/// remove its repository lock, and marks the run as interrupted: no further
+/// operations are started, but FINALLY hooks still run. A second signal
+/// terminates aresticrat immediately.
+pub fn init() {
+    let mut first_signal = None;
+    let result = ctrlc::set_handler(move || {
+        let mut interrupted = INTERRUPTED.lock().unwrap_or_else(|e| e.into_inner());
+        if *interrupted {
+            // A signal sent to the whole process group may arrive more than
+            // once, so only later signals terminate aresticrat.
+            if first_signal.is_some_and(|t: Instant| t.elapsed() > REPEAT_INTERVAL) {
+                std::process::exit(Status::Interrupted.code());
+            }
+            return;
+        }
+        *interrupted = true;
+        first_signal = Some(Instant::now());
+        WAKEUP.notify_all();
+        drop(interrupted);
+
+        print_log!(
+            Level::WARN,
+            "Interrupted. Waiting for running commands to finish ..."
+        );
+        run::interrupt_children();
+    });
+    if let Err(err) = result {
+        print_log!(Level::WARN, "Failed to install signal handler: {err}");
+    }
+}
+
+/// Whether a termination signal has been received.
+pub fn interrupted() -> bool {
+    *INTERRUPTED.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Sleeps for the given time, but returns early when a termination signal is
+/// received. Returns whether the run has been interrupted.
+pub fn sleep(duration: Duration) -> bool {
+    let interrupted = INTERRUPTED.lock().unwrap_or_else(|e| e.into_inner());
+    let (interrupted, _) = WAKEUP
+        .wait_timeout_while(interrupted, duration, |interrupted| !*interrupted)
+        .unwrap_or_else(|e| e.into_inner());
+    *interrupted
+}
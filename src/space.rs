@@ -0,0 +1,63 @@
+use crate::config::Size;
+use crate::report::format_size;
+use crate::restic_api::Repository;
+use std::path::{Path, PathBuf};
+
+/// Checks that the volumes to which a backup to the repository writes have at
+/// least the given free space: the one of the restic cache and, for a local
+/// repository, the one of the repository.
+pub fn check(repo: &Repository, local_path: Option<&Path>, min: Size) -> Result<(), String> {
+    let cache_dir = if repo.no_cache {
+        None
+    } else {
+        repo.cache_dir.clone().or_else(|| default_cache_dir(repo))
+    };
+    let volumes = [
+        cache_dir.map(|dir| ("restic cache", dir)),
+        local_path.map(|path| ("repository", path.to_path_buf())),
+    ];
+    for (name, path) in volumes.into_iter().flatten() {
+        // The directory may not exist yet, e.g. before the first backup.
+        let Some(existing) = path.ancestors().find(|p| p.exists()) else {
+            continue;
+        };
+        let available = fs4::available_space(existing).map_err(|e| {
+            format!(
+                "Failed to determine the free space of {}: {e}",
+                path.display()
+            )
+        })?;
+        if available < u64::from(min) {
+            return Err(format!(
+                "Only {} of the required {} are free on the volume of the {name} {}.",
+                format_size(available),
+                format_size(min.into()),
+                path.display()
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// The cache directory restic uses if none is configured.
+fn default_cache_dir(repo: &Repository) -> Option<PathBuf> {
+    let var = |name: &str| {
+        repo.environment
+            .get(name)
+            .cloned()
+            .or_else(|| std::env::var(name).ok())
+            .filter(|v| !v.is_empty())
+            .map(PathBuf::from)
+    };
+    if let Some(dir) = var("RESTIC_CACHE_DIR") {
+        return Some(dir);
+    }
+    let base = if cfg!(windows) {
+        var("LOCALAPPDATA")?
+    } else if cfg!(target_os = "macos") {
+        var("HOME")?.join("Library").join("Caches")
+    } else {
+        var("XDG_CACHE_HOME").or_else(|| Some(var("HOME")?.join(".cache")))?
+    };
+    Some(base.join("restic"))
+}
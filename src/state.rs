@@ -0,0 +1,283 @@
+use crate::config::Name;
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+static FILE: OnceLock<PathBuf> = OnceLock::new();
+
+/// Stores the state of subsequent runs in the given file.
+pub fn set_file(path: PathBuf) {
+    let _ = FILE.set(path);
+}
+
+/// The state file of a configuration file without a configured one, e.g.
+/// `aresticrat.state.json` next to `aresticrat.toml`.
+pub fn default_file(config_file: &Path) -> PathBuf {
+    config_file.with_extension("state.json")
+}
+
+/// Information that is kept across runs, e.g. the time of the last check of
+/// every repository.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct State {
+    #[serde(default)]
+    repos: HashMap<String, RepoState>,
+    /// The state of the backups of every location to every repository.
+    #[serde(default)]
+    backups: HashMap<String, HashMap<String, BackupState>>,
+    /// The replication of every repository to each of its replicas.
+    #[serde(default)]
+    replication: HashMap<String, HashMap<String, ReplicationState>>,
+}
+
+impl State {
+    /// The state of a repository. Repositories without state have never been
+    /// maintained by aresticrat.
+    pub fn repo(&self, name: &Name) -> RepoState {
+        self.repos.get(name.as_str()).cloned().unwrap_or_default()
+    }
+
+    /// The state of the backups of a location to a repository, if any has
+    /// been recorded.
+    pub fn backup(&self, location: &Name, repo: &Name) -> Option<&BackupState> {
+        self.backups.get(location.as_str())?.get(repo.as_str())
+    }
+
+    /// The state of the replication of a repository to another one.
+    pub fn replication(&self, repo: &Name, target: &Name) -> ReplicationState {
+        self.replication
+            .get(repo.as_str())
+            .and_then(|replicas| replicas.get(target.as_str()))
+            .cloned()
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct BackupState {
+    #[serde(default)]
+    last_run: Option<DateTime<Local>>,
+    #[serde(default)]
+    last_success: Option<DateTime<Local>>,
+    #[serde(default)]
+    error: Option<String>,
+}
+
+impl BackupState {
+    /// The end of the last backup.
+    pub fn last_run(&self) -> Option<DateTime<Local>> {
+        self.last_run
+    }
+    /// The end of the last successful backup, i.e. the approximate time of
+    /// the latest snapshot.
+    pub fn last_success(&self) -> Option<DateTime<Local>> {
+        self.last_success
+    }
+    /// The error of the last backup, if it failed.
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ReplicationState {
+    /// Snapshots that have not been copied yet.
+    #[serde(default)]
+    pending: Vec<String>,
+    /// The most recently copied snapshots, see [`COPIED_SIZE`].
+    #[serde(default)]
+    copied: Vec<String>,
+    /// The end of the last successful copy.
+    #[serde(default)]
+    last_copy: Option<DateTime<Local>>,
+}
+
+/// The number of copied snapshots that are kept per replica.
+const COPIED_SIZE: usize = 100;
+
+impl ReplicationState {
+    pub fn pending(&self) -> &[String] {
+        &self.pending
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct RepoState {
+    #[serde(default)]
+    last_check: Option<DateTime<Local>>,
+    #[serde(default)]
+    last_prune: Option<DateTime<Local>>,
+    #[serde(default)]
+    forgets_since_prune: u32,
+}
+
+impl RepoState {
+    /// The end of the last successful check.
+    pub fn last_check(&self) -> Option<DateTime<Local>> {
+        self.last_check
+    }
+    /// The end of the last successful forget with prune.
+    pub fn last_prune(&self) -> Option<DateTime<Local>> {
+        self.last_prune
+    }
+    /// The number of successful forgets without prune since the last prune.
+    pub fn forgets_since_prune(&self) -> u32 {
+        self.forgets_since_prune
+    }
+}
+
+/// Loads the state file. A missing file results in an empty state.
+pub fn load() -> Result<State, std::io::Error> {
+    let Some(path) = FILE.get() else {
+        return Ok(State::default());
+    };
+    let content = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(State::default()),
+        Err(err) => return Err(err),
+    };
+    serde_json::from_str(&content).map_err(|e| std::io::Error::new(ErrorKind::InvalidData, e))
+}
+
+/// Changes the state of a repository in the state file.
+fn update_repo(name: &Name, f: impl FnOnce(&mut RepoState)) -> Result<(), std::io::Error> {
+    update(|state| f(state.repos.entry(name.to_string()).or_default()))
+}
+
+/// Changes the state file.
+fn update(f: impl FnOnce(&mut State)) -> Result<(), std::io::Error> {
+    let Some(path) = FILE.get() else {
+        return Ok(());
+    };
+    // Operations may run concurrently in daemon mode. Reload the state
+    // before every change, so that no change of another operation is lost.
+    static LOCK: Mutex<()> = Mutex::new(());
+    let _guard = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+    let mut state = load()?;
+    f(&mut state);
+
+    let content = serde_json::to_string_pretty(&state).map_err(std::io::Error::other)?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let tmp_path = path.with_extension("json.tmp");
+    std::fs::write(&tmp_path, content)?;
+    std::fs::rename(&tmp_path, path)
+}
+
+/// Records the end of a backup of a location to a repository and its error,
+/// if it failed.
+pub fn record_backup(
+    location: &Name,
+    repo: &Name,
+    error: Option<&str>,
+) -> Result<(), std::io::Error> {
+    update(|state| {
+        let backup = state
+            .backups
+            .entry(location.to_string())
+            .or_default()
+            .entry(repo.to_string())
+            .or_default();
+        let now = Local::now();
+        backup.last_run = Some(now);
+        backup.error = error.map(str::to_string);
+        if error.is_none() {
+            backup.last_success = Some(now);
+        }
+    })
+}
+
+/// Records new snapshots of a repository that have to be copied to its
+/// replicas. Snapshots that have already been copied are ignored.
+pub fn record_snapshots(
+    repo: &Name,
+    targets: &[Name],
+    snapshots: &[String],
+) -> Result<(), std::io::Error> {
+    if targets.is_empty() || snapshots.is_empty() {
+        return Ok(());
+    }
+    update(|state| {
+        let replicas = state.replication.entry(repo.to_string()).or_default();
+        for target in targets {
+            let replica = replicas.entry(target.to_string()).or_default();
+            for snapshot in snapshots {
+                if !replica.pending.contains(snapshot) && !replica.copied.contains(snapshot) {
+                    replica.pending.push(snapshot.clone());
+                }
+            }
+        }
+    })
+}
+
+/// Records the successful copy of snapshots of a repository to a replica.
+pub fn record_copy(repo: &Name, target: &Name, snapshots: &[String]) -> Result<(), std::io::Error> {
+    update(|state| {
+        let replica = state
+            .replication
+            .entry(repo.to_string())
+            .or_default()
+            .entry(target.to_string())
+            .or_default();
+        replica
+            .pending
+            .retain(|snapshot| !snapshots.contains(snapshot));
+        replica.copied.extend(snapshots.iter().cloned());
+        let excess = replica.copied.len().saturating_sub(COPIED_SIZE);
+        replica.copied.drain(..excess);
+        replica.last_copy = Some(Local::now());
+    })
+}
+
+/// Removes snapshots of a repository that no longer have to be copied to a
+/// replica, e.g. because they have been forgotten.
+pub fn drop_pending(
+    repo: &Name,
+    target: &Name,
+    snapshots: &[String],
+) -> Result<(), std::io::Error> {
+    update(|state| {
+        if let Some(replica) = state
+            .replication
+            .get_mut(repo.as_str())
+            .and_then(|replicas| replicas.get_mut(target.as_str()))
+        {
+            replica
+                .pending
+                .retain(|snapshot| !snapshots.contains(snapshot));
+        }
+    })
+}
+
+/// Records the successful check of a repository.
+pub fn record_check(name: &Name) -> Result<(), std::io::Error> {
+    update_repo(name, |repo| repo.last_check = Some(Local::now()))
+}
+
+/// Records the successful prune of a repository.
+pub fn record_prune(name: &Name) -> Result<(), std::io::Error> {
+    update_repo(name, |repo| {
+        repo.last_prune = Some(Local::now());
+        repo.forgets_since_prune = 0;
+    })
+}
+
+/// Records a successful forget from a repository, with or without prune.
+pub fn record_forget(name: &Name, pruned: bool) -> Result<(), std::io::Error> {
+    update_repo(name, |repo| {
+        if pruned {
+            repo.last_prune = Some(Local::now());
+            repo.forgets_since_prune = 0;
+        } else {
+            repo.forgets_since_prune += 1;
+        }
+    })
+}
@@ -0,0 +1,71 @@
+use crate::cli::ColorMode;
+use std::fmt::Display;
+use std::io::IsTerminal;
+use std::sync::OnceLock;
+
+/// Whether colors are enabled on stdout and stderr.
+static ENABLED: OnceLock<(bool, bool)> = OnceLock::new();
+
+#[derive(Clone, Copy, Debug)]
+pub enum Color {
+    Green,
+    Red,
+    Yellow,
+}
+
+impl Color {
+    fn code(&self) -> &'static str {
+        match self {
+            Color::Green => "32",
+            Color::Red => "31",
+            Color::Yellow => "33",
+        }
+    }
+}
+
+/// Enables or disables colors for the rest of the run. In auto mode, colors
+/// are used on terminals unless the `NO_COLOR` variable is set.
+pub fn set(mode: ColorMode) {
+    let _ = ENABLED.set(detect(mode));
+}
+
+fn detect(mode: ColorMode) -> (bool, bool) {
+    match mode {
+        ColorMode::Always => (true, true),
+        ColorMode::Never => (false, false),
+        ColorMode::Auto => {
+            let no_color = std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+            (
+                !no_color && std::io::stdout().is_terminal(),
+                !no_color && std::io::stderr().is_terminal(),
+            )
+        }
+    }
+}
+
+fn enabled_on() -> (bool, bool) {
+    *ENABLED.get_or_init(|| detect(ColorMode::Auto))
+}
+
+/// Whether colors are used on stderr.
+pub fn enabled_stderr() -> bool {
+    enabled_on().1
+}
+
+/// Wraps the value in ANSI color codes if colors are used on stdout.
+pub fn paint<D: Display>(value: D, color: Color) -> String {
+    wrap(value, color, enabled_on().0)
+}
+
+/// Wraps the value in ANSI color codes if colors are used on stderr.
+pub fn paint_stderr<D: Display>(value: D, color: Color) -> String {
+    wrap(value, color, enabled_on().1)
+}
+
+fn wrap<D: Display>(value: D, color: Color, enabled: bool) -> String {
+    if enabled {
+        format!("\x1b[{}m{value}\x1b[0m", color.code())
+    } else {
+        value.to_string()
+    }
+}
@@ -0,0 +1,25 @@
+/// Replaces `{{name}}` placeholders in the template.
+///
+/// Values are looked up by name and passed through `escape` before insertion.
+/// Unknown placeholders are replaced by an empty string.
+pub fn render<L, E>(template: &str, lookup: L, escape: E) -> String
+where
+    L: Fn(&str) -> Option<String>,
+    E: Fn(&str) -> String,
+{
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let Some(len) = rest[start + 2..].find("}}") else {
+            break;
+        };
+        out.push_str(&rest[..start]);
+        let name = rest[start + 2..start + 2 + len].trim();
+        if let Some(value) = lookup(name) {
+            out.push_str(&escape(&value));
+        }
+        rest = &rest[start + 2 + len + 2..];
+    }
+    out.push_str(rest);
+    out
+}
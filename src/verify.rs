@@ -0,0 +1,228 @@
+use crate::config::{BackupOptions, Config, Location, MissingPaths, Name, Source};
+use crate::print_log;
+use crate::redact;
+use crate::style::{self, Color};
+use serde::Serialize;
+use std::path::Path;
+use tracing::Level;
+
+/// The results of the verify command. They are printed as they are added and,
+/// if requested, as a JSON document at the end.
+#[derive(Serialize)]
+pub struct Results {
+    success: bool,
+    repositories: Vec<Item>,
+    locations: Vec<Item>,
+    /// Whether problems are reported as warnings instead of errors.
+    #[serde(skip)]
+    warn_only: bool,
+}
+
+/// The result of a repository or location, e.g. "OK" or "NOT FOUND".
+#[derive(Serialize)]
+struct Item {
+    name: String,
+    status: &'static str,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    problems: Vec<String>,
+}
+
+impl Item {
+    fn ok(&self) -> bool {
+        is_ok(self.status)
+    }
+}
+
+fn is_ok(status: &str) -> bool {
+    matches!(status, "OK" | "INITIALIZED" | "UNLOCKED")
+}
+
+impl Results {
+    /// Results whose problems are reported as warnings instead of errors, if
+    /// requested.
+    pub fn new(warn_only: bool) -> Self {
+        Results {
+            success: false,
+            repositories: Vec::new(),
+            locations: Vec::new(),
+            warn_only,
+        }
+    }
+
+    pub fn add_repository(&mut self, name: &Name, status: &'static str, problem: Option<String>) {
+        match &problem {
+            Some(problem) => self.problem(&format!("Repository {name}: {status}: {problem}")),
+            None if is_ok(status) => {
+                print_log!(
+                    Level::INFO,
+                    "Repository {name}: {}",
+                    style::paint(status, Color::Green)
+                )
+            }
+            None => self.problem(&format!("Repository {name}: {status}")),
+        }
+        self.repositories.push(Item {
+            name: name.to_string(),
+            status,
+            problems: problem.into_iter().collect(),
+        });
+    }
+
+    pub fn add_location(&mut self, name: &Name, problems: Vec<String>) {
+        if problems.is_empty() {
+            print_log!(
+                Level::INFO,
+                "Location {name}: {}",
+                style::paint("OK", Color::Green)
+            );
+        }
+        for problem in &problems {
+            self.problem(&format!("Location {name}: FAILED: {problem}"));
+        }
+        let status = if problems.is_empty() { "OK" } else { "FAILED" };
+        self.locations.push(Item {
+            name: name.to_string(),
+            status,
+            problems,
+        });
+    }
+
+    fn problem(&self, message: &str) {
+        if self.warn_only {
+            print_log!(Level::WARN, "{message}");
+        } else {
+            print_log!(Level::ERROR, "{message}");
+        }
+    }
+
+    /// The number of failed repositories and locations.
+    pub fn failed(&self) -> (usize, usize) {
+        let failed = |items: &[Item]| items.iter().filter(|i| !i.ok()).count();
+        (failed(&self.repositories), failed(&self.locations))
+    }
+
+    pub fn print_json(mut self) {
+        self.success = self.failed() == (0, 0);
+        let json = serde_json::to_string_pretty(&self).expect("Results are serializable.");
+        println!("{}", redact::redact(&json));
+    }
+}
+
+/// The problems of a location that would make its operations fail: missing or
+/// unreadable paths, missing exclude files, undefined repositories and
+/// programs of hooks and commands that cannot be found.
+pub fn location_problems(
+    location_name: &Name,
+    location: &Location,
+    config: &Config,
+) -> Vec<String> {
+    let mut problems = Vec::new();
+    let backup_opts = crate::get_backup_options(location_name, config);
+
+    // Missing paths are expected if they are skipped.
+    let skip_missing = matches!(
+        location.missing_paths(),
+        Some(MissingPaths::SkipPath | MissingPaths::SkipLocation)
+    );
+    for path in location.paths() {
+        if !path.exists() {
+            if !skip_missing {
+                problems.push(format!("Path {} does not exist.", path.display()));
+            }
+        } else if let Err(err) = readable(path) {
+            problems.push(format!("Path {} is not readable: {err}", path.display()));
+        }
+    }
+    problems.extend(file_problems(&backup_opts));
+
+    for repo_name in location.repos() {
+        if !config.repos().contains_key(repo_name) {
+            problems.push(format!("Repository {repo_name} is not defined."));
+        }
+    }
+
+    let mut commands: Vec<(String, &str)> = Vec::new();
+    let sources = location.sources();
+    if let Some(command) = location.paths_command() {
+        commands.push(("paths command".to_string(), command.program()));
+    }
+    for source in &sources {
+        if let Source::Command { command, .. } = source {
+            commands.push(("command".to_string(), command.program()));
+        }
+    }
+    let backup_hooks = location.backup_hooks(backup_opts.hooks());
+    let forget_opts = crate::get_forget_options(location_name, config);
+    let check_opts = crate::get_check_options(location_name, config);
+    let copy_opts = crate::get_copy_options(location_name, config);
+    let hooks = [
+        ("backup", &backup_hooks),
+        ("forget", forget_opts.hooks()),
+        ("check", check_opts.hooks()),
+        ("copy", copy_opts.hooks()),
+    ];
+    for (operation, hooks) in hooks {
+        for (name, hook) in hooks.all() {
+            let program = hook.command().program(config.shell());
+            commands.push((format!("{name} hook of the {operation}"), program));
+        }
+    }
+    for (command, program) in commands {
+        if !program_exists(program) {
+            problems.push(format!(
+                "Program {program} of the {command} cannot be found."
+            ));
+        }
+    }
+    problems
+}
+
+/// Files of the backup options that must exist.
+fn file_problems(options: &BackupOptions) -> Vec<String> {
+    let files = [
+        ("exclude-file", options.exclude_file()),
+        ("iexclude-file", options.iexclude_file()),
+        ("files-from", options.files_from()),
+        ("files-from-verbatim", options.files_from_verbatim()),
+        ("files-from-raw", options.files_from_raw()),
+    ];
+    files
+        .into_iter()
+        .flat_map(|(option, files)| files.iter().map(move |file| (option, file)))
+        .filter(|(_, file)| !file.is_file())
+        .map(|(option, file)| format!("File {} of {option} does not exist.", file.display()))
+        .collect()
+}
+
+fn readable(path: &Path) -> std::io::Result<()> {
+    if path.is_dir() {
+        std::fs::read_dir(path).map(|_| ())
+    } else {
+        std::fs::File::open(path).map(|_| ())
+    }
+}
+
+/// Whether the program exists, which is either a path itself or looked up in
+/// the directories of `PATH`.
+fn program_exists(program: &str) -> bool {
+    let path = Path::new(program);
+    if path.components().count() > 1 {
+        return path.is_file();
+    }
+    let extensions: Vec<String> = if cfg!(windows) {
+        std::env::var("PATHEXT")
+            .unwrap_or_else(|_| ".EXE;.CMD;.BAT;.COM".to_string())
+            .split(';')
+            .map(str::to_string)
+            .chain(std::iter::once(String::new()))
+            .collect()
+    } else {
+        vec![String::new()]
+    };
+    let dirs = std::env::var_os("PATH").unwrap_or_default();
+    std::env::split_paths(&dirs).any(|dir| {
+        extensions
+            .iter()
+            .any(|ext| dir.join(format!("{program}{ext}")).is_file())
+    })
+}
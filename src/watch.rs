@@ -0,0 +1,147 @@
+use crate::cli::{BackupArgs, WatchArgs};
+use crate::config::{Config, LocationRepo, Name};
+use crate::print_log;
+use crate::signal;
+use ::notify::{EventKind, RecursiveMode, Watcher};
+use anyhow::{Result, anyhow, bail};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::mpsc::{RecvTimeoutError, channel};
+use std::time::{Duration, Instant};
+use tracing::Level;
+
+/// The longest time to wait for file system events at once, so that
+/// interruptions are noticed.
+const MAX_WAIT: Duration = Duration::from_secs(1);
+
+/// The change state of a watched location.
+struct State {
+    /// Canonical paths of the location.
+    paths: Vec<PathBuf>,
+    /// The time of the last change that has not been backed up yet.
+    changed: Option<Instant>,
+    /// The start time of the last backup.
+    last_backup: Option<Instant>,
+}
+
+impl State {
+    /// The time at which the next backup is due, if there are changes.
+    fn due(&self, quiet_period: Duration, min_interval: Duration) -> Option<Instant> {
+        let changed = self.changed?;
+        let earliest = self.last_backup.map_or(changed, |last| last + min_interval);
+        Some((changed + quiet_period).max(earliest))
+    }
+}
+
+/// Watches the paths of the selected locations and runs a backup of a location
+/// whenever its files have changed. Runs until the process is interrupted.
+pub fn run(config: &Config, args: &WatchArgs) -> Result<()> {
+    let quiet_period: Duration = config.watch().quiet_period().into();
+    let min_interval: Duration = config.watch().min_interval().into();
+
+    let selection = crate::resolve_selection(args.selected_locations(), config)?;
+    if selection.is_empty() {
+        bail!("No location to watch.");
+    }
+
+    let (tx, rx) = channel();
+    let mut watcher = ::notify::recommended_watcher(tx)
+        .map_err(|e| anyhow!("Failed to initialize file system watcher: {e}"))?;
+
+    let mut states = HashMap::new();
+    for location_name in selection.keys() {
+        let location = &config.locations()[location_name];
+        let mut paths = Vec::new();
+        for path in location.paths() {
+            watcher
+                .watch(path, RecursiveMode::Recursive)
+                .map_err(|e| anyhow!("Failed to watch {}: {e}", path.display()))?;
+            paths.push(std::fs::canonicalize(path).unwrap_or_else(|_| path.clone()));
+        }
+        states.insert(
+            location_name,
+            State {
+                paths,
+                changed: None,
+                last_backup: None,
+            },
+        );
+    }
+
+    print_log!(
+        Level::INFO,
+        "Watching {} location(s) for changes ...",
+        states.len()
+    );
+
+    while !signal::interrupted() {
+        let now = Instant::now();
+        let timeout = states
+            .values()
+            .filter_map(|s| s.due(quiet_period, min_interval))
+            .min()
+            .map_or(MAX_WAIT, |due| due.saturating_duration_since(now))
+            .min(MAX_WAIT);
+
+        match rx.recv_timeout(timeout) {
+            Ok(Ok(event)) => {
+                if matches!(
+                    event.kind,
+                    EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)
+                ) {
+                    let now = Instant::now();
+                    for state in states.values_mut() {
+                        if event
+                            .paths
+                            .iter()
+                            .any(|p| state.paths.iter().any(|l| p.starts_with(l)))
+                        {
+                            state.changed = Some(now);
+                        }
+                    }
+                }
+            }
+            Ok(Err(err)) => print_log!(Level::WARN, "File system watcher failed: {err}"),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => bail!("File system watcher stopped."),
+        }
+
+        let now = Instant::now();
+        for (location_name, state) in &mut states {
+            if state
+                .due(quiet_period, min_interval)
+                .is_none_or(|due| due > now)
+            {
+                continue;
+            }
+            state.changed = None;
+            state.last_backup = Some(now);
+
+            print_log!(Level::INFO, "Files of location {location_name} changed.");
+            let args = BackupArgs::new(location_selection(args, location_name), false);
+            if let Err(err) = crate::with_report("Backup", |r| crate::backup(config, &args, r)) {
+                print_log!(
+                    Level::ERROR,
+                    "Backup of location {location_name} failed: {err}"
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// The selected repositories of a location.
+fn location_selection(args: &WatchArgs, location_name: &Name) -> Vec<LocationRepo> {
+    let selection: Vec<_> = args
+        .selected_locations()
+        .iter()
+        .filter(|s| s.location() == location_name)
+        .cloned()
+        .collect();
+    if selection.is_empty() {
+        vec![location_name.clone().into()]
+    } else {
+        selection
+    }
+}